@@ -28,6 +28,9 @@
             libGL
             fontconfig
 
+            # alarm sound
+            alsa-lib
+
             # wayland libraries
             wayland
 
@@ -0,0 +1,302 @@
+use std::{collections::HashMap, time::{Duration, Instant}};
+
+use chrono::{DateTime, Local};
+use log::warn;
+use rodio::{source::SineWave, OutputStream, Sink, Source};
+use serde::{Deserialize, Serialize};
+
+use crate::{data::{ReadConfidence, SensedData}, expression::{ComputedChannel, Expression}, schema::TelemetrySchema, sources::{DataSource, SourceId, Sources}};
+
+/// Records at the end of a session that channels are evaluated over. History functions such as
+/// `avg(x, n)` see no further back than this.
+const HISTORY: usize = 200;
+
+/// Most entries kept in the alarm log
+const LOG_LENGTH: usize = 500;
+
+/// What raises an alarm, checked against the latest record of a session
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum AlarmCondition {
+    Above { channel: String, threshold: f64 },
+    Below { channel: String, threshold: f64 },
+    /// The channel changing faster than this many units per second, either way
+    RateAbove { channel: String, rate: f64 },
+    /// Nothing recieved for this many seconds. Only live sources are checked.
+    NoPacket { seconds: f64 },
+    /// The GPS position is missing
+    GpsLost,
+    /// The accelerometer or gyroscope reading is [`ReadConfidence::Unreliable`]
+    Unreliable,
+}
+
+impl AlarmCondition {
+    /// Names shown in the rule editor, in the order of [`AlarmCondition::example`]
+    pub const KINDS: [&'static str; 6] = ["Above", "Below", "Rate of change", "No packet", "GPS lost", "Unreliable reading"];
+
+    /// A condition of each kind, with default parameters
+    pub fn example(kind: usize) -> AlarmCondition {
+        match kind {
+            0 => AlarmCondition::Above { channel: "temperature".to_owned(), threshold: 50.0 },
+            1 => AlarmCondition::Below { channel: "pressure".to_owned(), threshold: 80000.0 },
+            2 => AlarmCondition::RateAbove { channel: "pressure".to_owned(), rate: 500.0 },
+            3 => AlarmCondition::NoPacket { seconds: 5.0 },
+            4 => AlarmCondition::GpsLost,
+            _ => AlarmCondition::Unreliable,
+        }
+    }
+
+    pub fn kind(&self) -> usize {
+        match self {
+            AlarmCondition::Above { .. } => 0,
+            AlarmCondition::Below { .. } => 1,
+            AlarmCondition::RateAbove { .. } => 2,
+            AlarmCondition::NoPacket { .. } => 3,
+            AlarmCondition::GpsLost => 4,
+            AlarmCondition::Unreliable => 5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AlarmRule {
+    pub name: String,
+    pub enabled: bool,
+    pub condition: AlarmCondition,
+    /// Whether raising the alarm plays [`play_alert`]
+    pub sound: bool,
+}
+
+/// Why a rule is raised, or `None` if it isn't. `silence` is the time since the last packet of a
+/// live source. `channel` may name a computed channel, or be any expression over the schema fields.
+pub fn check_condition(condition: &AlarmCondition, schema: &TelemetrySchema, channels: &[ComputedChannel], session: &[SensedData], silence: Option<Duration>) -> Option<String> {
+    let latest = session.last();
+
+    let values = |channel: &str| {
+        let tail = &session[session.len().saturating_sub(HISTORY)..];
+        let values = match channels.iter().find(|c| c.name == channel) {
+            Some(computed) => computed.evaluate(schema, tail),
+            None => Expression::parse(channel).and_then(|expression| expression.evaluate(schema, tail)),
+        };
+        values.ok().map(|values| (tail, values))
+    };
+
+    match condition {
+        AlarmCondition::Above { channel, threshold } => {
+            let value = *values(channel)?.1.last()?;
+            (value > *threshold).then(|| format!("{channel} is {value:.2}, above {threshold}"))
+        },
+        AlarmCondition::Below { channel, threshold } => {
+            let value = *values(channel)?.1.last()?;
+            (value < *threshold).then(|| format!("{channel} is {value:.2}, below {threshold}"))
+        },
+        AlarmCondition::RateAbove { channel, rate } => {
+            let (tail, values) = values(channel)?;
+            let [.., (t0, v0), (t1, v1)] = tail.iter().zip(values).filter(|(_, v)| !v.is_nan()).collect::<Vec<_>>()[..] else {
+                return None;
+            };
+            let dt = t1.timestamp().checked_sub(t0.timestamp()).filter(|dt| *dt > 0)? as f64 / 1_000_000.0;
+            let change = (v1 - v0) / dt;
+            (change.abs() > *rate).then(|| format!("{channel} changing by {change:+.2}/s"))
+        },
+        AlarmCondition::NoPacket { seconds } => {
+            let silence = silence?.as_secs_f64();
+            (silence > *seconds).then(|| format!("Nothing recieved for {silence:.0} s"))
+        },
+        AlarmCondition::GpsLost => {
+            latest.filter(|s| s.gps_position.iter().any(|p| p.is_nan())).map(|_| "No GPS position".to_owned())
+        },
+        AlarmCondition::Unreliable => {
+            let latest = latest?;
+            match (latest.acceleration_confidence, latest.gyroscope_confidence) {
+                (ReadConfidence::Unreliable, ReadConfidence::Unreliable) => Some("Accelerometer and gyroscope unreliable".to_owned()),
+                (ReadConfidence::Unreliable, _) => Some("Accelerometer unreliable".to_owned()),
+                (_, ReadConfidence::Unreliable) => Some("Gyroscope unreliable".to_owned()),
+                _ => None,
+            }
+        },
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AlarmLogEntry {
+    pub time: DateTime<Local>,
+    pub source: String,
+    pub rule: String,
+    /// Why the alarm was raised, or `None` when it cleared
+    pub message: Option<String>,
+}
+
+/// Watches the sources for the alarm rules, remembering which alarms are raised
+#[derive(Default)]
+pub struct AlarmMonitor {
+    /// Messages of the raised alarms, by rule and source
+    raised: HashMap<(usize, SourceId), String>,
+    /// Packet count of each source and when it last changed
+    last_packet: HashMap<SourceId, (usize, Instant)>,
+    log: Vec<AlarmLogEntry>,
+}
+
+impl AlarmMonitor {
+    /// Checks the current session of every source. Returns whether a newly raised alarm asks for a sound.
+    pub fn check(&mut self, rules: &[AlarmRule], channels: &[ComputedChannel], sources: &Sources, now: Instant) -> bool {
+        let mut sound = false;
+
+        for source in sources.iter() {
+            let lock = source.data_source.get_data_lock();
+            let Some(data) = source.data_source.get_data(&lock) else {
+                continue;
+            };
+            let session = data.sessions().get(source.current_session).map_or(&[][..], |s| s.as_slice());
+
+            let packets = data.sessions().iter().map(|s| s.len()).sum::<usize>() + data.buffered_frames() + data.rejected_count();
+            let last_packet = self.last_packet.entry(source.id).or_insert((packets, now));
+            if last_packet.0 != packets {
+                *last_packet = (packets, now);
+            }
+            let live = match &source.data_source {
+                DataSource::Live { .. } => true,
+                DataSource::Merged { merger } => merger.inputs()
+                    .filter_map(|id| sources.get(id))
+                    .any(|input| matches!(input.data_source, DataSource::Live { .. })),
+                _ => false,
+            };
+            let silence = live.then(|| now.duration_since(last_packet.1));
+
+            for (i, rule) in rules.iter().enumerate() {
+                let message = rule.enabled
+                    .then(|| check_condition(&rule.condition, data.schema(), channels, session, silence))
+                    .flatten();
+
+                match (message, self.raised.contains_key(&(i, source.id))) {
+                    (Some(message), raised) => {
+                        if !raised {
+                            sound |= rule.sound;
+                            self.push_log(&source.name, rule, Some(message.clone()));
+                        }
+                        self.raised.insert((i, source.id), message);
+                    },
+                    (None, true) => {
+                        self.raised.remove(&(i, source.id));
+                        self.push_log(&source.name, rule, None);
+                    },
+                    (None, false) => {},
+                }
+            }
+        }
+
+        self.raised.retain(|(i, id), _| *i < rules.len() && sources.get(*id).is_some());
+
+        sound
+    }
+
+    fn push_log(&mut self, source: &str, rule: &AlarmRule, message: Option<String>) {
+        self.log.push(AlarmLogEntry { time: Local::now(), source: source.to_owned(), rule: rule.name.clone(), message });
+        if self.log.len() > LOG_LENGTH {
+            self.log.remove(0);
+        }
+    }
+
+    /// Forgets the raised alarms, for when the rules were edited. They are raised again on the next check.
+    pub fn reset(&mut self) {
+        self.raised.clear();
+    }
+
+    pub fn raised_count(&self) -> usize {
+        self.raised.len()
+    }
+
+    /// Messages of the raised alarms
+    pub fn raised(&self) -> impl Iterator<Item = &String> {
+        self.raised.values()
+    }
+
+    /// Raisings and clearings of alarms, oldest first
+    pub fn log(&self) -> &[AlarmLogEntry] {
+        &self.log
+    }
+
+    pub fn clear_log(&mut self) {
+        self.log.clear();
+    }
+}
+
+/// Plays a short falling two-tone chime on the default audio output, on a thread of its own
+pub fn play_alert() {
+    std::thread::spawn(|| {
+        // The stream stops playing once dropped, so it is kept until the chime ends
+        let (_stream, handle) = match OutputStream::try_default() {
+            Ok(output) => output,
+            Err(e) => {
+                warn!("No audio output for the alarm sound: {e}");
+                return;
+            },
+        };
+        let sink = match Sink::try_new(&handle) {
+            Ok(sink) => sink,
+            Err(e) => {
+                warn!("Failed to play the alarm sound: {e}");
+                return;
+            },
+        };
+
+        for frequency in [880.0, 660.0] {
+            sink.append(SineWave::new(frequency).take_duration(Duration::from_millis(180)).amplify(0.3));
+        }
+        sink.sleep_until_end();
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(uptime: u32, temperature: f32) -> SensedData {
+        SensedData {
+            uptime,
+            temperature,
+            gps_position: [52.0, 21.0],
+            acceleration_confidence: ReadConfidence::High,
+            gyroscope_confidence: ReadConfidence::High,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_thresholds() {
+        let schema = TelemetrySchema::default();
+        let session = [record(0, 20.0), record(500, 21.0), record(1000, 26.0)];
+        let check = |condition: AlarmCondition| check_condition(&condition, &schema, &[], &session, None);
+
+        assert!(check(AlarmCondition::Above { channel: "temperature".to_owned(), threshold: 25.0 }).is_some());
+        assert!(check(AlarmCondition::Below { channel: "temperature".to_owned(), threshold: 25.0 }).is_none());
+        assert!(check(AlarmCondition::Above { channel: "temperature * 2".to_owned(), threshold: 51.0 }).is_some());
+        assert!(check(AlarmCondition::Above { channel: "humidity".to_owned(), threshold: 0.0 }).is_none());
+
+        // 5 °C in half a second
+        assert!(check(AlarmCondition::RateAbove { channel: "temperature".to_owned(), rate: 9.0 }).is_some());
+        assert!(check(AlarmCondition::RateAbove { channel: "temperature".to_owned(), rate: 11.0 }).is_none());
+
+        let channels = [ComputedChannel { name: "warming".to_owned(), unit: String::new(), expression: "d(temperature)/dt".to_owned() }];
+        let warming = AlarmCondition::Above { channel: "warming".to_owned(), threshold: 9.0 };
+        assert!(check_condition(&warming, &schema, &channels, &session, None).is_some());
+    }
+
+    #[test]
+    fn test_link_and_sensors() {
+        let schema = TelemetrySchema::default();
+        let mut session = vec![record(0, 20.0)];
+        let check = |condition: AlarmCondition, session: &[SensedData], silence: Option<Duration>| check_condition(&condition, &schema, &[], session, silence);
+
+        assert!(check(AlarmCondition::NoPacket { seconds: 5.0 }, &session, Some(Duration::from_secs(6))).is_some());
+        assert!(check(AlarmCondition::NoPacket { seconds: 5.0 }, &session, Some(Duration::from_secs(4))).is_none());
+        assert!(check(AlarmCondition::NoPacket { seconds: 5.0 }, &session, None).is_none());
+
+        assert!(check(AlarmCondition::GpsLost, &session, None).is_none());
+        assert!(check(AlarmCondition::Unreliable, &session, None).is_none());
+
+        session.push(SensedData { gyroscope_confidence: ReadConfidence::Unreliable, ..record(100, 20.0) });
+        session[1].gps_position[1] = f64::NAN;
+        assert!(check(AlarmCondition::GpsLost, &session, None).is_some());
+        assert_eq!(check(AlarmCondition::Unreliable, &session, None).as_deref(), Some("Gyroscope unreliable"));
+    }
+}
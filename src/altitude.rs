@@ -0,0 +1,267 @@
+use serde::{Deserialize, Serialize};
+
+use crate::data::SensedData;
+
+/// Specific gas constant of dry air, J/(kg·K)
+const GAS_CONSTANT: f64 = 287.05;
+/// Standard gravity, m/s²
+const GRAVITY: f64 = 9.80665;
+/// Temperature used when the probe doesn't measure one, °C
+const STANDARD_TEMPERATURE: f64 = 15.0;
+
+/// Where the pressure at the launch site, taken as zero altitude, comes from
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ReferencePressure {
+    /// Mean pressure of the first records of the session
+    FirstRecords(usize),
+    /// A pressure in Pa entered by hand
+    Manual(f64),
+}
+
+/// How the barometric altitude and vertical speed are derived
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AltitudeSettings {
+    pub reference: ReferencePressure,
+    /// Whether the measured temperature is used rather than a standard 15 °C
+    pub use_temperature: bool,
+    /// Length of the window the vertical speed is fitted over, in seconds
+    pub smoothing_s: f64,
+}
+
+impl Default for AltitudeSettings {
+    fn default() -> Self {
+        Self { reference: ReferencePressure::FirstRecords(10), use_temperature: true, smoothing_s: 2.0 }
+    }
+}
+
+/// Channels derived from the pressure of a session, one value per record. Values are NaN where
+/// the pressure is missing.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Altitude {
+    /// Pressure at zero altitude in Pa, NaN if it couldn't be found
+    pub reference: f64,
+    /// Metres above the launch site
+    pub altitude: Vec<f64>,
+    /// Metres per second, positive when climbing
+    pub vertical_speed: Vec<f64>,
+}
+
+impl Altitude {
+    pub fn compute(session: &[SensedData], settings: &AltitudeSettings) -> Altitude {
+        let reference = reference_pressure(session, settings);
+        let ground_temperature = ground_temperature(session, settings);
+
+        let altitude: Vec<f64> = session.iter()
+            .map(|s| altitude_of(s, reference, ground_temperature, settings))
+            .collect();
+
+        let vertical_speed = fit_slopes(session, &altitude, settings.smoothing_s);
+
+        Altitude { reference, altitude, vertical_speed }
+    }
+}
+
+/// Keeps the [`Altitude`] of a session up to date. Records added at the end only cost their
+/// own computation; any other change of the session computes it again.
+#[derive(Debug, Clone)]
+pub struct AltitudeTracker {
+    settings: AltitudeSettings,
+    altitude: Altitude,
+    /// Temperature at the launch site the altitudes were computed with
+    ground_temperature: f64,
+    /// Index and timestamp of the last record computed
+    last: Option<(u32, u64)>,
+}
+
+impl AltitudeTracker {
+    pub fn new(settings: AltitudeSettings) -> Self {
+        Self {
+            settings,
+            altitude: Altitude { reference: f64::NAN, altitude: vec![], vertical_speed: vec![] },
+            ground_temperature: f64::NAN,
+            last: None,
+        }
+    }
+
+    pub fn settings(&self) -> &AltitudeSettings {
+        &self.settings
+    }
+
+    pub fn altitude(&self) -> &Altitude {
+        &self.altitude
+    }
+
+    pub fn update(&mut self, session: &[SensedData]) -> &Altitude {
+        let settings = &self.settings;
+        let computed = self.altitude.altitude.len();
+
+        // The reference settles once the first records are in, and the ground temperature once one is measured
+        let reference = reference_pressure(session, settings);
+        let ground_temperature = ground_temperature(session, settings);
+        let same = |a: f64, b: f64| a == b || (a.is_nan() && b.is_nan());
+
+        let appended = session.len() >= computed
+            && self.last == computed.checked_sub(1).map(|i| (session[i].index, session[i].timestamp()))
+            && same(reference, self.altitude.reference)
+            && same(ground_temperature, self.ground_temperature);
+
+        if !appended {
+            self.altitude = Altitude::compute(session, settings);
+            self.ground_temperature = ground_temperature;
+        } else if session.len() > computed {
+            let altitude = &mut self.altitude;
+            altitude.altitude.extend(session[computed..].iter().map(|s| altitude_of(s, reference, ground_temperature, settings)));
+
+            // The slopes of the new records are fitted again from the start of the window before the first one.
+            // Going one record too far back does no harm, the fit leaves it out.
+            let window = (settings.smoothing_s * 1_000_000.0) as u64;
+            let first_new = session[computed].timestamp();
+            let mut start = computed;
+            while start > 0 && session[start].timestamp() + window >= first_new {
+                start -= 1;
+            }
+
+            let slopes = fit_slopes(&session[start..], &altitude.altitude[start..], settings.smoothing_s);
+            altitude.vertical_speed.extend_from_slice(&slopes[computed - start..]);
+        }
+
+        self.last = session.last().map(|s| (s.index, s.timestamp()));
+        &self.altitude
+    }
+}
+
+fn reference_pressure(session: &[SensedData], settings: &AltitudeSettings) -> f64 {
+    match settings.reference {
+        ReferencePressure::FirstRecords(count) => mean(session.iter().take(count.max(1)).map(|s| s.pressure as f64)),
+        ReferencePressure::Manual(pressure) => pressure,
+    }
+}
+
+fn temperature(s: &SensedData, settings: &AltitudeSettings) -> f64 {
+    match s.temperature as f64 {
+        t if settings.use_temperature && !t.is_nan() => t,
+        _ => STANDARD_TEMPERATURE,
+    }
+}
+
+fn ground_temperature(session: &[SensedData], settings: &AltitudeSettings) -> f64 {
+    session.iter()
+        .find(|s| !s.temperature.is_nan())
+        .map_or(STANDARD_TEMPERATURE, |s| temperature(s, settings))
+}
+
+fn altitude_of(s: &SensedData, reference: f64, ground_temperature: f64, settings: &AltitudeSettings) -> f64 {
+    // The layer between the launch site and the probe is taken to have the mean temperature of its ends
+    let mean_temperature = (ground_temperature + temperature(s, settings)) / 2.0 + 273.15;
+    GAS_CONSTANT * mean_temperature / GRAVITY * (reference / s.pressure as f64).ln()
+}
+
+fn mean(values: impl Iterator<Item = f64>) -> f64 {
+    let (sum, count) = values.filter(|v| !v.is_nan()).fold((0.0, 0), |(sum, count), v| (sum + v, count + 1));
+    if count == 0 { f64::NAN } else { sum / count as f64 }
+}
+
+/// Slope of a least squares line fitted to the values of the last `window` seconds before each record
+fn fit_slopes(session: &[SensedData], values: &[f64], window: f64) -> Vec<f64> {
+    let start = session.first().map_or(0, |s| s.timestamp());
+    let time = |i: usize| session[i].timestamp().saturating_sub(start) as f64 / 1_000_000.0;
+
+    let valid: Vec<usize> = (0..values.len()).filter(|&i| !values[i].is_nan()).collect();
+    let mut slopes = vec![f64::NAN; values.len()];
+
+    // Sums of t, v, t² and t·v over the valid records in the window
+    let (mut st, mut sv, mut stt, mut stv) = (0.0, 0.0, 0.0, 0.0);
+    let mut first = 0;
+
+    for (last, &i) in valid.iter().enumerate() {
+        let (t, v) = (time(i), values[i]);
+        st += t;
+        sv += v;
+        stt += t * t;
+        stv += t * v;
+
+        while time(valid[first]) < t - window {
+            let (t, v) = (time(valid[first]), values[valid[first]]);
+            st -= t;
+            sv -= v;
+            stt -= t * t;
+            stv -= t * v;
+            first += 1;
+        }
+
+        let n = (last - first + 1) as f64;
+        let spread = n * stt - st * st;
+        if n >= 2.0 && spread > 0.0 {
+            slopes[i] = (n * stv - st * sv) / spread;
+        }
+    }
+
+    slopes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(uptime: u32, pressure: f32) -> SensedData {
+        SensedData { uptime, pressure, temperature: 15.0, ..Default::default() }
+    }
+
+    #[test]
+    fn test_altitude() {
+        let session = [record(0, 101325.0), record(100, 101325.0), record(200, 89876.0), record(300, f32::NAN)];
+        let altitude = Altitude::compute(&session, &AltitudeSettings { reference: ReferencePressure::FirstRecords(2), ..Default::default() });
+
+        assert_eq!(altitude.reference, 101325.0);
+        assert_eq!(altitude.altitude[0], 0.0);
+        // About 1000 m in the standard atmosphere, a little more with the isothermal layer
+        assert!((altitude.altitude[2] - 1010.0).abs() < 5.0);
+        assert!(altitude.altitude[3].is_nan());
+
+        let manual = Altitude::compute(&session, &AltitudeSettings { reference: ReferencePressure::Manual(89876.0), ..Default::default() });
+        assert!(manual.altitude[0] < -1000.0);
+        assert_eq!(manual.altitude[2], 0.0);
+    }
+
+    #[test]
+    fn test_vertical_speed() {
+        // Climbing 5 m every 100 ms, with a missing record
+        let session: Vec<SensedData> = (0..20).map(|i| record(i * 100, 0.0)).collect();
+        let values: Vec<f64> = (0..20).map(|i| if i == 7 { f64::NAN } else { i as f64 * 5.0 }).collect();
+
+        let slopes = fit_slopes(&session, &values, 1.0);
+        assert!(slopes[0].is_nan());
+        assert!(slopes[7].is_nan());
+        for slope in &slopes[1..7] {
+            assert!((slope - 50.0).abs() < 1e-6);
+        }
+        assert!((slopes[19] - 50.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_tracker() {
+        // On the ground, then climbing, with a frame that arrives late
+        let session: Vec<SensedData> = (0..30)
+            .map(|i| SensedData { index: i, ..record(i * 100, 101325.0 - (i.saturating_sub(5) * 12) as f32) })
+            .collect();
+        let mut late = session.clone();
+        late.remove(20);
+
+        // A window that doesn't end right on a record, where rounding could decide either way
+        let settings = AltitudeSettings { reference: ReferencePressure::FirstRecords(5), smoothing_s: 0.45, ..Default::default() };
+        let mut tracker = AltitudeTracker::new(settings);
+
+        let close = |a: &Altitude, b: &Altitude| a.reference == b.reference && a.altitude.iter().chain(&a.vertical_speed)
+            .zip(b.altitude.iter().chain(&b.vertical_speed))
+            .all(|(a, b)| (a.is_nan() && b.is_nan()) || (a - b).abs() < 1e-6);
+
+        for end in [3, 8, 9, 20, 29] {
+            let expected = Altitude::compute(&late[..end], &settings);
+            assert!(close(tracker.update(&late[..end]), &expected), "differs after {end} records");
+        }
+
+        // The late frame is put in place, so the session is computed again
+        assert!(close(tracker.update(&session), &Altitude::compute(&session, &settings)));
+    }
+}
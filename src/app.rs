@@ -1,62 +1,100 @@
 
-use std::{fs, io::{BufRead, BufReader, Read}, sync::{atomic::AtomicBool, Arc, Mutex, MutexGuard}, thread, time::{Duration, Instant}};
+use std::{collections::HashMap, fs, io::{self, Write}, path::{Path, PathBuf}, sync::{Arc, Mutex}, time::{Duration, Instant}};
 
-use log::info;
+use egui::Color32;
 
-use crate::{data::MissionData, tabs::{data::{data_tab, DataTabState}, map::{map_tab, MapTabState}, plot::{plot_tab, PlotTabState}}};
+use crate::{alarms::{play_alert, AlarmCondition, AlarmMonitor, AlarmRule}, altitude::{Altitude, AltitudeSettings, AltitudeTracker, ReferencePressure}, capture::{CaptureInfo, CaptureWriter}, console::{console_panel, default_catalog, ConsoleState}, data::{MissionData, Segmentation, SensedData}, events::{timeline_panel, EventDetector, EventSettings, FlightEvent}, export::{computed_columns, export, schema_columns, ExportFormat, ExportSettings}, expression::ComputedChannel, import::{import_window, CsvTable, ImportMapping, ImportWizard, WizardAction}, merge::{Merger, MAX_RECEIVERS}, network::{connect_tcp, listen_tcp, listen_udp, NetworkSettings}, project::{resolve_path, Project, ProjectLog}, reader::{spawn_data_reader_thread, Connection, Reconnect}, replay::{Replay, REPLAY_SPEEDS}, simulator::{Simulator, SimulatorSettings}, schema::{FieldTarget, TelemetrySchema}, serial::{self, serial_settings_ui, PortIdentity, SerialSettings}, sources::{DataSource, LiveSource, SessionView, SourceId, Sources}, tabs::{data::{data_tab, DataTabState}, map::{map_tab, MapTabState}, plot::{plot_tab, PlotTabState}, rejected::{rejected_tab, RejectedTabState}, gaps::{gaps_tab, GapsTabState}}, track::Track};
 
 pub struct TemplateApp {
     current_tab: Tab,
-    data_source: DataSource,
-    current_session: usize,
+    sources: Sources,
+    /// Sources shown by each tab. The data and rejected tabs show only the first one.
+    tab_sources: HashMap<Tab, Vec<SourceId>>,
+
+    /// Layout of the telemetry frames, used for newly opened data sources
+    schema: Arc<TelemetrySchema>,
+    /// File the schema was loaded from, if not the default one
+    schema_path: Option<PathBuf>,
 
     plot_state: PlotTabState,
     data_state: DataTabState,
     map_state: MapTabState,
+    rejected_state: RejectedTabState,
+    gaps_state: GapsTabState,
+
+    console_state: ConsoleState,
+    show_console: bool,
 
     auto_repaint: bool,
+    /// Whether live sources are recorded to disk
+    capture_enabled: bool,
+
+    /// Settings used to open each serial port, by port name
+    serial_settings: HashMap<String, SerialSettings>,
+    /// Port whose settings are being edited, if the dialog is open
+    serial_settings_port: Option<String>,
+    network_settings: NetworkSettings,
+    simulator_settings: SimulatorSettings,
+    /// Sources picked in Sources > Merge sources
+    merge_selection: Vec<SourceId>,
+    /// Rules used to split the data of new sources into sessions
+    segmentation: Segmentation,
+    session_edit: SessionEdit,
+    /// How the barometric altitude and vertical speed are derived from the pressure
+    altitude: AltitudeSettings,
+    /// Altitude of every session shown so far, by source and session
+    altitude_trackers: HashMap<(SourceId, usize), AltitudeTracker>,
+    /// Channels defined by the user as expressions over the telemetry fields
+    channels: Vec<ComputedChannel>,
+    show_channels: bool,
+    event_settings: EventSettings,
+    /// Flight event detection of every session shown so far, by source and session
+    event_detectors: HashMap<(SourceId, usize), EventDetector>,
+    show_timeline: bool,
+    alarm_rules: Vec<AlarmRule>,
+    alarm_monitor: AlarmMonitor,
+    show_alarms: bool,
+    export_settings: ExportSettings,
+    /// The CSV import being set up, if the wizard is open
+    import_wizard: Option<ImportWizard>,
+    import_mappings: Vec<ImportMapping>,
 
     status_message: Option<StatusMessage>
 }
 
-#[derive(Debug, Clone)]
-enum DataSource {
-    File {
-        name: String,
-        data: MissionData
-    },
-    SerialPort {
-        port_name: String,
-        baud_rate: u32,
-
-        data: Arc<Mutex<MissionData>>,
-        cancel_reader: Arc<AtomicBool>
-    },
-    None
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Tab {
+    Data,
+    Plot,
+    Map,
+    Rejected,
+    Gaps
 }
 
-impl DataSource {
-    fn get_data_lock(&self) -> Option<MutexGuard<'_, MissionData>> {
-        match &self {
-            DataSource::SerialPort { data, .. } => Some(data.lock().unwrap()),
-            _ => None,
-        }
-    }
+impl Tab {
+    const ALL: [Tab; 5] = [Tab::Data, Tab::Plot, Tab::Map, Tab::Rejected, Tab::Gaps];
 
-    fn get_data<'a>(&'a self, lock: &'a Option<MutexGuard<'_, MissionData>>) -> Option<&'a MissionData> {
-        match &self {
-            DataSource::File { data, .. } => Some(data),
-            DataSource::SerialPort { .. } => lock.as_ref().map(|v| &**v),
-            DataSource::None => None,
-        }
+    /// Whether the tab can show several sources on top of each other
+    fn shows_many_sources(self) -> bool {
+        matches!(self, Tab::Plot | Tab::Map)
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
-enum Tab {
-    Data,
-    Plot,
-    Map
+/// Inputs of the session menu in the status bar
+#[derive(Debug, Clone, Default)]
+struct SessionEdit {
+    name: String,
+    /// Record of the session to split at, starting from 0
+    split_at: usize,
+}
+
+/// A change to the sessions of a source, picked from the session menu
+#[derive(Debug, Clone)]
+enum SessionAction {
+    Rename(String),
+    Split(usize),
+    MergeWithNext,
+    Delete,
 }
 
 #[derive(Debug, Clone)]
@@ -66,48 +104,152 @@ struct StatusMessage {
     text: String
 }
 
+const SERIAL_SETTINGS_KEY: &str = "serial_settings";
+const NETWORK_SETTINGS_KEY: &str = "network_settings";
+const SIMULATOR_SETTINGS_KEY: &str = "simulator_settings";
+const COMMAND_CATALOG_KEY: &str = "command_catalog";
+const SEGMENTATION_KEY: &str = "segmentation";
+const PLOT_STATE_KEY: &str = "plot_state";
+const MAP_SETTINGS_KEY: &str = "map_settings";
+const DATA_STATE_KEY: &str = "data_state";
+const REJECTED_STATE_KEY: &str = "rejected_state";
+const GAPS_STATE_KEY: &str = "gaps_state";
+const ALTITUDE_SETTINGS_KEY: &str = "altitude_settings";
+const COMPUTED_CHANNELS_KEY: &str = "computed_channels";
+const EVENT_SETTINGS_KEY: &str = "event_settings";
+const ALARM_RULES_KEY: &str = "alarm_rules";
+const EXPORT_SETTINGS_KEY: &str = "export_settings";
+const IMPORT_MAPPINGS_KEY: &str = "import_mappings";
+
+const PROJECT_EXTENSION: &str = "gsproj";
+
 impl TemplateApp {
     /// Called once before the first frame.
     pub fn new(cc: &eframe::CreationContext<'_>) -> Self {
+        let serial_settings = cc.storage
+            .and_then(|storage| eframe::get_value(storage, SERIAL_SETTINGS_KEY))
+            .unwrap_or_default();
+        let network_settings = cc.storage
+            .and_then(|storage| eframe::get_value(storage, NETWORK_SETTINGS_KEY))
+            .unwrap_or_default();
+        let simulator_settings = cc.storage
+            .and_then(|storage| eframe::get_value(storage, SIMULATOR_SETTINGS_KEY))
+            .unwrap_or_default();
+        let command_catalog = cc.storage
+            .and_then(|storage| eframe::get_value(storage, COMMAND_CATALOG_KEY))
+            .unwrap_or_else(default_catalog);
+        let segmentation = cc.storage
+            .and_then(|storage| eframe::get_value(storage, SEGMENTATION_KEY))
+            .unwrap_or_default();
+
+        let mut map_state = MapTabState::new(&cc.egui_ctx);
+        if let Some(settings) = cc.storage.and_then(|storage| eframe::get_value(storage, MAP_SETTINGS_KEY)) {
+            map_state.apply_settings(settings);
+        }
+
         Self {
             current_tab: Tab::Data,
 
-            data_source: DataSource::None,
-            current_session: 0,
+            sources: Sources::default(),
+            tab_sources: HashMap::new(),
 
-            plot_state: PlotTabState::default(),
-            data_state: DataTabState {
-                stick_to_bottom: true
-            },
-            map_state: MapTabState::new(&cc.egui_ctx),
+            schema: Arc::default(),
+            schema_path: None,
+
+            plot_state: cc.storage
+                .and_then(|storage| eframe::get_value(storage, PLOT_STATE_KEY))
+                .unwrap_or_default(),
+            data_state: cc.storage
+                .and_then(|storage| eframe::get_value(storage, DATA_STATE_KEY))
+                .unwrap_or(DataTabState { stick_to_bottom: true }),
+            map_state,
+            rejected_state: cc.storage
+                .and_then(|storage| eframe::get_value(storage, REJECTED_STATE_KEY))
+                .unwrap_or(RejectedTabState { stick_to_bottom: true }),
+            gaps_state: cc.storage
+                .and_then(|storage| eframe::get_value(storage, GAPS_STATE_KEY))
+                .unwrap_or_default(),
+            console_state: ConsoleState::new(command_catalog),
+            show_console: false,
             auto_repaint: true,
+            capture_enabled: true,
+            serial_settings,
+            serial_settings_port: None,
+            network_settings,
+            simulator_settings,
+            merge_selection: vec![],
+            segmentation,
+            session_edit: SessionEdit::default(),
+            altitude: cc.storage
+                .and_then(|storage| eframe::get_value(storage, ALTITUDE_SETTINGS_KEY))
+                .unwrap_or_default(),
+            channels: cc.storage
+                .and_then(|storage| eframe::get_value(storage, COMPUTED_CHANNELS_KEY))
+                .unwrap_or_default(),
+            show_channels: false,
+            altitude_trackers: HashMap::new(),
+            event_settings: cc.storage
+                .and_then(|storage| eframe::get_value(storage, EVENT_SETTINGS_KEY))
+                .unwrap_or_default(),
+            event_detectors: HashMap::new(),
+            show_timeline: false,
+            alarm_rules: cc.storage
+                .and_then(|storage| eframe::get_value(storage, ALARM_RULES_KEY))
+                .unwrap_or_default(),
+            alarm_monitor: AlarmMonitor::default(),
+            show_alarms: false,
+            export_settings: cc.storage
+                .and_then(|storage| eframe::get_value(storage, EXPORT_SETTINGS_KEY))
+                .unwrap_or_default(),
+            import_wizard: None,
+            import_mappings: cc.storage
+                .and_then(|storage| eframe::get_value(storage, IMPORT_MAPPINGS_KEY))
+                .unwrap_or_default(),
             status_message: None
         }
     }
 }
 
-fn spawn_data_reader_thread<T: Read + BufRead + Send + 'static>(mut reader: T, data: Arc<Mutex<MissionData>>) -> Arc<AtomicBool> {
-    let canceller = Arc::new(AtomicBool::new(false));
-    let cloned_canceller = canceller.clone();
-    thread::spawn(move || {
-        info!("Data reader thread spawned");
-        loop {
-            let mut string = String::new();
-            let _ = reader.read_line(&mut string);
+impl eframe::App for TemplateApp {
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        eframe::set_value(storage, SERIAL_SETTINGS_KEY, &self.serial_settings);
+        eframe::set_value(storage, NETWORK_SETTINGS_KEY, &self.network_settings);
+        eframe::set_value(storage, SIMULATOR_SETTINGS_KEY, &self.simulator_settings);
+        eframe::set_value(storage, COMMAND_CATALOG_KEY, &self.console_state.catalog);
+        eframe::set_value(storage, SEGMENTATION_KEY, &self.segmentation);
+        eframe::set_value(storage, ALTITUDE_SETTINGS_KEY, &self.altitude);
+        eframe::set_value(storage, COMPUTED_CHANNELS_KEY, &self.channels);
+        eframe::set_value(storage, EVENT_SETTINGS_KEY, &self.event_settings);
+        eframe::set_value(storage, ALARM_RULES_KEY, &self.alarm_rules);
+        eframe::set_value(storage, PLOT_STATE_KEY, &self.plot_state);
+        eframe::set_value(storage, MAP_SETTINGS_KEY, &self.map_state.settings());
+        eframe::set_value(storage, DATA_STATE_KEY, &self.data_state);
+        eframe::set_value(storage, REJECTED_STATE_KEY, &self.rejected_state);
+        eframe::set_value(storage, GAPS_STATE_KEY, &self.gaps_state);
+        eframe::set_value(storage, EXPORT_SETTINGS_KEY, &self.export_settings);
+        eframe::set_value(storage, IMPORT_MAPPINGS_KEY, &self.import_mappings);
+    }
 
-            let _ = data.lock().unwrap().parse_line(&string);
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
 
-            if canceller.load(std::sync::atomic::Ordering::Relaxed) {
-                info!("Cancel order detected; ending thread.");
-                return;
+        let now = Instant::now();
+        for source in self.sources.iter_mut() {
+            if source.data_source.tick(now) {
+                ctx.request_repaint();
             }
         }
-    });
-    cloned_canceller
-}
+        self.sources.update_merged();
 
-impl eframe::App for TemplateApp {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        if self.alarm_monitor.check(&self.alarm_rules, &self.channels, &self.sources, now) {
+            play_alert();
+        }
+        if self.alarm_monitor.raised_count() > 0 {
+            // Keeps the status bar flashing
+            ctx.request_repaint_after(Duration::from_millis(250));
+        } else if self.alarm_rules.iter().any(|rule| rule.enabled) {
+            // Alarms about silent sources are raised even if nothing else repaints
+            ctx.request_repaint_after(Duration::from_secs(1));
+        }
 
         if self.auto_repaint {
             ctx.request_repaint();
@@ -118,47 +260,120 @@ impl eframe::App for TemplateApp {
                 ui.menu_button("File", |ui| {
                     if ui.button("Import log").clicked() {
                         if let Some(path) = rfd::FileDialog::new().pick_file() {
-                            let name = path.file_name()
-                                .expect("path should always point to a file")
-                                .to_str().expect("filename should be valid unicode")
-                                .to_owned();
+                            self.open_log(path, false);
+                            ui.close();
+                        }
+                    }
+
+                    if ui.button("Replay log").on_hover_text("Play a log back in real time, as if it were being recieved").clicked() {
+                        if let Some(path) = rfd::FileDialog::new().pick_file() {
+                            self.open_log(path, true);
+                            ui.close();
+                        }
+                    }
+
+                    if ui.button("Import CSV").on_hover_text("Import a table with a header row, mapping its columns to telemetry fields").clicked() {
+                        if let Some(path) = rfd::FileDialog::new().add_filter("Delimited text", &["csv", "tsv", "txt"]).pick_file() {
+                            match fs::read(&path).map(|bytes| CsvTable::parse(&String::from_utf8_lossy(&bytes))) {
+                                Err(_) => self.set_short_status("Unable to read file".to_owned()),
+                                Ok(None) => self.set_short_status("The file has no header row".to_owned()),
+                                Ok(Some(table)) => self.import_wizard = Some(ImportWizard::new(path, table, &self.import_mappings, &self.schema)),
+                            }
+                            ui.close();
+                        }
+                    }
 
-                            match fs::read_to_string(path) {
+                    if ui.button("Import GPS track").on_hover_text("Show a GPX file or NMEA log of another receiver on the map, for reference").clicked() {
+                        if let Some(path) = rfd::FileDialog::new().add_filter("GPS track", &["gpx", "nmea", "txt", "log"]).pick_file() {
+                            let name = path.file_name().map_or(String::new(), |name| name.to_string_lossy().into_owned());
+                            match fs::read(&path).map(|bytes| Track::parse(name, &String::from_utf8_lossy(&bytes))) {
                                 Err(_) => self.set_short_status("Unable to read file".to_owned()),
-                                Ok(text) => {
-                                    self.change_data_source(DataSource::File {
-                                        name,
-                                        data: MissionData::from_log(&text)
-                                    });
+                                Ok(track) if track.points.is_empty() => self.set_short_status("No positions found in the file".to_owned()),
+                                Ok(track) => {
+                                    self.set_short_status(format!("Loaded {} positions from {}", track.points.len(), track.name));
+                                    self.map_state.set_reference(Some(track));
                                 },
                             }
+                            ui.close();
+                        }
+                    }
+
+                    ui.separator();
+
+                    if ui.button("Open project").clicked() {
+                        if let Some(path) = rfd::FileDialog::new().add_filter("Project", &[PROJECT_EXTENSION]).pick_file() {
+                            self.open_project(&path);
+                            ui.close();
+                        }
+                    }
 
+                    if ui.button("Save project").on_hover_text("Save the open logs, selected sessions, ground station, plot setup and notes").clicked() {
+                        if let Some(path) = rfd::FileDialog::new().add_filter("Project", &[PROJECT_EXTENSION]).set_file_name(format!("flight.{PROJECT_EXTENSION}")).save_file() {
+                            self.save_project(&path);
                             ui.close();
                         }
                     }
 
+                    ui.menu_button("Export", |ui| self.export_menu(ui));
+
+                    ui.separator();
+
+                    ui.menu_button("Telemetry schema", |ui| {
+                        let schema_name = self.schema_path.as_ref()
+                            .and_then(|path| path.file_name())
+                            .map(|name| name.to_string_lossy().into_owned());
+                        ui.label(format!("Current: {}", schema_name.as_deref().unwrap_or("Default")));
+
+                        if ui.button("Load schema file").clicked() {
+                            if let Some(path) = rfd::FileDialog::new().pick_file() {
+                                if self.load_schema(path) {
+                                    self.set_short_status("Schema loaded; it will be used for newly opened data".to_owned());
+                                }
+                                ui.close();
+                            }
+                        }
+
+                        if ui.add_enabled(self.schema_path.is_some(), egui::Button::new("Use default schema")).clicked() {
+                            self.schema = Arc::default();
+                            self.schema_path = None;
+                            ui.close();
+                        }
+                    });
+
+                    ui.separator();
+
                     if ui.button("Quit").clicked() {
                         ctx.send_viewport_cmd(egui::ViewportCommand::Close);
                     }
                 });
 
                 ui.menu_button("Connection", |ui| {
-                    ui.menu_button("Port", |ui| {
-                        if ui.radio(! matches!(self.data_source, DataSource::SerialPort {..}), "None").clicked() {
-                            self.change_data_source(DataSource::None);
+                    ui.checkbox(&mut self.capture_enabled, "Record raw data to disk")
+                        .on_hover_text("Applies to newly opened connections");
+
+                    ui.separator();
+
+                    let active_live = self.sources.active()
+                        .filter(|source| matches!(source.data_source, DataSource::Live {..}))
+                        .map(|source| source.id);
+                    if ui.add_enabled(active_live.is_some(), egui::Button::new("Disconnect")).on_hover_text("Closes the active source").clicked() {
+                        if let Some(id) = active_live {
+                            self.close_source(id);
                         }
-                    
+                        ui.close();
+                    }
+
+                    ui.menu_button("Port", |ui| {
+                        ui.weak("Click an open port to close it");
+
                         if let Ok(ports) = serialport::available_ports() {
-                            for port in ports.iter() {
-                                let name = port.port_name.to_owned();
-                                let port_chosen = {
-                                    match &self.data_source {
-                                        DataSource::SerialPort { port_name, .. } => *port_name == name,
-                                        _ => false,
-                                    }
-                                };
+                            for port_info in ports.iter() {
+                                let name = port_info.port_name.to_owned();
+                                let open_source = self.sources.iter()
+                                    .find(|source| matches!(&source.data_source, DataSource::Live { kind: LiveSource::SerialPort { port_name, .. }, .. } if *port_name == name))
+                                    .map(|source| source.id);
 
-                                if ui.radio(port_chosen, match &port.port_type {
+                                if ui.radio(open_source.is_some(), match &port_info.port_type {
                                     serialport::SerialPortType::UsbPort(info) => {
                                         format!(
                                             "{} ({}; {})", 
@@ -169,30 +384,63 @@ impl eframe::App for TemplateApp {
                                     },
                                     _ => name.to_owned()
                                 }).clicked() {
-                                    match serialport::new(name.to_owned(), 115200)
-                                            .timeout(Duration::from_millis(1000))
-                                            .open() {
+                                    if let Some(id) = open_source {
+                                        self.close_source(id);
+                                        continue;
+                                    }
+
+                                    let settings = self.serial_settings.get(&name).cloned().unwrap_or_default();
+
+                                    match settings.open(&name) {
                                         Err(e) => self.set_short_status(e.description),
-                                        Ok(mut port) => {
-                                            port.set_flow_control(serialport::FlowControl::Hardware).unwrap();
-                                            let data: Arc<Mutex<MissionData>> = Arc::new(Mutex::new(MissionData::new()));
-
-                                            self.change_data_source(DataSource::SerialPort {
-                                                port_name: name.to_owned(),
-                                                baud_rate: 115200,
-                                                data: data.clone(),
-                                                cancel_reader: spawn_data_reader_thread(BufReader::new(port), data)
-                                            });
+                                        Ok(port) => {
+                                            let reconnect = PortIdentity::of(port_info).reconnector(settings.clone());
+
+                                            self.open_live_source(
+                                                LiveSource::SerialPort { port_name: name.to_owned(), settings },
+                                                Some(serial::connection(port)),
+                                                Some(reconnect)
+                                            );
                                         },
                                     }
                                 }
                             }
                         }
                     });
+
+                    if ui.button("Serial settings…").clicked() {
+                        self.serial_settings_port = match self.sources.active().map(|source| &source.data_source) {
+                            Some(DataSource::Live { kind: LiveSource::SerialPort { port_name, .. }, .. }) => Some(port_name.clone()),
+                            _ => serialport::available_ports().ok()
+                                .and_then(|ports| ports.first().map(|p| p.port_name.clone()))
+                                .or(Some(String::new())),
+                        };
+                        ui.close();
+                    }
+
+                    ui.separator();
+
+                    ui.menu_button("Network", |ui| {
+                        self.network_menu(ui);
+                    });
+
+                    ui.menu_button("Simulator", |ui| {
+                        self.simulator_menu(ui);
+                    });
+                });
+
+                ui.menu_button("Sources", |ui| {
+                    self.sources_menu(ui);
                 });
 
                 ui.menu_button("View", |ui| {
                     ui.checkbox(&mut self.auto_repaint, "Repaint automatically");
+                    ui.checkbox(&mut self.show_console, "Command console");
+                    ui.checkbox(&mut self.show_timeline, "Mission timeline");
+                    ui.checkbox(&mut self.show_alarms, "Alarms");
+                    ui.menu_button("Session segmentation", |ui| self.segmentation_menu(ui));
+                    ui.menu_button("Barometric altitude", |ui| self.altitude_menu(ui));
+                    ui.checkbox(&mut self.show_channels, "Computed channels");
                     egui::global_theme_preference_buttons(ui); 
                 });
 
@@ -202,6 +450,8 @@ impl eframe::App for TemplateApp {
                     ui.selectable_value(&mut self.current_tab, Tab::Data, "Data");
                     ui.selectable_value(&mut self.current_tab, Tab::Plot, "Plot");
                     ui.selectable_value(&mut self.current_tab, Tab::Map, "Map");
+                    ui.selectable_value(&mut self.current_tab, Tab::Rejected, "Rejected");
+                    ui.selectable_value(&mut self.current_tab, Tab::Gaps, "Gaps");
                 });
             });
         });
@@ -209,32 +459,158 @@ impl eframe::App for TemplateApp {
         egui::TopBottomPanel::bottom("status").show(ctx, |ui| {
 
             ui.horizontal(|ui| {
-                let data_lock = self.data_source.get_data_lock();
-                let data = self.data_source.get_data(&data_lock);
-                let session = data.and_then(|d| d.sessions().get(self.current_session));
+                let raised = self.alarm_monitor.raised_count();
+                if raised > 0 {
+                    let flash = (ui.input(|i| i.time) * 2.0) as i64 % 2 == 0;
+                    let text = egui::RichText::new(format!("🔔 {raised} alarms")).strong();
+                    let button = if flash {
+                        egui::Button::new(text.color(Color32::WHITE)).fill(ui.visuals().error_fg_color)
+                    } else {
+                        egui::Button::new(text.color(ui.visuals().error_fg_color))
+                    };
 
-                if let Some(data) = data {
-                    fn session_name(index: usize, record_count: usize) -> String {
-                        format!("Session {index} ({record_count} records)")
+                    let hover = self.alarm_monitor.raised().cloned().collect::<Vec<_>>().join("\n");
+                    if ui.add(button).on_hover_text(hover).clicked() {
+                        self.show_alarms = true;
                     }
+                    ui.separator();
+                }
+
+                if self.sources.len() > 1 {
+                    let active = self.sources.active_id();
+                    let mut chosen = active;
 
-                    egui::ComboBox::from_id_salt("session_combo_box")
-                        .selected_text(session_name(self.current_session, session.map_or(0, |s| s.len())))
+                    egui::ComboBox::from_id_salt("source_combo_box")
+                        .selected_text(self.sources.active().map_or(egui::RichText::new("No source"), |s| egui::RichText::new(&s.name).color(s.color)))
                         .show_ui(ui, |ui| {
-                            for (i, session) in data.sessions().iter().enumerate() {
-                                ui.selectable_value(&mut self.current_session, i, session_name(i, session.len()));
+                            for source in self.sources.iter() {
+                                ui.selectable_value(&mut chosen, Some(source.id), egui::RichText::new(&source.name).color(source.color));
+                            }
+                        })
+                        .response.on_hover_text("The source controlled from the status bar and the command console");
+
+                    if let Some(id) = chosen.filter(|id| Some(*id) != active) {
+                        self.sources.set_active(id);
+                    }
+                }
+
+                let mut edit_refused = false;
+                if let Some(source) = self.sources.active_mut() {
+                    let data_lock = source.data_source.get_data_lock();
+                    let data = source.data_source.get_data(&data_lock);
+                    let session = data.and_then(|d| d.sessions().get(source.current_session));
+
+                    let mut action = None;
+
+                    if let Some(data) = data {
+                        fn session_name(data: &MissionData, index: usize) -> String {
+                            let record_count = data.sessions().get(index).map_or(0, |s| s.len());
+                            match data.session_name(index) {
+                                Some(name) => format!("{name} ({record_count} records)"),
+                                None => format!("Session {index} ({record_count} records)"),
+                            }
+                        }
+
+                        egui::ComboBox::from_id_salt("session_combo_box")
+                            .selected_text(session_name(data, source.current_session))
+                            .show_ui(ui, |ui| {
+                                for i in 0..data.sessions().len() {
+                                    ui.selectable_value(&mut source.current_session, i, session_name(data, i));
+                                }
+                            });
+
+                        if let Some(session) = session {
+                            action = session_menu(ui, &mut self.session_edit, data, source.current_session, session.len());
+                        }
+
+                        let recently_rejected = data.rejected_lines().back()
+                            .is_some_and(|last| (chrono::Local::now() - last.recieved).num_seconds() < 5);
+
+                        let text = egui::RichText::new(format!("⚠ {} rejected", data.rejected_count()));
+                        let text = if recently_rejected { text.color(ui.visuals().warn_fg_color) } else { text };
+
+                        if ui.button(text).on_hover_text("Lines that could not be parsed. Click to inspect.").clicked() {
+                            self.current_tab = Tab::Rejected;
+                            self.tab_sources.insert(Tab::Rejected, vec![source.id]);
+                        }
+                    }
+
+                    match &source.data_source {
+                        DataSource::File { name, .. }
+                            => ui.label(format!("Displaying data from {}", name)),
+                        DataSource::Replay { name, .. }
+                            => ui.label(format!("Replaying {}", name)),
+                        DataSource::Simulator { simulator }
+                            => ui.label(format!("Simulating a flight (seed {}, {:?})", simulator.settings().seed, simulator.phase())),
+                        DataSource::Merged { merger }
+                            => ui.label(format!("Merging {} receivers", merger.inputs().count())),
+                        DataSource::Live { kind, reader, .. } if reader.is_connected()
+                            => ui.label(kind.connected_text()),
+                        DataSource::Live { kind: kind @ LiveSource::TcpServer { .. }, .. }
+                            => ui.label(kind.disconnected_text()),
+                        DataSource::Live { kind, .. }
+                            => ui.colored_label(ui.visuals().error_fg_color, kind.disconnected_text()),
+                    };
+
+                    let has_pressure = data.is_some_and(|data| data.schema().field(FieldTarget::Pressure).is_some());
+                    if let Some(session) = session.filter(|_| has_pressure) {
+                        let altitude = session_altitude(&mut self.altitude_trackers, self.altitude, (source.id, source.current_session), session);
+                        let latest = (0..session.len()).rev().find(|&i| !altitude.altitude[i].is_nan());
+
+                        if let Some(i) = latest {
+                            ui.separator();
+                            ui.label(format!("Altitude {:.1} m", altitude.altitude[i]))
+                                .on_hover_text("Barometric altitude above the reference pressure");
+                            if !altitude.vertical_speed[i].is_nan() {
+                                ui.label(format!("↕ {:+.1} m/s", altitude.vertical_speed[i]));
+                            }
+                        }
+                    }
+
+                    drop(data_lock);
+
+                    if let Some(action) = action {
+                        let current_session = &mut source.current_session;
+                        let edited = source.data_source.edit_data(|data| {
+                            match action {
+                                SessionAction::Rename(name) => data.rename_session(*current_session, Some(name)),
+                                SessionAction::Split(at) => data.split_session(*current_session, at),
+                                SessionAction::MergeWithNext => data.merge_with_next(*current_session),
+                                SessionAction::Delete => {
+                                    data.delete_session(*current_session);
+                                    *current_session = (*current_session).min(data.sessions().len().saturating_sub(1));
+                                },
                             }
                         });
+
+                        // Sessions may now be in different positions
+                        if edited {
+                            self.event_detectors.retain(|(id, _), _| *id != source.id);
+                            self.altitude_trackers.retain(|(id, _), _| *id != source.id);
+                        }
+                        edit_refused = !edited;
+                    }
+
+                    if let DataSource::Replay { replay, .. } = &mut source.data_source {
+                        ui.separator();
+                        replay_controls(ui, replay);
+                    }
+
+                    if let DataSource::Live { capture: Some(capture), .. } = &source.data_source {
+                        let file_name = capture.path.file_name().map_or_else(String::new, |n| n.to_string_lossy().into_owned());
+                        let bytes = capture.bytes_written.load(std::sync::atomic::Ordering::Relaxed);
+
+                        ui.separator();
+                        ui.label(format!("⏺ Recording to {} ({:.1} kB)", file_name, bytes as f64 / 1000.0))
+                            .on_hover_text(capture.path.display().to_string());
+                    }
+                } else {
+                    ui.label("No data.");
                 }
 
-                ui.label(match &self.data_source {
-                    DataSource::File { name, .. }
-                        => format!("Displaying data from {}", name),
-                    DataSource::SerialPort { port_name, baud_rate, .. } 
-                        => format!("Connected to serial {} at {} baud", port_name, baud_rate ),
-                    DataSource::None 
-                        => "No data.".to_owned(),
-                });
+                if edit_refused {
+                    self.set_short_status("Sessions of replays and merged sources can't be edited".to_owned());
+                }
     
                 if let Some(status) = self.status_message.clone() {
                     if status.since.elapsed() > status.duration {
@@ -251,52 +627,1018 @@ impl eframe::App for TemplateApp {
             });
         });
 
-        egui::CentralPanel::default().show(ctx, |ui| {
-            let data_lock = self.data_source.get_data_lock();
-            let data = self.data_source.get_data(&data_lock);
+        self.serial_settings_window(ctx);
+        self.import_wizard_window(ctx);
+        self.channels_window(ctx);
+        self.alarms_window(ctx);
 
-            let session = data.and_then(|d| d.sessions().get(self.current_session));
+        let replies = match self.sources.active().map(|source| &source.data_source) {
+            Some(DataSource::Live { reader, .. }) => reader.take_replies(),
+            _ => vec![],
+        };
+        self.console_state.match_replies(&replies, chrono::Local::now());
 
-            if let Some(session) = session {
-                match self.current_tab {
-                    Tab::Data => {
-                        data_tab(ui, &mut self.data_state, session);
-                    },
-                    Tab::Plot => {
-                        plot_tab(ui, &mut self.plot_state, session);
-                    },
-                    Tab::Map => {
-                        map_tab(ui, &mut self.map_state, session);
-                    },
-                }
-            } else if data.is_some() {
-                ui.heading("No data.");
-                ui.label("If you are connected to the ground station, you should see some data shortly.");
-            } else {
+        if self.show_console {
+            egui::SidePanel::right("console_panel").show(ctx, |ui| {
+                let uplink = match self.sources.active().map(|source| &source.data_source) {
+                    Some(DataSource::Live { reader, .. }) => Some(reader),
+                    _ => None,
+                };
+
+                console_panel(ui, &mut self.console_state, uplink);
+            });
+        }
+
+        if self.show_timeline {
+            egui::SidePanel::right("timeline_panel").show(ctx, |ui| {
+                let Some(source) = self.sources.active() else {
+                    ui.heading("Mission timeline");
+                    ui.label("No data.");
+                    return;
+                };
+
+                let lock = source.data_source.get_data_lock();
+                let session = source.data_source.get_data(&lock)
+                    .and_then(|data| data.sessions().get(source.current_session))
+                    .map_or(&[][..], |session| session.as_slice());
+                let events = session_events(&mut self.event_detectors, self.event_settings, (source.id, source.current_session), session);
+
+                timeline_panel(ui, &mut self.event_settings, session, &events);
+            });
+        }
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            if self.sources.is_empty() {
                 ui.heading("No data available.");
                 ui.label("Load a log file using File > Import log");
                 ui.label("Or choose a device to connect to through Connection > Port > ...");
+                ui.label("Or listen for telemetry over the network through Connection > Network > ...");
+                ui.label("Or simulate a flight through Connection > Simulator");
+                return;
+            }
+
+            if self.sources.len() > 1 {
+                self.tab_sources_selector(ui);
+                ui.separator();
+            }
+
+            let shown = shown_sources(&self.sources, &self.tab_sources, self.current_tab);
+            let locks: Vec<_> = shown.iter().map(|source| source.data_source.get_data_lock()).collect();
+
+            let (views, (events, keys)): (Vec<SessionView<'_>>, (Vec<Vec<FlightEvent>>, Vec<_>)) = shown.iter().zip(&locks)
+                .filter_map(|(source, lock)| {
+                    let mission = source.data_source.get_data(lock)?;
+                    let session = mission.sessions().get(source.current_session)?;
+                    let key = (source.id, source.current_session);
+                    let events = session_events(&mut self.event_detectors, self.event_settings, key, session);
+                    session_altitude(&mut self.altitude_trackers, self.altitude, key, session);
+
+                    Some((SessionView {
+                        name: &source.name,
+                        color: source.color,
+                        session_index: source.current_session,
+                        mission,
+                        session,
+                    }, (events, key)))
+                })
+                .unzip();
+            let altitudes: Vec<&Altitude> = keys.iter().map(|key| self.altitude_trackers[key].altitude()).collect();
+
+            let first = shown.first().zip(locks.first())
+                .and_then(|(source, lock)| Some((source.current_session, source.data_source.get_data(lock)?)));
+
+            match (self.current_tab, first) {
+                (Tab::Rejected, Some((_, data))) => {
+                    rejected_tab(ui, &mut self.rejected_state, data);
+                },
+                (Tab::Gaps, Some((session, data))) if data.sessions().get(session).is_some() => {
+                    gaps_tab(ui, &mut self.gaps_state, &data.sessions()[session]);
+                },
+                (Tab::Data, Some((session, data))) if data.sessions().get(session).is_some() => {
+                    let receivers = match shown.first().map(|source| &source.data_source) {
+                        Some(DataSource::Merged { merger }) => merger.receiver_stats(session),
+                        _ => vec![],
+                    };
+                    data_tab(ui, &mut self.data_state, data, session, &receivers, altitudes[0], &self.channels);
+                },
+                (Tab::Plot, _) if !views.is_empty() => {
+                    plot_tab(ui, &mut self.plot_state, &views, &events, &altitudes, &self.channels);
+                },
+                (Tab::Map, _) if !views.is_empty() => {
+                    map_tab(ui, &mut self.map_state, &views, &events);
+                },
+                _ => {
+                    ui.heading("No data.");
+                    ui.label("If you are connected to the ground station, you should see some data shortly.");
+                },
             }
-            
         });   
 
     }
 }
 
+/// Sources shown by a tab, falling back to the active source if none of the chosen ones are open
+fn shown_sources<'a>(sources: &'a Sources, tab_sources: &HashMap<Tab, Vec<SourceId>>, tab: Tab) -> Vec<&'a crate::sources::Source> {
+    let shown: Vec<_> = tab_sources.get(&tab).into_iter().flatten()
+        .filter_map(|id| sources.get(*id))
+        .collect();
+
+    if shown.is_empty() && !tab.shows_many_sources() {
+        sources.active().into_iter().collect()
+    } else {
+        shown
+    }
+}
+
+/// Events of a session, found by its detector. The detector starts over when the settings change.
+fn session_events(detectors: &mut HashMap<(SourceId, usize), EventDetector>, settings: EventSettings, key: (SourceId, usize), session: &[SensedData]) -> Vec<FlightEvent> {
+    let detector = detectors.entry(key).or_insert_with(|| EventDetector::new(settings));
+    if *detector.settings() != settings {
+        *detector = EventDetector::new(settings);
+    }
+    detector.update(session).to_vec()
+}
+
+/// Altitude of a session, kept up to date by its tracker. The tracker starts over when the settings change.
+fn session_altitude<'a>(trackers: &'a mut HashMap<(SourceId, usize), AltitudeTracker>, settings: AltitudeSettings, key: (SourceId, usize), session: &[SensedData]) -> &'a Altitude {
+    let tracker = trackers.entry(key).or_insert_with(|| AltitudeTracker::new(settings));
+    if *tracker.settings() != settings {
+        *tracker = AltitudeTracker::new(settings);
+    }
+    tracker.update(session)
+}
+
+/// The menu next to the session combo box, for fixing sessions the segmentation got wrong
+fn session_menu(ui: &mut egui::Ui, edit: &mut SessionEdit, data: &MissionData, session: usize, record_count: usize) -> Option<SessionAction> {
+    let mut action = None;
+
+    ui.menu_button("✏", |ui| {
+        ui.horizontal(|ui| {
+            ui.add(egui::TextEdit::singleline(&mut edit.name).hint_text("Session name").desired_width(140.0));
+            if ui.button("Rename").clicked() {
+                action = Some(SessionAction::Rename(std::mem::take(&mut edit.name)));
+                ui.close();
+            }
+        });
+
+        ui.horizontal(|ui| {
+            ui.add(egui::DragValue::new(&mut edit.split_at).range(1..=record_count.saturating_sub(1)).prefix("at record "));
+            if ui.add_enabled(record_count > 1, egui::Button::new("Split")).on_hover_text("Move this record and the ones after it into a new session").clicked() {
+                action = Some(SessionAction::Split(edit.split_at));
+                ui.close();
+            }
+        });
+
+        ui.separator();
+
+        if ui.add_enabled(session + 1 < data.sessions().len(), egui::Button::new("Merge with next session")).clicked() {
+            action = Some(SessionAction::MergeWithNext);
+            ui.close();
+        }
+        if ui.button("Delete session").clicked() {
+            action = Some(SessionAction::Delete);
+            ui.close();
+        }
+    }).response.on_hover_text("Rename, split, merge or delete this session");
+
+    action
+}
+
+fn replay_controls(ui: &mut egui::Ui, replay: &mut Replay) {
+    let (icon, hover) = if replay.is_playing() { ("⏸", "Pause") } else { ("▶", "Play") };
+    if ui.button(icon).on_hover_text(hover).clicked() {
+        if replay.is_playing() {
+            replay.pause();
+        } else {
+            replay.play();
+        }
+    }
+
+    egui::ComboBox::from_id_salt("replay_speed_combo_box")
+        .width(60.0)
+        .selected_text(format!("{}x", replay.speed()))
+        .show_ui(ui, |ui| {
+            for speed in REPLAY_SPEEDS {
+                if ui.selectable_label(replay.speed() == speed, format!("{speed}x")).clicked() {
+                    replay.set_speed(speed);
+                }
+            }
+        });
+
+    fn format_time(seconds: f64) -> String {
+        let seconds = seconds as u64;
+        format!("{}:{:02}", seconds / 60, seconds % 60)
+    }
+
+    let mut time = replay.time();
+    let scrubber = egui::Slider::new(&mut time, 0.0..=replay.duration())
+        .show_value(false);
+
+    if ui.add(scrubber).changed() {
+        replay.seek(time);
+    }
+
+    ui.label(format!("{} / {}", format_time(replay.time()), format_time(replay.duration())));
+}
+
 impl TemplateApp {
-    fn change_data_source(&mut self, new: DataSource) {
-        if let DataSource::SerialPort { cancel_reader, .. } = &self.data_source {
-            cancel_reader.store(true, std::sync::atomic::Ordering::Relaxed);
+    /// Opens a log file as a source, either whole or as a replay
+    fn open_log(&mut self, path: PathBuf, replay: bool) -> Option<SourceId> {
+        let name = path.file_name()
+            .expect("path should always point to a file")
+            .to_string_lossy()
+            .into_owned();
+
+        let Ok(bytes) = fs::read(&path) else {
+            self.set_short_status(format!("Unable to read {name}"));
+            return None;
+        };
+        let text = String::from_utf8_lossy(&bytes);
+
+        Some(if replay {
+            self.add_source(format!("{name} (replay)"), DataSource::Replay {
+                name,
+                path: Some(path),
+                replay: Box::new(Replay::new(&text, self.schema.clone()))
+            })
+        } else {
+            self.add_source(name.clone(), DataSource::File {
+                name,
+                path: Some(path),
+                data: MissionData::from_log(&text, self.schema.clone())
+            })
+        })
+    }
+
+    /// Reads a telemetry schema file and uses it for newly opened data. Returns whether it worked.
+    fn load_schema(&mut self, path: PathBuf) -> bool {
+        match fs::read_to_string(&path).map(|text| TelemetrySchema::parse(&text)) {
+            Err(_) => self.set_short_status("Unable to read file".to_owned()),
+            Ok(Err(e)) => self.set_short_status(format!("Invalid schema (line {}): {}", e.line, e.msg)),
+            Ok(Ok(schema)) => {
+                self.schema = Arc::new(schema);
+                self.schema_path = Some(path);
+                return true;
+            },
+        }
+        false
+    }
+
+    fn save_project(&mut self, path: &Path) {
+        let logs = self.sources.iter()
+            .filter_map(|source| {
+                let (path, replay) = match &source.data_source {
+                    DataSource::File { path: Some(path), .. } => (path, false),
+                    DataSource::Replay { path: Some(path), .. } => (path, true),
+                    _ => return None,
+                };
+
+                Some(ProjectLog {
+                    path: path.clone(),
+                    name: source.name.clone(),
+                    color: [source.color.r(), source.color.g(), source.color.b()],
+                    replay,
+                    session: source.current_session,
+                })
+            })
+            .collect();
+
+        let project = Project {
+            logs,
+            schema: self.schema_path.clone(),
+            segmentation: self.segmentation,
+            altitude: self.altitude,
+            plot: self.plot_state.clone(),
+            map: self.map_state.settings(),
+            data: self.data_state.clone(),
+            gaps: self.gaps_state.clone(),
+        };
+
+        match project.save(path) {
+            Ok(()) => self.set_short_status(format!("Project saved to {}", path.display())),
+            Err(e) => self.set_short_status(format!("Unable to save the project: {e}")),
+        }
+    }
+
+    /// Writes the sessions of the active source out for other tools
+    fn export_menu(&mut self, ui: &mut egui::Ui) {
+        let settings = &mut self.export_settings;
+
+        for format in ExportFormat::ALL {
+            ui.radio_value(&mut settings.format, format, format.name());
+        }
+
+        ui.separator();
+
+        ui.checkbox(&mut settings.all_sessions, "All sessions");
+        ui.checkbox(&mut settings.apply_plot_filters, "Only records shown by the plot filters");
+
+        ui.separator();
+
+        let Some(source) = self.sources.active() else {
+            ui.weak("Open a source to export its data");
+            return;
+        };
+
+        if ui.button(format!("Export {}…", source.name)).clicked() {
+            let format = settings.format;
+            let file_name = format!("{}.{}", source.name, format.extension());
+
+            if let Some(path) = rfd::FileDialog::new().add_filter(format.name(), &[format.extension()]).set_file_name(file_name).save_file() {
+                let lock = source.data_source.get_data_lock();
+                let result = match source.data_source.get_data(&lock) {
+                    Some(data) => {
+                        // Session and position in the session of every exported record
+                        let positions: Vec<(usize, usize)> = data.sessions().iter()
+                            .enumerate()
+                            .filter(|(i, _)| settings.all_sessions || *i == source.current_session)
+                            .flat_map(|(i, session)| session.iter().enumerate().map(move |(j, frame)| (i, j, frame)))
+                            .filter(|(_, _, frame)| !settings.apply_plot_filters || self.plot_state.includes(frame))
+                            .map(|(i, j, _)| (i, j))
+                            .collect();
+                        let rows: Vec<(usize, &SensedData)> = positions.iter()
+                            .map(|&(i, j)| (i, &data.sessions()[i][j]))
+                            .collect();
+
+                        let mut columns = schema_columns(data.schema());
+                        columns.extend(computed_columns(&self.channels, data, &positions));
+
+                        fs::File::create(&path)
+                            .map(io::BufWriter::new)
+                            .and_then(|mut writer| {
+                                export(&mut writer, format, &columns, &rows)?;
+                                writer.flush()?;
+                                Ok(rows.len())
+                            })
+                    },
+                    None => Ok(0),
+                };
+                drop(lock);
+
+                match result {
+                    Ok(count) => self.set_short_status(format!("Exported {count} records to {}", path.display())),
+                    Err(e) => self.set_short_status(format!("Unable to export: {e}")),
+                }
+            }
+            ui.close();
         }
+    }
+
+    /// Replaces the open sources and tab settings with the ones of a project
+    fn open_project(&mut self, path: &Path) {
+        let project = match Project::load(path) {
+            Ok(project) => project,
+            Err(e) => {
+                self.set_short_status(format!("Unable to open the project: {e}"));
+                return;
+            },
+        };
+
+        let ids: Vec<SourceId> = self.sources.iter().map(|source| source.id).collect();
+        for id in ids {
+            self.close_source(id);
+        }
+
+        match project.schema {
+            Some(schema) => {
+                self.load_schema(resolve_path(path, &schema));
+            },
+            None => {
+                self.schema = Arc::default();
+                self.schema_path = None;
+            },
+        }
+
+        self.segmentation = project.segmentation;
+        self.altitude = project.altitude;
+        self.plot_state = project.plot;
+        self.map_state.apply_settings(project.map);
+        self.data_state = project.data;
+        self.gaps_state = project.gaps;
+
+        let mut missing = 0;
+        for log in project.logs {
+            let Some(id) = self.open_log(resolve_path(path, &log.path), log.replay) else {
+                missing += 1;
+                continue;
+            };
+
+            if let Some(source) = self.sources.get_mut(id) {
+                source.name = log.name;
+                source.color = Color32::from_rgb(log.color[0], log.color[1], log.color[2]);
+                source.current_session = log.session;
+            }
+        }
+
+        if missing > 0 {
+            self.set_short_status(format!("{missing} logs of the project could not be opened"));
+        }
+    }
+
+    /// Opens a new source, shown by every tab
+    fn add_source(&mut self, name: String, mut data_source: DataSource) -> SourceId {
+        data_source.set_segmentation(self.segmentation);
+        let id = self.sources.add(name, data_source);
+
+        for tab in Tab::ALL {
+            let shown = self.tab_sources.entry(tab).or_default();
+            if !tab.shows_many_sources() {
+                shown.clear();
+            }
+            shown.push(id);
+        }
+
+        id
+    }
+
+    fn close_source(&mut self, id: SourceId) {
+        self.sources.close(id);
+        self.event_detectors.retain(|(source, _), _| *source != id);
+        self.altitude_trackers.retain(|(source, _), _| *source != id);
+
+        for shown in self.tab_sources.values_mut() {
+            shown.retain(|shown| *shown != id);
+        }
+    }
+
+    fn sources_menu(&mut self, ui: &mut egui::Ui) {
+        if self.sources.is_empty() {
+            ui.label("No sources are open.");
+            return;
+        }
+
+        let mut closed = None;
+        let mut activated = None;
+        let active = self.sources.active_id();
+
+        egui::Grid::new("sources_grid").num_columns(4).show(ui, |ui| {
+            for source in self.sources.iter_mut() {
+                if ui.radio(active == Some(source.id), "").on_hover_text("Control from the status bar and the command console").clicked() {
+                    activated = Some(source.id);
+                }
+                egui::color_picker::color_edit_button_srgba(ui, &mut source.color, egui::color_picker::Alpha::Opaque);
+                ui.add(egui::TextEdit::singleline(&mut source.name).desired_width(160.0));
+                if ui.button("✖").on_hover_text("Close").clicked() {
+                    closed = Some(source.id);
+                }
+                ui.end_row();
+            }
+        });
+
+        if let Some(id) = activated {
+            self.sources.set_active(id);
+        }
+        if let Some(id) = closed {
+            self.close_source(id);
+        }
+
+        ui.separator();
+
+        ui.menu_button("Merge sources", |ui| self.merge_menu(ui));
+
+        if ui.button("Close all").clicked() {
+            let ids: Vec<SourceId> = self.sources.iter().map(|source| source.id).collect();
+            for id in ids {
+                self.close_source(id);
+            }
+            ui.close();
+        }
+    }
+
+    /// Combines several receivers of the same probe into one source
+    fn merge_menu(&mut self, ui: &mut egui::Ui) {
+        let mut inputs = vec![];
+        for source in self.sources.iter() {
+            if matches!(source.data_source, DataSource::Merged { .. }) {
+                continue;
+            }
+
+            let mut selected = self.merge_selection.contains(&source.id);
+            if ui.checkbox(&mut selected, &source.name).changed() {
+                if selected {
+                    self.merge_selection.push(source.id);
+                } else {
+                    self.merge_selection.retain(|id| *id != source.id);
+                }
+            }
+            if selected {
+                inputs.push((source.id, source.name.clone()));
+            }
+        }
+
+        ui.separator();
+
+        if ui.add_enabled(inputs.len() > 1 && inputs.len() <= MAX_RECEIVERS, egui::Button::new("Merge")).clicked() {
+            let names: Vec<&str> = inputs.iter().map(|(_, name)| name.as_str()).collect();
+            let name = format!("Merged ({})", names.join(" + "));
+            let merger = Merger::new(inputs, self.schema.clone());
+
+            self.merge_selection.clear();
+            self.add_source(name, DataSource::Merged { merger: Box::new(merger) });
+            ui.close();
+        }
+    }
+
+    /// Lets the user choose the sources shown by the current tab
+    fn tab_sources_selector(&mut self, ui: &mut egui::Ui) {
+        let tab = self.current_tab;
+        let mut shown: Vec<SourceId> = shown_sources(&self.sources, &self.tab_sources, tab).iter().map(|s| s.id).collect();
+
+        ui.horizontal_wrapped(|ui| {
+            ui.label("Showing:");
+
+            for source in self.sources.iter() {
+                let text = egui::RichText::new(&source.name).color(source.color);
+                let mut selected = shown.contains(&source.id);
+
+                if tab.shows_many_sources() {
+                    if ui.checkbox(&mut selected, text).changed() {
+                        if selected {
+                            shown.push(source.id);
+                        } else {
+                            shown.retain(|id| *id != source.id);
+                        }
+                    }
+                } else if ui.radio(selected, text).clicked() {
+                    shown = vec![source.id];
+                }
+            }
+        });
+
+        self.tab_sources.insert(tab, shown);
+    }
+
+    fn import_wizard_window(&mut self, ctx: &egui::Context) {
+        let Some(wizard) = &mut self.import_wizard else {
+            return;
+        };
+
+        match import_window(ctx, wizard, &mut self.import_mappings, &self.schema) {
+            Some(WizardAction::Import(data, skipped)) => {
+                let name = wizard.path.file_name().map_or_else(String::new, |name| name.to_string_lossy().into_owned());
+                self.import_wizard = None;
+
+                // Projects reopen logs in the firmware format, so the path isn't kept
+                self.add_source(name.clone(), DataSource::File { name, path: None, data });
+                if skipped > 0 {
+                    self.set_short_status(format!("{skipped} rows could not be read and were skipped"));
+                }
+            },
+            Some(WizardAction::Cancel) => self.import_wizard = None,
+            None => {},
+        }
+    }
+
+    /// Lists the computed channels, checking their expressions against the schema of the active source
+    fn channels_window(&mut self, ctx: &egui::Context) {
+        let schema = self.sources.active()
+            .and_then(|source| {
+                let lock = source.data_source.get_data_lock();
+                source.data_source.get_data(&lock).map(|data| data.schema().clone())
+            })
+            .unwrap_or_else(|| self.schema.as_ref().clone());
+
+        let mut removed = None;
+        egui::Window::new("Computed channels")
+            .open(&mut self.show_channels)
+            .resizable(true)
+            .show(ctx, |ui| {
+                egui::Grid::new("channels_grid").num_columns(4).striped(true).show(ui, |ui| {
+                    ui.strong("Name");
+                    ui.strong("Unit");
+                    ui.strong("Expression");
+                    ui.end_row();
+
+                    for i in 0..self.channels.len() {
+                        let duplicate = self.channels[..i].iter().any(|other| other.name == self.channels[i].name);
+                        let channel = &mut self.channels[i];
+
+                        ui.add(egui::TextEdit::singleline(&mut channel.name).desired_width(120.0));
+                        ui.add(egui::TextEdit::singleline(&mut channel.unit).desired_width(50.0));
+                        ui.add(egui::TextEdit::singleline(&mut channel.expression).desired_width(280.0).code_editor());
+
+                        ui.horizontal(|ui| {
+                            if ui.small_button("✖").clicked() {
+                                removed = Some(i);
+                            }
+                            match channel.evaluate(&schema, &[]) {
+                                _ if channel.name.trim().is_empty() => {ui.colored_label(ui.visuals().warn_fg_color, "The channel needs a name");},
+                                _ if duplicate => {ui.colored_label(ui.visuals().warn_fg_color, "Another channel has this name");},
+                                Ok(_) => {ui.label("✔");},
+                                Err(e) => {ui.colored_label(ui.visuals().error_fg_color, e.to_string());},
+                            }
+                        });
+                        ui.end_row();
+                    }
+                });
+
+                if ui.button("Add channel").clicked() {
+                    self.channels.push(ComputedChannel {
+                        name: format!("channel_{}", self.channels.len() + 1),
+                        unit: String::new(),
+                        expression: String::new(),
+                    });
+                }
+
+                ui.separator();
+
+                ui.weak("Use the field names of the schema, t for the uptime in seconds, + - * / ^ and parentheses.");
+                ui.weak("Functions: sqrt, abs, sin, cos, tan, atan2, ln, log10, exp, min, max.");
+                ui.weak("d(x)/dt is the change per second, prev(x, n) the value n records earlier, avg(x, n) the mean of the last n records.");
+                ui.weak("Example: sqrt(accel_x^2 + accel_y^2), avg(d(pressure)/dt, 10), pressure / 100");
+            });
+
+        if let Some(i) = removed {
+            self.channels.remove(i);
+        }
+    }
+
+    /// Edits the alarm rules and shows when alarms were raised and cleared
+    fn alarms_window(&mut self, ctx: &egui::Context) {
+        let rules_before = self.alarm_rules.clone();
+        let mut removed = None;
+
+        egui::Window::new("Alarms")
+            .open(&mut self.show_alarms)
+            .resizable(true)
+            .show(ctx, |ui| {
+                ui.heading("Rules");
+
+                egui::Grid::new("alarm_rules_grid").num_columns(5).striped(true).show(ui, |ui| {
+                    for (i, rule) in self.alarm_rules.iter_mut().enumerate() {
+                        ui.checkbox(&mut rule.enabled, "").on_hover_text("Enabled");
+                        ui.add(egui::TextEdit::singleline(&mut rule.name).desired_width(120.0));
+
+                        let mut kind = rule.condition.kind();
+                        egui::ComboBox::from_id_salt(("alarm_kind", i))
+                            .selected_text(AlarmCondition::KINDS[kind])
+                            .show_ui(ui, |ui| {
+                                for (k, name) in AlarmCondition::KINDS.iter().enumerate() {
+                                    ui.selectable_value(&mut kind, k, *name);
+                                }
+                            });
+                        if kind != rule.condition.kind() {
+                            rule.condition = AlarmCondition::example(kind);
+                        }
+
+                        ui.horizontal(|ui| {
+                            match &mut rule.condition {
+                                AlarmCondition::Above { channel, threshold } | AlarmCondition::Below { channel, threshold } => {
+                                    ui.add(egui::TextEdit::singleline(channel).desired_width(140.0).hint_text("Channel"))
+                                        .on_hover_text("A schema field, a computed channel or an expression");
+                                    ui.add(egui::DragValue::new(threshold).speed(0.1));
+                                },
+                                AlarmCondition::RateAbove { channel, rate } => {
+                                    ui.add(egui::TextEdit::singleline(channel).desired_width(140.0).hint_text("Channel"))
+                                        .on_hover_text("A schema field, a computed channel or an expression");
+                                    ui.add(egui::DragValue::new(rate).range(0.0..=f64::MAX).speed(0.1).prefix("± ").suffix(" /s"));
+                                },
+                                AlarmCondition::NoPacket { seconds } => {
+                                    ui.add(egui::DragValue::new(seconds).range(0.5..=3600.0).speed(0.5).prefix("for ").suffix(" s"));
+                                },
+                                AlarmCondition::GpsLost | AlarmCondition::Unreliable => {},
+                            }
+                        });
 
-        self.data_source = new;
+                        ui.horizontal(|ui| {
+                            ui.checkbox(&mut rule.sound, "🔊").on_hover_text("Play a sound when raised");
+                            if ui.small_button("✖").clicked() {
+                                removed = Some(i);
+                            }
+                        });
+                        ui.end_row();
+                    }
+                });
 
-        let data_lock = self.data_source.get_data_lock();
-        let data = self.data_source.get_data(&data_lock);
+                if ui.button("Add rule").clicked() {
+                    self.alarm_rules.push(AlarmRule {
+                        name: format!("Alarm {}", self.alarm_rules.len() + 1),
+                        enabled: true,
+                        condition: AlarmCondition::example(0),
+                        sound: false,
+                    });
+                }
 
-        self.current_session = data
-            .map(|data| data.sessions().len())
-            .map_or(0, |len| { if len > 0 {len - 1} else { 0 } });
+                ui.separator();
+
+                ui.horizontal(|ui| {
+                    ui.heading("Log");
+                    if ui.small_button("Clear").clicked() {
+                        self.alarm_monitor.clear_log();
+                    }
+                });
+
+                egui::ScrollArea::vertical().max_height(240.0).auto_shrink([false, true]).show(ui, |ui| {
+                    if self.alarm_monitor.log().is_empty() {
+                        ui.weak("No alarms so far");
+                    }
+
+                    for entry in self.alarm_monitor.log().iter().rev() {
+                        let time = entry.time.format("%H:%M:%S");
+                        match &entry.message {
+                            Some(message) => ui.colored_label(ui.visuals().error_fg_color, format!("{time} {} · {}: {message}", entry.source, entry.rule)),
+                            None => ui.label(format!("{time} {} · {}: cleared", entry.source, entry.rule)),
+                        };
+                    }
+                });
+            });
+
+        if let Some(i) = removed {
+            self.alarm_rules.remove(i);
+        }
+        if self.alarm_rules != rules_before {
+            self.alarm_monitor.reset();
+        }
+    }
+
+    fn serial_settings_window(&mut self, ctx: &egui::Context) {
+        let Some(mut port_name) = self.serial_settings_port.clone() else {
+            return;
+        };
+
+        let mut open = true;
+        egui::Window::new("Serial settings")
+            .open(&mut open)
+            .resizable(false)
+            .collapsible(false)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Port");
+                    egui::ComboBox::from_id_salt("serial_settings_port_combo_box")
+                        .selected_text(if port_name.is_empty() { "No port" } else { &port_name })
+                        .show_ui(ui, |ui| {
+                            let ports = serialport::available_ports().unwrap_or_default();
+                            let known = self.serial_settings.keys().cloned();
+
+                            let mut names: Vec<String> = ports.into_iter().map(|p| p.port_name).chain(known).collect();
+                            names.sort();
+                            names.dedup();
+
+                            for name in names {
+                                let label = name.clone();
+                                ui.selectable_value(&mut port_name, name, label);
+                            }
+                        });
+                });
+
+                ui.separator();
+
+                if port_name.is_empty() {
+                    ui.label("No serial ports available.");
+                    return;
+                }
+
+                let settings = self.serial_settings.entry(port_name.clone()).or_default();
+                serial_settings_ui(ui, settings);
+
+                ui.separator();
+
+                ui.horizontal(|ui| {
+                    if ui.button("Reset to defaults").clicked() {
+                        *settings = SerialSettings::default();
+                    }
+                    ui.weak("Used the next time the port is opened");
+                });
+            });
+
+        self.serial_settings_port = if open { Some(port_name) } else { None };
+    }
+
+    /// Starts reading a live source. With no initial `connection`, the source
+    /// shows as disconnected until `reconnect` succeeds.
+    fn open_live_source(&mut self, kind: LiveSource, connection: Option<Connection>, reconnect: Option<Reconnect>) {
+        let data = Arc::new(Mutex::new(MissionData::with_schema(self.schema.clone())));
+        let (writer, capture) = self.start_capture(&kind.name()).unzip();
+
+        self.add_source(kind.name(), DataSource::Live {
+            kind,
+            data: data.clone(),
+            reader: spawn_data_reader_thread(connection, reconnect, data, writer),
+            capture
+        });
+    }
+
+    fn network_menu(&mut self, ui: &mut egui::Ui) {
+        let chosen = match self.sources.active().map(|source| &source.data_source) {
+            Some(DataSource::Live { kind, .. }) => Some(kind.clone()),
+            _ => None,
+        };
+
+        ui.label("TCP client");
+        ui.horizontal(|ui| {
+            ui.add(egui::TextEdit::singleline(&mut self.network_settings.tcp_host).desired_width(140.0));
+            ui.label(":");
+            ui.add(egui::DragValue::new(&mut self.network_settings.tcp_port));
+
+            if ui.button("Connect").clicked() {
+                let address = format!("{}:{}", self.network_settings.tcp_host, self.network_settings.tcp_port);
+
+                // Connecting may take a while, so it is left to the reader thread
+                let connect = connect_tcp(address.clone());
+                self.open_live_source(LiveSource::TcpClient { address }, None, Some(connect));
+                ui.close();
+            }
+        });
+        if let Some(LiveSource::TcpClient { address }) = &chosen {
+            ui.weak(format!("Current: {address}"));
+        }
+
+        ui.separator();
+
+        ui.label("TCP server");
+        ui.horizontal(|ui| {
+            ui.label("Port");
+            ui.add(egui::DragValue::new(&mut self.network_settings.server_port));
+
+            if ui.button("Listen").clicked() {
+                let port = self.network_settings.server_port;
+
+                match listen_tcp(port) {
+                    Err(e) => self.set_short_status(format!("Unable to listen on TCP port {port}: {e}")),
+                    Ok(accept) => {
+                        self.open_live_source(LiveSource::TcpServer { port }, None, Some(accept));
+                        ui.close();
+                    },
+                }
+            }
+        });
+
+        ui.separator();
+
+        ui.label("UDP");
+        ui.horizontal(|ui| {
+            ui.label("Port");
+            ui.add(egui::DragValue::new(&mut self.network_settings.udp_port));
+
+            if ui.button("Listen").clicked() {
+                let port = self.network_settings.udp_port;
+
+                match listen_udp(port) {
+                    Err(e) => self.set_short_status(format!("Unable to listen on UDP port {port}: {e}")),
+                    Ok(connection) => {
+                        self.open_live_source(LiveSource::UdpListener { port }, Some(connection), None);
+                        ui.close();
+                    },
+                }
+            }
+        });
+    }
+
+    fn simulator_menu(&mut self, ui: &mut egui::Ui) {
+        let settings = &mut self.simulator_settings;
+
+        egui::Grid::new("simulator_settings_grid").num_columns(2).show(ui, |ui| {
+            ui.label("Seed");
+            ui.horizontal(|ui| {
+                ui.add(egui::DragValue::new(&mut settings.seed));
+                if ui.button("🎲").on_hover_text("Random seed").clicked() {
+                    settings.seed = rand::random();
+                }
+            });
+            ui.end_row();
+
+            ui.label("Frame interval");
+            ui.add(egui::DragValue::new(&mut settings.interval_ms).range(10..=5000).suffix(" ms"));
+            ui.end_row();
+
+            ui.label("Packet loss");
+            let mut loss = settings.packet_loss * 100.0;
+            if ui.add(egui::Slider::new(&mut loss, 0.0..=90.0).suffix(" %")).changed() {
+                settings.packet_loss = loss / 100.0;
+            }
+            ui.end_row();
+
+            ui.label("Reboot chance");
+            let mut reset = settings.reset_chance * 100.0;
+            if ui.add(egui::Slider::new(&mut reset, 0.0..=5.0).suffix(" % per frame")).changed() {
+                settings.reset_chance = reset / 100.0;
+            }
+            ui.end_row();
+        });
+
+        if ui.button("Start simulation").clicked() {
+            let simulator = Simulator::new(self.simulator_settings.clone(), MissionData::with_schema(self.schema.clone()));
+            let name = format!("Simulator (seed {})", self.simulator_settings.seed);
+            self.add_source(name, DataSource::Simulator { simulator: Box::new(simulator) });
+            ui.close();
+        }
+    }
+
+    fn segmentation_menu(&mut self, ui: &mut egui::Ui) {
+        let segmentation = &mut self.segmentation;
+
+        egui::Grid::new("segmentation_grid").num_columns(2).show(ui, |ui| {
+            ui.label("Index reset");
+            ui.add(egui::DragValue::new(&mut segmentation.index_reset).prefix("to ≤ "))
+                .on_hover_text("An index going back to this value or below starts a new session. Smaller steps back are taken as reordered frames.");
+            ui.end_row();
+
+            ui.label("Uptime reset");
+            ui.add(egui::DragValue::new(&mut segmentation.uptime_reset_ms).range(0..=u32::MAX).prefix("by > ").suffix(" ms"))
+                .on_hover_text("Uptime going back this far starts a new session, even if the index kept counting");
+            ui.end_row();
+
+            ui.label("Time gap");
+            ui.add(egui::DragValue::new(&mut segmentation.time_gap_s).range(0.0..=86400.0).speed(1.0).suffix(" s"))
+                .on_hover_text("A longer pause in uptime starts a new session. 0 turns this off.");
+            ui.end_row();
+
+            ui.label("Reorder buffer");
+            ui.add(egui::DragValue::new(&mut segmentation.reorder_window).range(0..=1000).suffix(" frames"))
+                .on_hover_text("Recent frames are held back this long so that ones recieved out of order can be put in place. 0 turns the buffer off.");
+            ui.end_row();
+        });
+
+        ui.horizontal(|ui| {
+            if ui.button("Reset").clicked() {
+                self.segmentation = Segmentation::default();
+            }
+
+            if ui.button("Apply to open sources").on_hover_text("Names and manual edits of sessions are lost").clicked() {
+                self.event_detectors.clear();
+                self.altitude_trackers.clear();
+                for source in self.sources.iter_mut() {
+                    source.data_source.set_segmentation(self.segmentation);
+
+                    let lock = source.data_source.get_data_lock();
+                    let session_count = source.data_source.get_data(&lock).map_or(0, |data| data.sessions().len());
+                    drop(lock);
+                    source.current_session = source.current_session.min(session_count.saturating_sub(1));
+                }
+                ui.close();
+            }
+        });
+    }
+
+    fn altitude_menu(&mut self, ui: &mut egui::Ui) {
+        let settings = &mut self.altitude;
+
+        ui.label("Reference pressure");
+        ui.horizontal(|ui| {
+            let mut count = match settings.reference {
+                ReferencePressure::FirstRecords(count) => count,
+                ReferencePressure::Manual(_) => 10,
+            };
+            if ui.radio(matches!(settings.reference, ReferencePressure::FirstRecords(_)), "Mean of the first").clicked() {
+                settings.reference = ReferencePressure::FirstRecords(count);
+            }
+            if ui.add(egui::DragValue::new(&mut count).range(1..=10000).suffix(" records")).changed() {
+                settings.reference = ReferencePressure::FirstRecords(count);
+            }
+        });
+        ui.horizontal(|ui| {
+            let mut pressure = match settings.reference {
+                ReferencePressure::Manual(pressure) => pressure,
+                ReferencePressure::FirstRecords(_) => 101325.0,
+            };
+            if ui.radio(matches!(settings.reference, ReferencePressure::Manual(_)), "Entered").clicked() {
+                settings.reference = ReferencePressure::Manual(pressure);
+            }
+            if ui.add(egui::DragValue::new(&mut pressure).range(1.0..=200000.0).speed(10.0).suffix(" Pa")).changed() {
+                settings.reference = ReferencePressure::Manual(pressure);
+            }
+        });
+
+        let latest_pressure = self.sources.active().and_then(|source| {
+            let lock = source.data_source.get_data_lock();
+            let data = source.data_source.get_data(&lock)?;
+            let session = data.sessions().get(source.current_session)?;
+            session.iter().rev().map(|s| s.pressure as f64).find(|p| !p.is_nan())
+        });
+        if ui.add_enabled(latest_pressure.is_some(), egui::Button::new("Capture from the active source"))
+            .on_hover_text("Take the latest pressure recieved, e.g. while the probe waits on the launch pad")
+            .clicked()
+        {
+            settings.reference = ReferencePressure::Manual(latest_pressure.unwrap_or(101325.0));
+        }
+
+        ui.separator();
+
+        ui.checkbox(&mut settings.use_temperature, "Use the measured temperature")
+            .on_hover_text("Otherwise the air is taken to be at 15 °C");
+        ui.horizontal(|ui| {
+            ui.label("Smooth vertical speed over");
+            ui.add(egui::DragValue::new(&mut settings.smoothing_s).range(0.1..=60.0).speed(0.1).suffix(" s"));
+        });
+
+        if ui.button("Reset").clicked() {
+            self.altitude = AltitudeSettings::default();
+        }
+    }
+
+    /// Opens a capture file for a newly opened live source, if recording is enabled
+    fn start_capture(&mut self, source_name: &str) -> Option<(CaptureWriter, CaptureInfo)> {
+        if !self.capture_enabled {
+            return None;
+        }
+
+        match CaptureWriter::start(source_name) {
+            Ok(capture) => Some(capture),
+            Err(e) => {
+                self.set_short_status(format!("Unable to create capture file: {e}"));
+                None
+            },
+        }
     }
 
     fn set_status(&mut self, text: String, duration: Duration) {
@@ -0,0 +1,150 @@
+use std::{fs::{self, File, OpenOptions}, io::{self, Write}, path::{Path, PathBuf}, sync::{atomic::{AtomicU64, Ordering}, Arc}};
+
+use chrono::{DateTime, Local, TimeZone};
+use directories::ProjectDirs;
+
+/// Writes every line recieved from a live source to disk, prefixed with the
+/// time it arrived at, as `@<unix millis>\t<line>`. The result can be
+/// imported again with [`crate::data::MissionData::from_log`].
+pub struct CaptureWriter {
+    file: File,
+    bytes_written: Arc<AtomicU64>,
+}
+
+/// Shown to the user while a capture is running
+#[derive(Debug, Clone)]
+pub struct CaptureInfo {
+    pub path: PathBuf,
+    pub bytes_written: Arc<AtomicU64>,
+}
+
+impl CaptureWriter {
+    /// Creates the capture file, failing if it already exists
+    pub fn create(path: &Path, source_name: &str) -> io::Result<(CaptureWriter, CaptureInfo)> {
+        let mut file = OpenOptions::new().write(true).create_new(true).open(path)?;
+        let header = format!("-- Raw capture of {} started {}\n", source_name, Local::now().to_rfc3339());
+        file.write_all(header.as_bytes())?;
+
+        let bytes_written = Arc::new(AtomicU64::new(header.len() as u64));
+
+        Ok((
+            CaptureWriter { file, bytes_written: bytes_written.clone() },
+            CaptureInfo { path: path.to_owned(), bytes_written },
+        ))
+    }
+
+    /// Starts a capture in a new file in the data directory, named after the time and the source
+    pub fn start(source_name: &str) -> io::Result<(CaptureWriter, CaptureInfo)> {
+        let dir = ProjectDirs::from("eu", "vlospace", env!("CARGO_CRATE_NAME"))
+            .map(|dirs| dirs.data_dir().join("captures"))
+            .ok_or(io::Error::new(io::ErrorKind::NotFound, "no data directory available"))?;
+        fs::create_dir_all(&dir)?;
+
+        let stem = format!("capture-{}-{}", Local::now().format("%Y-%m-%d_%H-%M-%S"), file_name_part(source_name));
+        CaptureWriter::create_unique(&dir, &stem, source_name)
+    }
+
+    /// Creates `<stem>.log` in the directory, or `<stem>-2.log` and so on if that is taken,
+    /// so that sources opened in the same second never write to the same file
+    fn create_unique(dir: &Path, stem: &str, source_name: &str) -> io::Result<(CaptureWriter, CaptureInfo)> {
+        let mut attempt = 1;
+        loop {
+            let name = match attempt {
+                1 => format!("{stem}.log"),
+                n => format!("{stem}-{n}.log"),
+            };
+            match CaptureWriter::create(&dir.join(name), source_name) {
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => attempt += 1,
+                result => return result,
+            }
+        }
+    }
+
+    /// Appends a raw line, as recieved, to the capture
+    pub fn write_line(&mut self, line: &[u8]) -> io::Result<()> {
+        let line = line.strip_suffix(b"\n").unwrap_or(line);
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+
+        let mut buffer = format!("@{}\t", Local::now().timestamp_millis()).into_bytes();
+        buffer.extend_from_slice(line);
+        buffer.push(b'\n');
+
+        // Written in one go and not buffered, so nothing is lost if the app crashes
+        self.file.write_all(&buffer)?;
+        self.bytes_written.fetch_add(buffer.len() as u64, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+/// Source name reduced to characters that are safe in a file name on every platform
+fn file_name_part(source_name: &str) -> String {
+    let name: String = source_name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '.' { c } else { '_' })
+        .collect();
+    name.trim_matches(['_', '.']).to_owned()
+}
+
+/// Splits the host recieve time written by [`CaptureWriter`] off a line
+pub fn strip_host_timestamp(text: &str) -> (Option<DateTime<Local>>, &str) {
+    if let Some((timestamp, rest)) = text.strip_prefix('@').and_then(|t| t.split_once('\t')) {
+        if let Some(time) = timestamp.parse::<i64>().ok().and_then(|ms| Local.timestamp_millis_opt(ms).single()) {
+            return (Some(time), rest);
+        }
+    }
+
+    (None, text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::MissionData;
+
+    #[test]
+    fn test_strip_host_timestamp() {
+        let (time, text) = strip_host_timestamp("@1700000000123\t1\t2\t3");
+        assert_eq!(time.map(|t| t.timestamp_millis()), Some(1700000000123));
+        assert_eq!(text, "1\t2\t3");
+
+        assert_eq!(strip_host_timestamp("1\t2\t3"), (None, "1\t2\t3"));
+        assert_eq!(strip_host_timestamp("@abc\t2"), (None, "@abc\t2"));
+    }
+
+    #[test]
+    fn test_captures_kept_apart() {
+        assert_eq!(file_name_part("/dev/ttyUSB0"), "dev_ttyUSB0");
+        assert_eq!(file_name_part("TCP 10.0.0.2:5000"), "TCP_10.0.0.2_5000");
+
+        let dir = std::env::temp_dir();
+        let stem = format!("gs_viewer_capture_unique_{}", std::process::id());
+        let (_, first) = CaptureWriter::create_unique(&dir, &stem, "A").unwrap();
+        let (_, second) = CaptureWriter::create_unique(&dir, &stem, "B").unwrap();
+        let first_text = fs::read_to_string(&first.path).unwrap();
+        fs::remove_file(&first.path).unwrap();
+        fs::remove_file(&second.path).unwrap();
+
+        assert_eq!(second.path, dir.join(format!("{stem}-2.log")));
+        assert!(first_text.starts_with("-- Raw capture of A "));
+    }
+
+    #[test]
+    fn test_capture_can_be_imported() {
+        let path = std::env::temp_dir().join(format!("gs_viewer_capture_test_{}.log", std::process::id()));
+        let frame = b"670\t38076\t929\t27.19\t98709.02\t0\t0\t0\t3\t0\t0\t0\t0\t0\tnan\tnan\t0\r\n";
+
+        let (mut writer, info) = CaptureWriter::create(&path, "test").unwrap();
+        writer.write_line(frame).unwrap();
+        writer.write_line(b"garbage\xff\n").unwrap();
+        drop(writer);
+
+        let text = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(info.bytes_written.load(Ordering::Relaxed), text.len() as u64);
+
+        let data = MissionData::from_log(&String::from_utf8_lossy(&text), Arc::default());
+        assert_eq!(data.sessions().len(), 1);
+        assert_eq!(data.sessions()[0][0].index, 670);
+        assert_eq!(data.rejected_count(), 1);
+        assert_eq!(data.rejected_lines()[0].text, "garbage\u{FFFD}");
+    }
+}
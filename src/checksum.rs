@@ -0,0 +1,86 @@
+/// Algorithm used to protect a telemetry frame
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChecksumKind {
+    /// XOR of all bytes, like in NMEA sentences. Sent as 2 hex digits.
+    Xor,
+    /// CRC-16/CCITT-FALSE (polynomial 0x1021, initial value 0xFFFF). Sent as 4 hex digits.
+    Crc16,
+}
+
+impl ChecksumKind {
+    pub fn from_name(name: &str) -> Option<ChecksumKind> {
+        match name {
+            "xor" => Some(ChecksumKind::Xor),
+            "crc16" => Some(ChecksumKind::Crc16),
+            _ => None,
+        }
+    }
+
+    /// Guesses the algorithm from the number of hex digits of a checksum
+    pub fn from_digit_count(digits: usize) -> Option<ChecksumKind> {
+        match digits {
+            2 => Some(ChecksumKind::Xor),
+            4 => Some(ChecksumKind::Crc16),
+            _ => None,
+        }
+    }
+
+    pub fn compute(&self, bytes: &[u8]) -> u16 {
+        match self {
+            ChecksumKind::Xor => xor(bytes) as u16,
+            ChecksumKind::Crc16 => crc16(bytes),
+        }
+    }
+}
+
+pub fn xor(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0, |acc, b| acc ^ b)
+}
+
+pub fn crc16(bytes: &[u8]) -> u16 {
+    let mut crc: u16 = 0xFFFF;
+
+    for byte in bytes {
+        crc ^= (*byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+
+    crc
+}
+
+/// Splits a trailing `*XX` checksum column off a frame. Returns the protected part
+/// of the frame and the checksum text without the asterisk.
+pub fn split_checksum(text: &str) -> (&str, Option<&str>) {
+    match text.rsplit_once('\t') {
+        Some((frame, checksum)) if checksum.starts_with('*') => (frame, Some(&checksum[1..])),
+        _ => (text, None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_xor() {
+        // Checksum of a well known NMEA sentence
+        assert_eq!(xor(b"GPGGA,092750.000,5321.6802,N,00630.3372,W,1,8,1.03,61.7,M,55.2,M,,"), 0x76);
+    }
+
+    #[test]
+    fn test_crc16() {
+        assert_eq!(crc16(b"123456789"), 0x29B1);
+    }
+
+    #[test]
+    fn test_split_checksum() {
+        assert_eq!(split_checksum("1\t2\t*4F"), ("1\t2", Some("4F")));
+        assert_eq!(split_checksum("1\t2"), ("1\t2", None));
+    }
+}
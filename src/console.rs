@@ -0,0 +1,367 @@
+use chrono::{DateTime, Local, TimeDelta};
+use egui::{Color32, RichText, Ui};
+use serde::{Deserialize, Serialize};
+
+use crate::reader::{ReaderHandle, Reply};
+
+/// How long to wait for a reply before a command is considered unacknowledged
+const ACK_TIMEOUT: TimeDelta = TimeDelta::seconds(5);
+
+/// How many sent commands are kept in the history
+const MAX_HISTORY: usize = 200;
+
+/// A command that can be sent to the probe with a single click
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CommandDefinition {
+    pub name: String,
+    /// Line written to the connection
+    pub text: String,
+    /// Asks for confirmation before sending
+    pub dangerous: bool,
+    /// Text a reply must contain to acknowledge the command. Empty if no reply is expected.
+    pub ack: String,
+}
+
+impl CommandDefinition {
+    fn new(name: &str, text: &str, dangerous: bool, ack: &str) -> Self {
+        Self { name: name.to_owned(), text: text.to_owned(), dangerous, ack: ack.to_owned() }
+    }
+}
+
+pub fn default_catalog() -> Vec<CommandDefinition> {
+    vec![
+        CommandDefinition::new("Arm", "ARM", true, "ACK ARM"),
+        CommandDefinition::new("Disarm", "DISARM", false, "ACK DISARM"),
+        CommandDefinition::new("Buzzer on", "BUZZER 1", false, "ACK BUZZER"),
+        CommandDefinition::new("Buzzer off", "BUZZER 0", false, "ACK BUZZER"),
+        CommandDefinition::new("Reset index", "RESET INDEX", true, "ACK RESET"),
+    ]
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CommandStatus {
+    /// Sent, no reply expected
+    Sent,
+    AwaitingAck,
+    /// Acknowledged by the given reply
+    Acknowledged(String),
+    TimedOut,
+    /// Could not be written to the connection
+    Failed(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SentCommand {
+    pub text: String,
+    pub ack: String,
+    pub sent: DateTime<Local>,
+    pub status: CommandStatus,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ConsoleState {
+    pub catalog: Vec<CommandDefinition>,
+    history: Vec<SentCommand>,
+
+    input: String,
+    /// Position in the history recalled with the arrow keys
+    recalled: Option<usize>,
+    /// Dangerous command waiting for confirmation
+    confirming: Option<CommandDefinition>,
+}
+
+impl ConsoleState {
+    pub fn new(catalog: Vec<CommandDefinition>) -> Self {
+        Self { catalog, ..Default::default() }
+    }
+
+    pub fn history(&self) -> &[SentCommand] {
+        &self.history
+    }
+
+    /// Finds the catalog entry for typed text, matching its name or text
+    fn definition_for(&self, input: &str) -> CommandDefinition {
+        let input = input.trim();
+
+        self.catalog.iter()
+            .find(|c| c.text.eq_ignore_ascii_case(input) || c.name.eq_ignore_ascii_case(input))
+            .cloned()
+            .unwrap_or_else(|| CommandDefinition::new(input, input, false, ""))
+    }
+
+    /// Sends a command, or asks for confirmation first if it's dangerous
+    fn request(&mut self, uplink: &ReaderHandle, command: CommandDefinition) {
+        if command.dangerous {
+            self.confirming = Some(command);
+        } else {
+            self.send(uplink, command);
+        }
+    }
+
+    fn send(&mut self, uplink: &ReaderHandle, command: CommandDefinition) {
+        let sent = Local::now();
+        // Waited for before sending, as the reply may come back before `send` returns
+        if !command.ack.is_empty() {
+            uplink.await_reply(&command.ack, sent + ACK_TIMEOUT);
+        }
+
+        let result = uplink.send(&command.text).map_err(|e| e.to_string());
+        if result.is_err() && !command.ack.is_empty() {
+            uplink.cancel_reply(&command.ack, sent + ACK_TIMEOUT);
+        }
+        self.record(&command, result, sent);
+    }
+
+    /// Adds a command to the history
+    pub fn record(&mut self, command: &CommandDefinition, result: Result<(), String>, sent: DateTime<Local>) {
+        let status = match result {
+            Err(e) => CommandStatus::Failed(e),
+            Ok(()) if command.ack.is_empty() => CommandStatus::Sent,
+            Ok(()) => CommandStatus::AwaitingAck,
+        };
+
+        if self.history.len() >= MAX_HISTORY {
+            self.history.remove(0);
+        }
+        self.history.push(SentCommand { text: command.text.clone(), ack: command.ack.clone(), sent, status });
+    }
+
+    /// Marks the commands acknowledged by the given replies, and the ones that waited too long as timed out
+    pub fn match_replies(&mut self, replies: &[Reply], now: DateTime<Local>) {
+        for reply in replies {
+            let waiting = self.history.iter_mut().find(|command| {
+                command.status == CommandStatus::AwaitingAck
+                    && command.sent <= reply.recieved
+                    && reply.text.contains(&command.ack)
+            });
+
+            if let Some(command) = waiting {
+                command.status = CommandStatus::Acknowledged(reply.text.clone());
+            }
+        }
+
+        for command in self.history.iter_mut() {
+            if command.status == CommandStatus::AwaitingAck && now - command.sent > ACK_TIMEOUT {
+                command.status = CommandStatus::TimedOut;
+            }
+        }
+    }
+}
+
+/// Sends commands over `uplink`, the connection of the current data source, if any
+pub fn console_panel(ui: &mut Ui, state: &mut ConsoleState, uplink: Option<&ReaderHandle>) {
+    let uplink = uplink.filter(|u| u.can_send());
+
+    ui.heading("Command console");
+    match uplink {
+        Some(_) => ui.label("Uplink ready"),
+        None => ui.colored_label(ui.visuals().warn_fg_color, "⚠ The current data source can't send commands"),
+    };
+
+    ui.separator();
+
+    ui.add_enabled_ui(uplink.is_some(), |ui| {
+        ui.horizontal_wrapped(|ui| {
+            for command in state.catalog.clone() {
+                let label = if command.dangerous {
+                    RichText::new(format!("⚠ {}", command.name)).color(ui.visuals().error_fg_color)
+                } else {
+                    RichText::new(&command.name)
+                };
+
+                if ui.button(label).on_hover_text(&command.text).clicked() {
+                    if let Some(uplink) = uplink {
+                        state.request(uplink, command);
+                    }
+                }
+            }
+        });
+
+        ui.horizontal(|ui| {
+            let input = ui.add(egui::TextEdit::singleline(&mut state.input).hint_text("Command").desired_width(180.0));
+
+            if input.has_focus() {
+                recall_history(ui, state);
+            }
+
+            let entered = input.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+            if (ui.button("Send").clicked() || entered) && !state.input.trim().is_empty() {
+                if let Some(uplink) = uplink {
+                    let command = state.definition_for(&state.input);
+                    state.request(uplink, command);
+                    state.input.clear();
+                    state.recalled = None;
+                }
+                input.request_focus();
+            }
+        });
+    });
+
+    ui.separator();
+
+    egui::CollapsingHeader::new("Catalog").show(ui, |ui| {
+        catalog_editor(ui, &mut state.catalog);
+    });
+
+    ui.separator();
+
+    egui::ScrollArea::vertical().stick_to_bottom(true).auto_shrink([false, false]).show(ui, |ui| {
+        if state.history().is_empty() {
+            ui.weak("No commands sent yet.");
+        }
+
+        egui::Grid::new("command_history_grid").num_columns(3).striped(true).show(ui, |ui| {
+            for command in state.history() {
+                ui.label(command.sent.format("%H:%M:%S").to_string());
+                ui.monospace(&command.text);
+
+                match &command.status {
+                    CommandStatus::Sent => ui.label("Sent"),
+                    CommandStatus::AwaitingAck => ui.label("Waiting for reply…"),
+                    CommandStatus::Acknowledged(reply) => ui.colored_label(Color32::from_rgb(0, 160, 0), "✔ Acknowledged").on_hover_text(reply),
+                    CommandStatus::TimedOut => ui.colored_label(ui.visuals().warn_fg_color, format!("⚠ No \"{}\"", command.ack)),
+                    CommandStatus::Failed(e) => ui.colored_label(ui.visuals().error_fg_color, "⚠ Failed").on_hover_text(e),
+                };
+                ui.end_row();
+            }
+        });
+    });
+
+    if let (Some(command), Some(uplink)) = (state.confirming.clone(), uplink) {
+        let modal = egui::Modal::new(egui::Id::new("confirm_command_modal")).show(ui.ctx(), |ui| {
+            ui.heading(format!("Send \"{}\"?", command.name));
+            ui.label(format!("This will send {} to the probe.", command.text));
+
+            ui.separator();
+
+            ui.horizontal(|ui| {
+                if ui.button(RichText::new("Send").color(ui.visuals().error_fg_color)).clicked() {
+                    state.send(uplink, command.clone());
+                    state.confirming = None;
+                }
+                if ui.button("Cancel").clicked() {
+                    state.confirming = None;
+                }
+            });
+        });
+
+        if modal.should_close() {
+            state.confirming = None;
+        }
+    }
+}
+
+/// Steps through the history of sent commands with the up and down arrows
+fn recall_history(ui: &Ui, state: &mut ConsoleState) {
+    let (up, down) = ui.input(|i| (i.key_pressed(egui::Key::ArrowUp), i.key_pressed(egui::Key::ArrowDown)));
+    let len = state.history.len();
+
+    let recalled = match (up, down, state.recalled) {
+        (true, _, None) if len > 0 => Some(len - 1),
+        (true, _, Some(i)) => Some(i.saturating_sub(1)),
+        (_, true, Some(i)) if i + 1 < len => Some(i + 1),
+        (_, true, Some(_)) => None,
+        _ => return,
+    };
+
+    state.recalled = recalled;
+    state.input = recalled.map_or_else(String::new, |i| state.history[i].text.clone());
+}
+
+fn catalog_editor(ui: &mut Ui, catalog: &mut Vec<CommandDefinition>) {
+    let mut removed = None;
+
+    egui::Grid::new("command_catalog_grid").num_columns(5).show(ui, |ui| {
+        ui.label("Name");
+        ui.label("Sends");
+        ui.label("Reply");
+        ui.label("Confirm");
+        ui.end_row();
+
+        for (i, command) in catalog.iter_mut().enumerate() {
+            ui.add(egui::TextEdit::singleline(&mut command.name).desired_width(80.0));
+            ui.add(egui::TextEdit::singleline(&mut command.text).desired_width(80.0));
+            ui.add(egui::TextEdit::singleline(&mut command.ack).desired_width(80.0).hint_text("None"));
+            ui.checkbox(&mut command.dangerous, "");
+            if ui.button("🗑").clicked() {
+                removed = Some(i);
+            }
+            ui.end_row();
+        }
+    });
+
+    if let Some(i) = removed {
+        catalog.remove(i);
+    }
+
+    ui.horizontal(|ui| {
+        if ui.button("Add command").clicked() {
+            catalog.push(CommandDefinition::new("New command", "", false, ""));
+        }
+        if ui.button("Reset to defaults").clicked() {
+            *catalog = default_catalog();
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn arm() -> CommandDefinition {
+        default_catalog().remove(0)
+    }
+
+    #[test]
+    fn test_definition_for_input() {
+        let state = ConsoleState::new(default_catalog());
+        assert_eq!(state.definition_for("arm"), arm());
+        assert_eq!(state.definition_for(" ARM "), arm());
+        assert_eq!(state.definition_for("PING").ack, "");
+    }
+
+    fn reply(text: &str, recieved: DateTime<Local>) -> Reply {
+        Reply { text: text.to_owned(), recieved }
+    }
+
+    #[test]
+    fn test_ack_matching() {
+        let mut state = ConsoleState::new(default_catalog());
+        let sent = Local::now();
+
+        state.record(&arm(), Ok(()), sent);
+        state.record(&CommandDefinition::new("Ping", "PING", false, ""), Ok(()), sent);
+        state.record(&arm(), Err("broken pipe".to_owned()), sent);
+        assert_eq!(state.history()[1].status, CommandStatus::Sent);
+
+        state.match_replies(&[reply("ACK BUZZER", sent)], sent);
+        assert_eq!(state.history()[0].status, CommandStatus::AwaitingAck);
+
+        state.match_replies(&[reply("ACK ARM ok", sent)], sent);
+        assert_eq!(state.history()[0].status, CommandStatus::Acknowledged("ACK ARM ok".to_owned()));
+        assert_eq!(state.history()[2].status, CommandStatus::Failed("broken pipe".to_owned()));
+    }
+
+    #[test]
+    fn test_ack_timeout() {
+        let mut state = ConsoleState::new(default_catalog());
+        let sent = Local::now();
+        state.record(&arm(), Ok(()), sent);
+
+        state.match_replies(&[], sent + TimeDelta::seconds(1));
+        assert_eq!(state.history()[0].status, CommandStatus::AwaitingAck);
+
+        state.match_replies(&[], sent + TimeDelta::seconds(6));
+        assert_eq!(state.history()[0].status, CommandStatus::TimedOut);
+    }
+
+    #[test]
+    fn test_replies_before_sending_ignored() {
+        let mut state = ConsoleState::new(default_catalog());
+        let sent = Local::now();
+
+        state.record(&arm(), Ok(()), sent);
+        state.match_replies(&[reply("ACK ARM", sent - TimeDelta::seconds(1))], sent);
+        assert_eq!(state.history()[0].status, CommandStatus::AwaitingAck);
+    }
+}
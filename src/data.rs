@@ -1,54 +1,421 @@
-use std::{fmt::Display, str::FromStr, vec};
+use std::{collections::VecDeque, fmt::Display, str::FromStr, sync::Arc, vec};
 
+use chrono::{DateTime, Local};
 use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::{capture::strip_host_timestamp, checksum::{split_checksum, ChecksumKind}, schema::{FieldType, FieldValue, TelemetrySchema}};
 
 /// A record of an entire mission - an entire log file, or data
 /// recieved from the radio possibly across multiple CanSat sessions
 #[derive(Clone, Default, Debug, PartialEq)]
 pub struct MissionData {
+    schema: Arc<TelemetrySchema>,
     sessions: Vec<Vec<SensedData>>,
-    last_index: Option<u32>,
+    /// Bookkeeping of each session
+    session_info: Vec<SessionInfo>,
+    segmentation: Segmentation,
+    /// Recent frames held back to be put in order, ordered by index
+    reorder_buffer: Vec<BufferedFrame>,
+
+    /// Corrupted frames recieved before the first session started
+    pending_corrupted: usize,
+
+    /// Number of lines passed to [`MissionData::parse_line`]
+    line_count: usize,
+    /// The most recent lines that could not be parsed
+    rejected: VecDeque<RejectedLine>,
+    rejected_count: usize,
+}
+
+#[derive(Clone, Default, Debug, PartialEq)]
+struct SessionInfo {
+    /// Name given by the user
+    name: Option<String>,
+    /// Number of frames with an invalid checksum
+    corrupted: usize,
+    /// Frames recieved after a frame with a higher index
+    reordered: usize,
+    /// Frames recieved again and dropped
+    duplicates: usize,
+    /// Position of the first frame of the last session merged into this one. Only the frames from
+    /// here on are ordered by index, the ones before belong to earlier boots of the probe.
+    merged_at: usize,
+}
+
+impl SessionInfo {
+    fn absorb(&mut self, other: SessionInfo) {
+        self.name = self.name.take().or(other.name);
+        self.corrupted += other.corrupted;
+        self.reordered += other.reordered;
+        self.duplicates += other.duplicates;
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct BufferedFrame {
+    data: SensedData,
+    reordered: bool,
+    /// Copies of the frame recieved while it was held
+    duplicates: usize,
+}
+
+/// Rules deciding when a recieved frame starts a new session
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Segmentation {
+    /// An index going back to this value or below is taken as a restart of the probe.
+    /// Frames whose index goes back less far are reordered frames of the current session.
+    pub index_reset: u32,
+    /// Uptime going back by more than this many milliseconds is taken as a restart,
+    /// even if the index kept counting
+    pub uptime_reset_ms: u32,
+    /// Uptime jumping forward by more than this many seconds starts a new session, 0 disables it
+    pub time_gap_s: f64,
+    /// Number of recent frames held back so that reordered ones can be put in place before
+    /// the segmentation sees them, 0 disables the buffer
+    pub reorder_window: usize,
+}
+
+impl Default for Segmentation {
+    fn default() -> Self {
+        Self {
+            index_reset: 10,
+            uptime_reset_ms: 2000,
+            time_gap_s: 0.0,
+            reorder_window: 0,
+        }
+    }
+}
+
+impl Segmentation {
+    /// Whether `next` belongs to a new session rather than to the one ending with `last`
+    pub(crate) fn starts_session(&self, last: &SensedData, next: &SensedData) -> bool {
+        self.index_restarts(last, next) || self.uptime_restarts(last, next)
+    }
+
+    /// Whether the index going back from `last` to `next` means the probe restarted
+    fn index_restarts(&self, last: &SensedData, next: &SensedData) -> bool {
+        next.index < last.index && next.index <= self.index_reset
+    }
+
+    /// Whether the uptime going back or jumping forward from `last` to `next` starts a new session
+    fn uptime_restarts(&self, last: &SensedData, next: &SensedData) -> bool {
+        if last.uptime.saturating_sub(next.uptime) > self.uptime_reset_ms {
+            return true;
+        }
+
+        self.time_gap_s > 0.0 && next.uptime.saturating_sub(last.uptime) as f64 > self.time_gap_s * 1000.0
+    }
+}
+
+/// How many rejected lines are kept for inspection
+const MAX_REJECTED_LINES: usize = 1000;
+
+/// A line that was not accepted as a telemetry frame
+#[derive(Clone, Debug, PartialEq)]
+pub struct RejectedLine {
+    pub text: String,
+    pub error: LogReadError,
+    pub recieved: DateTime<Local>,
+    /// 1-based number of the line within the log or connection
+    pub line_number: usize,
 }
 
 impl MissionData {
-    pub fn new() -> Self {
-        Self { sessions: vec![], last_index: None }
+    pub fn with_schema(schema: Arc<TelemetrySchema>) -> Self {
+        Self { schema, ..Default::default() }
     }
 
-    pub fn from_log(text: &str) -> MissionData {
-        let mut data = MissionData::new();
+    pub fn with_segmentation(mut self, segmentation: Segmentation) -> Self {
+        self.segmentation = segmentation;
+        self
+    }
 
-        for line in text.lines() {
-            if line.starts_with("--") {
-                continue;
-            }
+    pub fn from_log(text: &str, schema: Arc<TelemetrySchema>) -> MissionData {
+        let mut data = MissionData::with_schema(schema);
 
+        for line in text.lines() {
             if let Err(err) = data.parse_line(line) {
                 warn!("Failed to parse log line: {err:?}");
             }
         }
+        data.flush_reorder_buffer();
 
         data
     }
 
+    pub fn schema(&self) -> &TelemetrySchema {
+        &self.schema
+    }
+
     pub fn sessions(&self) -> &[Vec<SensedData>] {
         &self.sessions
     }
 
+    /// The name the user gave to a session, if any
+    pub fn session_name(&self, session: usize) -> Option<&str> {
+        self.session_info.get(session)?.name.as_deref()
+    }
+
+    pub fn segmentation(&self) -> Segmentation {
+        self.segmentation
+    }
+
+    /// Number of frames rejected because of a checksum mismatch during a session
+    pub fn corrupted_frames(&self, session: usize) -> usize {
+        self.session_info.get(session).map_or(0, |info| info.corrupted)
+    }
+
+    /// Number of frames of a session that arrived after a frame with a higher index
+    pub fn reordered_frames(&self, session: usize) -> usize {
+        self.session_info.get(session).map_or(0, |info| info.reordered)
+    }
+
+    /// Number of frames of a session that were recieved more than once, not counting the first copy
+    pub fn duplicate_frames(&self, session: usize) -> usize {
+        self.session_info.get(session).map_or(0, |info| info.duplicates)
+    }
+
+    /// Number of frames held in the reorder buffer, not yet part of a session
+    pub fn buffered_frames(&self) -> usize {
+        self.reorder_buffer.len()
+    }
+
+    /// The most recent rejected lines, oldest first
+    pub fn rejected_lines(&self) -> &VecDeque<RejectedLine> {
+        &self.rejected
+    }
+
+    /// Number of lines rejected since the start, including ones no longer kept
+    pub fn rejected_count(&self) -> usize {
+        self.rejected_count
+    }
+
+    /// Parses a line and adds it to the current session. Empty lines and
+    /// comments starting with `--` are skipped; any other line that fails
+    /// to parse is recorded in [`MissionData::rejected_lines`]. Lines may be
+    /// prefixed with the host recieve time written by raw captures.
     pub fn parse_line(&mut self, text: &str) -> Result<(), LogReadError> {
-        let data = parse_log_line(text)?;
+        self.line_count += 1;
 
-        if self.last_index.is_none() || data.index < self.last_index.unwrap_or(0) {
-            self.sessions.push(vec![data]);
-        } else {
-            self.sessions.last_mut().expect("A session should have been added by now")
-                .push(data);
+        let (recieved, text) = strip_host_timestamp(text);
+
+        if text.trim().is_empty() || text.starts_with("--") {
+            return Ok(());
         }
 
-        self.last_index = Some(data.index);
+        let data = match parse_log_line(&self.schema, text) {
+            Ok(data) => data,
+            Err(err) => {
+                if let LogReadError::ChecksumMismatch { .. } = err {
+                    match self.session_info.last_mut() {
+                        Some(info) => info.corrupted += 1,
+                        None => self.pending_corrupted += 1,
+                    }
+                }
+
+                if self.rejected.len() >= MAX_REJECTED_LINES {
+                    self.rejected.pop_front();
+                }
+                self.rejected.push_back(RejectedLine {
+                    text: text.trim_end().to_owned(),
+                    error: err.clone(),
+                    recieved: recieved.unwrap_or_else(Local::now),
+                    line_number: self.line_count,
+                });
+                self.rejected_count += 1;
+
+                return Err(err);
+            }
+        };
+        self.recieve_frame(data);
 
         Ok(())
     }
+
+    /// Takes in a parsed frame. Duplicates are dropped, other frames go through the reorder
+    /// buffer, which releases the oldest one once it holds more than its window.
+    pub(crate) fn recieve_frame(&mut self, data: SensedData) {
+        let newest = self.reorder_buffer.last().map(|buffered| &buffered.data)
+            .into_iter()
+            .chain(self.sessions.last().and_then(|frames| frames.last()))
+            .max_by_key(|frame| frame.index);
+
+        // A frame that still fits after the released ones was only delayed, even if its index went
+        // further back than the segmentation would allow. Its uptime is still checked.
+        let in_window = !self.reorder_buffer.is_empty() && self.sessions.last()
+            .and_then(|frames| frames.last())
+            .is_some_and(|released| data.index > released.index);
+
+        let (starts_session, reordered) = match newest {
+            Some(newest) => {
+                let starts_session = self.segmentation.uptime_restarts(newest, &data)
+                    || !in_window && self.segmentation.index_restarts(newest, &data);
+                (starts_session, data.index < newest.index)
+            },
+            None => (true, false),
+        };
+
+        // Frames of the previous session are never held back behind the start of a new one
+        if starts_session {
+            self.flush_reorder_buffer();
+            self.start_session(vec![data]);
+            return;
+        }
+
+        if let Some(buffered) = self.reorder_buffer.iter_mut().find(|buffered| buffered.data.index == data.index) {
+            buffered.duplicates += 1;
+            return;
+        }
+        if let (Some(frames), Some(info)) = (self.sessions.last(), self.session_info.last_mut()) {
+            if frames[info.merged_at..].binary_search_by_key(&data.index, |frame| frame.index).is_ok() {
+                info.duplicates += 1;
+                return;
+            }
+        }
+
+        let position = self.reorder_buffer.partition_point(|buffered| buffered.data.index <= data.index);
+        self.reorder_buffer.insert(position, BufferedFrame { data, reordered, duplicates: 0 });
+
+        while self.reorder_buffer.len() > self.segmentation.reorder_window {
+            let buffered = self.reorder_buffer.remove(0);
+            self.release_frame(buffered);
+        }
+    }
+
+    /// Moves all frames held in the reorder buffer into the current session
+    pub fn flush_reorder_buffer(&mut self) {
+        for buffered in std::mem::take(&mut self.reorder_buffer) {
+            self.release_frame(buffered);
+        }
+    }
+
+    fn release_frame(&mut self, buffered: BufferedFrame) {
+        self.append_frame(buffered.data);
+
+        let info = self.session_info.last_mut().expect("A session should have been added by now");
+        info.reordered += buffered.reordered as usize;
+        info.duplicates += buffered.duplicates;
+    }
+
+    /// Adds a frame to the current session, or starts a new one if the
+    /// [`Segmentation`] rules say the probe restarted
+    fn push_frame(&mut self, data: SensedData) {
+        let last = self.sessions.last().and_then(|frames| frames.last());
+
+        match last {
+            Some(last) if !self.segmentation.starts_session(last, &data) => self.append_frame(data),
+            _ => self.start_session(vec![data]),
+        }
+    }
+
+    /// Adds a frame to the current session in order of index
+    fn append_frame(&mut self, data: SensedData) {
+        match self.sessions.len().checked_sub(1) {
+            Some(session) => self.insert_ordered(session, data),
+            None => self.start_session(vec![data]),
+        }
+    }
+
+    /// Inserts a frame among the frames of the last boot merged into the session, in order of index
+    fn insert_ordered(&mut self, session: usize, data: SensedData) {
+        let start = self.session_info[session].merged_at;
+        let frames = &mut self.sessions[session];
+        let position = start + frames[start..].partition_point(|frame| frame.index <= data.index);
+        frames.insert(position, data);
+    }
+
+    /// Inserts a frame into the given session, keeping it ordered by index.
+    /// Sessions up to the given one are started if they don't exist yet.
+    pub(crate) fn insert_frame(&mut self, session: usize, data: SensedData) {
+        while self.sessions.len() <= session {
+            self.start_session(vec![]);
+        }
+
+        self.insert_ordered(session, data);
+    }
+
+    fn start_session(&mut self, frames: Vec<SensedData>) {
+        self.sessions.push(frames);
+        self.session_info.push(SessionInfo {
+            corrupted: std::mem::take(&mut self.pending_corrupted),
+            ..Default::default()
+        });
+    }
+
+    /// Splits the sessions again with different rules. Names and manual edits are lost;
+    /// the counters of an old session go to the first new session its frames ended up in.
+    pub fn resegment(&mut self, segmentation: Segmentation) {
+        self.flush_reorder_buffer();
+        self.segmentation = segmentation;
+
+        let sessions = std::mem::take(&mut self.sessions);
+        let session_info = std::mem::take(&mut self.session_info);
+
+        for (frames, info) in sessions.into_iter().zip(session_info) {
+            let first_new = self.sessions.len();
+            for frame in frames {
+                self.push_frame(frame);
+            }
+
+            let target = first_new.min(self.session_info.len().saturating_sub(1));
+            match self.session_info.get_mut(target) {
+                Some(target) => target.absorb(SessionInfo { name: None, ..info }),
+                None => self.pending_corrupted += info.corrupted,
+            }
+        }
+    }
+
+    /// Moves the frames of a session from the given position on into a new session right after it
+    pub fn split_session(&mut self, session: usize, at: usize) {
+        let Some(frames) = self.sessions.get_mut(session) else {
+            return;
+        };
+        if at == 0 || at >= frames.len() {
+            return;
+        }
+
+        let tail = frames.split_off(at);
+        self.sessions.insert(session + 1, tail);
+
+        let info = &mut self.session_info[session];
+        let merged_at = info.merged_at.saturating_sub(at);
+        if info.merged_at >= at {
+            info.merged_at = 0;
+        }
+        self.session_info.insert(session + 1, SessionInfo { merged_at, ..Default::default() });
+    }
+
+    /// Joins the session after the given one to it. The frames stay in the order they were
+    /// recieved, so two boots of the probe that share indices are kept apart.
+    pub fn merge_with_next(&mut self, session: usize) {
+        if session + 1 >= self.sessions.len() {
+            return;
+        }
+
+        let next = self.sessions.remove(session + 1);
+        let frames = &mut self.sessions[session];
+        let merged_at = frames.len() + self.session_info[session + 1].merged_at;
+        frames.extend(next);
+        let next_info = self.session_info.remove(session + 1);
+        let info = &mut self.session_info[session];
+        info.absorb(next_info);
+        info.merged_at = merged_at;
+    }
+
+    pub fn rename_session(&mut self, session: usize, name: Option<String>) {
+        if let Some(info) = self.session_info.get_mut(session) {
+            info.name = name.filter(|name| !name.trim().is_empty());
+        }
+    }
+
+    pub fn delete_session(&mut self, session: usize) {
+        if session < self.sessions.len() {
+            self.sessions.remove(session);
+            self.session_info.remove(session);
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Clone, Copy)]
@@ -73,10 +440,12 @@ impl TryFrom<u8> for ReadConfidence {
     }
 }
 
-#[derive(Debug, PartialEq, Clone, Copy)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct SensedData {
     pub index: u32,
     pub uptime: u32,
+    /// Sub-millisecond part of the uptime, in microseconds
+    pub micros: u32,
 
     pub temperature: f32,
     pub pressure: f32,
@@ -84,24 +453,91 @@ pub struct SensedData {
     pub acceleration: [f64; 3],
     pub acceleration_confidence: ReadConfidence,
 
+    pub gyroscope: [f64; 3],
+    pub gyroscope_confidence: ReadConfidence,
+
     pub gps_time: u32,
     pub gps_position: [f64; 2],
-    pub gps_altitude: f64
+    pub gps_altitude: f64,
+
+    /// Values of the schema fields without a dedicated member, in schema order
+    pub extra: Vec<f64>,
 }
 
-#[derive(Debug, PartialEq)]
+impl Default for SensedData {
+    /// An empty record, with every measurement missing
+    fn default() -> Self {
+        Self {
+            index: 0,
+            uptime: 0,
+            micros: 0,
+            temperature: f32::NAN,
+            pressure: f32::NAN,
+            acceleration: [f64::NAN; 3],
+            acceleration_confidence: ReadConfidence::Unreliable,
+            gyroscope: [f64::NAN; 3],
+            gyroscope_confidence: ReadConfidence::Unreliable,
+            gps_time: 0,
+            gps_position: [f64::NAN; 2],
+            gps_altitude: f64::NAN,
+            extra: vec![],
+        }
+    }
+}
+
+impl SensedData {
+    /// Time since the probe booted in microseconds, combining `uptime` and `micros`
+    pub fn timestamp(&self) -> u64 {
+        self.uptime as u64 * 1000 + self.micros.min(999) as u64
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
 pub enum LogReadError {
     ParseError { msg: String, value: Option<String> },
     ReadError,
+    /// The frame has no checksum, but the schema requires one
+    MissingChecksum,
+    ChecksumMismatch { expected: u16, actual: u16 },
+}
+
+impl Display for LogReadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LogReadError::ParseError { msg, value: Some(value) } => write!(f, "{msg} (\"{value}\")"),
+            LogReadError::ParseError { msg, value: None } => write!(f, "{msg}"),
+            LogReadError::ReadError => write!(f, "Not enough values"),
+            LogReadError::MissingChecksum => write!(f, "Missing checksum"),
+            LogReadError::ChecksumMismatch { expected, actual } => write!(f, "Checksum mismatch (expected {expected:X}, got {actual:X})"),
+        }
+    }
 }
 
-fn parse_log_line(text: &str) -> Result<SensedData, LogReadError> {
+pub(crate) fn parse_log_line(schema: &TelemetrySchema, text: &str) -> Result<SensedData, LogReadError> {
     let text = text.trim_start().trim_end();
 
+    let text = match split_checksum(text) {
+        (frame, Some(checksum)) => {
+            let kind = schema.checksum()
+                .or(ChecksumKind::from_digit_count(checksum.len()))
+                .ok_or_else(|| LogReadError::ParseError { msg: "Unknown checksum format".to_owned(), value: Some(checksum.to_owned()) })?;
+            let expected = u16::from_str_radix(checksum, 16)
+                .map_err(|e| LogReadError::ParseError { msg: e.to_string(), value: Some(checksum.to_owned()) })?;
+            let actual = kind.compute(frame.as_bytes());
+
+            if expected != actual {
+                return Err(LogReadError::ChecksumMismatch { expected, actual });
+            }
+
+            frame
+        },
+        (_, None) if schema.checksum().is_some() => return Err(LogReadError::MissingChecksum),
+        (frame, None) => frame,
+    };
+
     let mut iterator = text.split('\t');
 
-    fn try_parse_next<T: std::str::FromStr>(iter: & mut dyn Iterator<Item = & str>) -> Result<T, LogReadError> where <T as FromStr>::Err: Display {
-        let value = iter.next().ok_or(LogReadError::ReadError)?;
+    fn try_parse<T: std::str::FromStr>(value: &str) -> Result<T, LogReadError> where <T as FromStr>::Err: Display {
         match value.parse::<T>() {
             Ok(v) => Ok(v),
             Err(e) => {
@@ -110,46 +546,40 @@ fn parse_log_line(text: &str) -> Result<SensedData, LogReadError> {
         }
     }
 
-    fn try_parse_confidence_value(iter: & mut dyn Iterator<Item = & str>) -> Result<ReadConfidence, LogReadError> {
-        ReadConfidence::try_from(try_parse_next::<u8>(iter)?)
-            .map_err(|_| LogReadError::ParseError { msg: "Invalid confidence value".to_owned(), value: None })
-    }
-
-    let index: u32          = try_parse_next(&mut iterator)?;
-    let uptime: u32         = try_parse_next(&mut iterator)?;
-    let _micros: u32        = try_parse_next(&mut iterator)?;
-    let temperature: f32    = try_parse_next(&mut iterator)?;
-    let pressure: f32       = try_parse_next(&mut iterator)?;
-    let acceleration: [f64; 3] = [
-        try_parse_next(&mut iterator)?,
-        try_parse_next(&mut iterator)?,
-        try_parse_next(&mut iterator)?
-    ];
-    let acceleration_confidence = try_parse_confidence_value(&mut iterator)?;
-    let _gyroscope: [f64; 3] = [
-        try_parse_next(&mut iterator)?,
-        try_parse_next(&mut iterator)?,
-        try_parse_next(&mut iterator)?
-    ];
-    let _gyroscope_confidence = try_parse_confidence_value(&mut iterator)?;
-    let gps_time: u32         = try_parse_next(&mut iterator)?;
-    let gps_position: [f64; 2] = [
-        try_parse_next(&mut iterator)?,
-        try_parse_next(&mut iterator)?
-    ];
-    let gps_altitude: f64   = try_parse_next(&mut iterator)?;
-
-    Ok(SensedData {
-        index,
-        uptime,
-        temperature,
-        pressure,
-        acceleration,
-        acceleration_confidence,
-        gps_time,
-        gps_position,
-        gps_altitude,
-    })
+    fn try_parse_confidence_value(value: &str) -> Result<ReadConfidence, LogReadError> {
+        ReadConfidence::try_from(try_parse::<u8>(value)?)
+            .map_err(|_| LogReadError::ParseError { msg: "Invalid confidence value".to_owned(), value: Some(value.to_string()) })
+    }
+
+    let mut data = SensedData {
+        extra: vec![f64::NAN; schema.extra_count()],
+        ..Default::default()
+    };
+
+    for field in schema.fields() {
+        let value = match iterator.next() {
+            Some(value) if !value.is_empty() => value,
+            _ if field.optional => continue,
+            Some(_) => return Err(LogReadError::ParseError { msg: format!("Missing value of {}", field.name), value: None }),
+            None => return Err(LogReadError::ReadError),
+        };
+
+        let value = match field.field_type {
+            FieldType::Integer => FieldValue::Integer(try_parse(value)?),
+            FieldType::Float => FieldValue::Float(try_parse(value)?),
+            FieldType::Confidence => FieldValue::Confidence(try_parse_confidence_value(value)?),
+        };
+
+        field.store(&mut data, value);
+    }
+
+    Ok(data)
+}
+
+/// A frame of the default schema with the given index and uptime, for tests
+#[cfg(test)]
+pub(crate) fn test_frame(index: u32, uptime: u32) -> String {
+    format!("{index}\t{uptime}\t0\t2\t3\t4\t4\t4\t0\t6\t6\t6\t0\t8\t9\t9\t10")
 }
 
 #[cfg(test)]
@@ -159,33 +589,208 @@ mod tests {
     #[test]
     fn test_read_log_line() {
         assert_eq!(
-            parse_log_line("0\t1\t1\t2\t3\t4\t4\t4\t0\t6\t6\t6\t0\t8\t9\t9\t10"),
+            parse_log_line(&TelemetrySchema::default(), "0\t1\t1\t2\t3\t4\t4\t4\t0\t6\t6\t6\t0\t8\t9\t9\t10"),
             Ok(SensedData {
                 index: 0,
                 uptime: 1,
+                micros: 1,
                 temperature: 2.0,
                 pressure: 3.0,
                 acceleration: [4.0, 4.0, 4.0],
                 acceleration_confidence: ReadConfidence::Unreliable,
+                gyroscope: [6.0, 6.0, 6.0],
+                gyroscope_confidence: ReadConfidence::Unreliable,
                 gps_time: 8,
                 gps_position: [9.0, 9.0],
-                gps_altitude: 10.0
+                gps_altitude: 10.0,
+                extra: vec![],
             })
         );
     }
 
     #[test]
     fn test_read_log_line_nan() {
-        let value = parse_log_line("0\t1\t1\t2\t3\tnan\tnan\tnan\t0\t6\t6\t6\t0\t8\t9\t9\t10").unwrap();
+        let value = parse_log_line(&TelemetrySchema::default(), "0\t1\t1\t2\t3\tnan\tnan\tnan\t0\t6\t6\t6\t0\t8\t9\t9\t10").unwrap();
         assert!(value.acceleration[0].is_nan());
         assert!(value.acceleration[1].is_nan());
         assert!(value.acceleration[2].is_nan());
     }
 
+    #[test]
+    fn test_timestamp() {
+        let value = parse_log_line(&TelemetrySchema::default(), "670\t38076\t929\t27.19\t98709.02\t0\t0\t0\t3\t0\t0\t0\t0\t0\tnan\tnan\t0").unwrap();
+        assert_eq!(value.timestamp(), 38_076_929);
+    }
+
+    #[test]
+    fn test_read_log_line_custom_schema() {
+        let schema = TelemetrySchema::parse("uptime int ms\nindex int\npressure float Pa\nhumidity float %\nbattery float V optional").unwrap();
+
+        let value = parse_log_line(&schema, "1500\t7\t98000.5\t41.5").unwrap();
+        assert_eq!(value.index, 7);
+        assert_eq!(value.uptime, 1500);
+        assert_eq!(value.pressure, 98000.5);
+        assert_eq!(value.extra[0], 41.5);
+        assert!(value.extra[1].is_nan());
+        assert!(value.temperature.is_nan());
+
+        assert_eq!(parse_log_line(&schema, "1500\t7\t98000.5"), Err(LogReadError::ReadError));
+    }
+
+    #[test]
+    fn test_read_log_line_checksum() {
+        let schema = TelemetrySchema::default();
+        let frame = "670\t38076\t929\t27.19\t98709.02\t0\t0\t0\t3\t0\t0\t0\t0\t0\tnan\tnan\t0";
+
+        let xor = format!("{frame}\t*{:02X}", crate::checksum::xor(frame.as_bytes()));
+        assert!(parse_log_line(&schema, &xor).is_ok());
+
+        let crc = format!("{frame}\t*{:04X}", crate::checksum::crc16(frame.as_bytes()));
+        assert!(parse_log_line(&schema, &crc).is_ok());
+
+        let corrupted = crc.replacen("27.19", "27.18", 1);
+        assert!(matches!(parse_log_line(&schema, &corrupted), Err(LogReadError::ChecksumMismatch { .. })));
+
+        let strict = TelemetrySchema::parse(&format!("{}\nchecksum crc16", crate::schema::DEFAULT_SCHEMA)).unwrap();
+        assert!(parse_log_line(&strict, &crc).is_ok());
+        assert_eq!(parse_log_line(&strict, frame), Err(LogReadError::MissingChecksum));
+    }
+
+    #[test]
+    fn test_corrupted_frame_count() {
+        let frame = "1\t38076\t929\t27.19\t98709.02\t0\t0\t0\t3\t0\t0\t0\t0\t0\tnan\tnan\t0";
+        let mut data = MissionData::default();
+
+        assert!(data.parse_line(&format!("{frame}\t*00")).is_err());
+        assert!(data.parse_line(frame).is_ok());
+        assert!(data.parse_line(&format!("{frame}\t*00")).is_err());
+
+        assert_eq!(data.corrupted_frames(0), 2);
+    }
+
+    #[test]
+    fn test_rejected_lines() {
+        let data = MissionData::from_log("-- comment\n\ngarbage\n1\t2\n", Arc::default());
+
+        assert_eq!(data.rejected_count(), 2);
+        assert_eq!(data.rejected_lines()[0].text, "garbage");
+        assert_eq!(data.rejected_lines()[0].line_number, 3);
+        assert_eq!(data.rejected_lines()[1].line_number, 4);
+        assert_eq!(data.rejected_lines()[1].error, LogReadError::ReadError);
+    }
+
+    #[test]
+    fn test_rejected_lines_bounded() {
+        let mut data = MissionData::default();
+        for _ in 0..MAX_REJECTED_LINES + 5 {
+            let _ = data.parse_line("garbage");
+        }
+
+        assert_eq!(data.rejected_count(), MAX_REJECTED_LINES + 5);
+        assert_eq!(data.rejected_lines().len(), MAX_REJECTED_LINES);
+        assert_eq!(data.rejected_lines()[0].line_number, 6);
+    }
+
+    fn session_indices(data: &MissionData) -> Vec<Vec<u32>> {
+        data.sessions().iter().map(|s| s.iter().map(|d| d.index).collect()).collect()
+    }
+
+    #[test]
+    fn test_segmentation() {
+        // A reordered frame, a restart that keeps counting, a restart from 0 and a long pause
+        let log = [test_frame(20, 2000), test_frame(22, 2200), test_frame(21, 2100), test_frame(23, 2300), test_frame(24, 100), test_frame(0, 50), test_frame(1, 9000)].join("\n");
+        let mut data = MissionData::from_log(&log, Arc::default());
+        assert_eq!(session_indices(&data), [vec![20, 21, 22, 23], vec![24], vec![0, 1]]);
+
+        data.resegment(Segmentation { uptime_reset_ms: u32::MAX, time_gap_s: 5.0, ..Default::default() });
+        assert_eq!(session_indices(&data), [vec![20, 21, 22, 23, 24], vec![0], vec![1]]);
+    }
+
+    #[test]
+    fn test_reorder_buffer() {
+        let log = [test_frame(1, 100), test_frame(3, 300), test_frame(2, 200), test_frame(4, 400), test_frame(4, 400), test_frame(5, 500), test_frame(0, 0)].join("\n");
+
+        // Without the buffer, the late frame looks like a restart
+        let data = MissionData::from_log(&log, Arc::default());
+        assert_eq!(session_indices(&data), [vec![1, 3], vec![2, 4, 5], vec![0]]);
+        assert_eq!(data.duplicate_frames(1), 1);
+
+        let mut data = MissionData::default().with_segmentation(Segmentation { reorder_window: 2, ..Default::default() });
+        for line in log.lines().take(6) {
+            data.parse_line(line).unwrap();
+        }
+        assert_eq!(session_indices(&data), [vec![1, 2, 3]]);
+        assert_eq!(data.buffered_frames(), 2);
+
+        // A restart releases everything held back
+        data.parse_line(&test_frame(0, 0)).unwrap();
+        assert_eq!(session_indices(&data), [vec![1, 2, 3, 4, 5], vec![0]]);
+        assert_eq!((data.reordered_frames(0), data.duplicate_frames(0)), (1, 1));
+    }
+
+    #[test]
+    fn test_reorder_buffer_segmentation() {
+        let segmentation = Segmentation { time_gap_s: 5.0, reorder_window: 2, ..Default::default() };
+        let mut data = MissionData::default().with_segmentation(segmentation);
+
+        // The uptime resets while the index keeps counting, then jumps forward by 8.8 s
+        let frames = [(1, 10_100), (2, 10_200), (3, 10_300), (4, 10_400), (5, 10_500), (6, 100), (7, 200), (8, 9000), (9, 9100)];
+        for (index, uptime) in frames {
+            data.parse_line(&test_frame(index, uptime)).unwrap();
+        }
+        data.flush_reorder_buffer();
+        assert_eq!(session_indices(&data), [vec![1, 2, 3, 4, 5], vec![6, 7], vec![8, 9]]);
+    }
+
+    #[test]
+    fn test_session_edits() {
+        let log = [test_frame(1, 100), test_frame(2, 200), test_frame(3, 300), test_frame(0, 0), test_frame(1, 100)].join("\n");
+        let mut data = MissionData::from_log(&log, Arc::default());
+        assert_eq!(session_indices(&data), [vec![1, 2, 3], vec![0, 1]]);
+
+        data.split_session(0, 1);
+        assert_eq!(session_indices(&data), [vec![1], vec![2, 3], vec![0, 1]]);
+
+        data.rename_session(1, Some("Flight".to_owned()));
+        data.merge_with_next(0);
+        assert_eq!(session_indices(&data), [vec![1, 2, 3], vec![0, 1]]);
+        assert_eq!(data.session_name(0), Some("Flight"));
+
+        data.delete_session(0);
+        assert_eq!(session_indices(&data), [vec![0, 1]]);
+        assert_eq!(data.session_name(0), None);
+
+        // New frames keep going to the last session
+        data.parse_line(&test_frame(2, 200)).unwrap();
+        assert_eq!(session_indices(&data), [vec![0, 1, 2]]);
+    }
+
+    #[test]
+    fn test_merge_across_restart() {
+        let log = [test_frame(25, 2500), test_frame(26, 2600), test_frame(0, 0), test_frame(1, 100)].join("\n");
+        let mut data = MissionData::from_log(&log, Arc::default());
+        assert_eq!(session_indices(&data), [vec![25, 26], vec![0, 1]]);
+
+        data.merge_with_next(0);
+        assert_eq!(session_indices(&data), [vec![25, 26, 0, 1]]);
+
+        // Frames of the second boot are only compared with each other
+        for (index, uptime) in [(2, 200), (25, 2500), (26, 2600), (24, 2400), (26, 2600)] {
+            data.parse_line(&test_frame(index, uptime)).unwrap();
+        }
+        assert_eq!(session_indices(&data), [vec![25, 26, 0, 1, 2, 24, 25, 26]]);
+        assert_eq!(data.duplicate_frames(0), 1);
+
+        // Splitting at the boundary gives back both boots
+        data.split_session(0, 2);
+        data.parse_line(&test_frame(23, 2300)).unwrap();
+        assert_eq!(session_indices(&data), [vec![25, 26], vec![0, 1, 2, 23, 24, 25, 26]]);
+    }
+
     #[test]
     fn test_read_log_line_real_data() {
         assert!(
-            parse_log_line("670\t38076\t929\t27.19\t98709.02\t-0.007813\t-0.011719\t0.015625\t3\t3.136642\t-0.274198\t2.485954\t0\t0\tnan\tnan\t0.000000")
+            parse_log_line(&TelemetrySchema::default(), "670\t38076\t929\t27.19\t98709.02\t-0.007813\t-0.011719\t0.015625\t3\t3.136642\t-0.274198\t2.485954\t0\t0\tnan\tnan\t0.000000")
                 .is_ok()
         );
     }
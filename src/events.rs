@@ -0,0 +1,332 @@
+use std::collections::VecDeque;
+
+use egui::{CollapsingHeader, DragValue, Ui};
+use serde::{Deserialize, Serialize};
+
+use crate::data::SensedData;
+
+/// A shock this soon before the probe comes to rest is taken as the touchdown, not the parachute
+const TOUCHDOWN_MARGIN_S: f64 = 2.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlightEventKind {
+    Launch,
+    Apogee,
+    Deployment,
+    Landing,
+}
+
+impl FlightEventKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            FlightEventKind::Launch => "Launch",
+            FlightEventKind::Apogee => "Apogee",
+            FlightEventKind::Deployment => "Deployment",
+            FlightEventKind::Landing => "Landing",
+        }
+    }
+}
+
+/// A moment of the flight found in the telemetry
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FlightEvent {
+    pub kind: FlightEventKind,
+    /// Position of the record in the session
+    pub record: usize,
+}
+
+/// Thresholds of the event detection
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EventSettings {
+    /// Acceleration that marks the launch, in g
+    pub launch_acceleration: f64,
+    /// How far the pressure must rise above its minimum for it to be taken as the apogee, in Pa
+    pub apogee_margin: f64,
+    /// Acceleration of the parachute opening, in g
+    pub deployment_acceleration: f64,
+    /// How long the probe must lie still to have landed, in seconds
+    pub landing_window_s: f64,
+    /// Largest standard deviation of the pressure while lying still, in Pa
+    pub landing_pressure_deviation: f64,
+    /// Largest standard deviation of the acceleration while lying still, in g
+    pub landing_acceleration_deviation: f64,
+}
+
+impl Default for EventSettings {
+    fn default() -> Self {
+        Self {
+            launch_acceleration: 3.0,
+            apogee_margin: 50.0,
+            deployment_acceleration: 2.0,
+            landing_window_s: 10.0,
+            landing_pressure_deviation: 8.0,
+            landing_acceleration_deviation: 0.05,
+        }
+    }
+}
+
+/// Part of the flight the detector is waiting for the end of
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Phase {
+    Ground,
+    /// Climbing, with the record of the lowest pressure so far
+    Ascent { lowest: Option<(usize, f32)> },
+    /// Falling, with the time of the parachute opening if it was seen
+    Descent { deployed_at: Option<f64> },
+    Landed,
+}
+
+/// Finds the flight events of a session. Records are processed as they arrive, so
+/// a live session only costs the new records on each update.
+#[derive(Debug, Clone)]
+pub struct EventDetector {
+    settings: EventSettings,
+    events: Vec<FlightEvent>,
+    phase: Phase,
+    /// Records already processed
+    processed: usize,
+    /// Index and timestamp of the last record processed
+    last: Option<(u32, u64)>,
+    /// Time in seconds, pressure and acceleration magnitude of the recent records, for the landing
+    window: VecDeque<(f64, f64, f64)>,
+}
+
+impl EventDetector {
+    pub fn new(settings: EventSettings) -> Self {
+        Self { settings, events: vec![], phase: Phase::Ground, processed: 0, last: None, window: VecDeque::new() }
+    }
+
+    pub fn settings(&self) -> &EventSettings {
+        &self.settings
+    }
+
+    /// Processes the records added to the session since the last update. Starts over if records
+    /// were removed or inserted before the end, as that moves the ones already processed.
+    pub fn update(&mut self, session: &[SensedData]) -> &[FlightEvent] {
+        let last = self.processed.checked_sub(1)
+            .and_then(|i| session.get(i))
+            .map(|s| (s.index, s.timestamp()));
+        if last != self.last {
+            *self = EventDetector::new(self.settings);
+        }
+
+        for (i, s) in session.iter().enumerate().skip(self.processed) {
+            self.process(i, s);
+        }
+        self.processed = session.len();
+        self.last = session.last().map(|s| (s.index, s.timestamp()));
+
+        &self.events
+    }
+
+    fn process(&mut self, i: usize, s: &SensedData) {
+        let acceleration = s.acceleration.iter().map(|a| a * a).sum::<f64>().sqrt();
+        let settings = &self.settings;
+
+        match &mut self.phase {
+            Phase::Ground => {
+                if acceleration >= settings.launch_acceleration {
+                    self.events.push(FlightEvent { kind: FlightEventKind::Launch, record: i });
+                    self.phase = Phase::Ascent { lowest: None };
+                }
+            },
+            Phase::Ascent { lowest } => {
+                if s.pressure.is_nan() {
+                    return;
+                }
+                match *lowest {
+                    Some((record, pressure)) if (s.pressure - pressure) as f64 >= settings.apogee_margin => {
+                        self.events.push(FlightEvent { kind: FlightEventKind::Apogee, record });
+                        self.phase = Phase::Descent { deployed_at: None };
+                    },
+                    Some((_, pressure)) if s.pressure >= pressure => {},
+                    _ => *lowest = Some((i, s.pressure)),
+                }
+            },
+            Phase::Descent { deployed_at } => {
+                let time = s.timestamp() as f64 / 1_000_000.0;
+
+                if deployed_at.is_none() && acceleration >= settings.deployment_acceleration {
+                    self.events.push(FlightEvent { kind: FlightEventKind::Deployment, record: i });
+                    *deployed_at = Some(time);
+                }
+
+                if s.pressure.is_nan() || acceleration.is_nan() {
+                    return;
+                }
+
+                self.window.push_back((time, s.pressure as f64, acceleration));
+                while self.window.front().is_some_and(|&(start, _, _)| start < time - settings.landing_window_s) {
+                    self.window.pop_front();
+                }
+
+                let covered = self.window.front().is_some_and(|&(start, _, _)| time - start >= settings.landing_window_s * 0.9);
+                if covered
+                    && deviation(self.window.iter().map(|w| w.1)) <= settings.landing_pressure_deviation
+                    && deviation(self.window.iter().map(|w| w.2)) <= settings.landing_acceleration_deviation
+                {
+                    let record = i + 1 - self.window.len();
+                    let rest_start = self.window.front().map_or(time, |w| w.0);
+                    if deployed_at.is_some_and(|deployed_at| deployed_at >= rest_start - TOUCHDOWN_MARGIN_S) {
+                        self.events.retain(|event| event.kind != FlightEventKind::Deployment);
+                    }
+
+                    self.events.push(FlightEvent { kind: FlightEventKind::Landing, record });
+                    self.phase = Phase::Landed;
+                }
+            },
+            Phase::Landed => {},
+        }
+    }
+}
+
+fn deviation(values: impl Iterator<Item = f64> + Clone) -> f64 {
+    let count = values.clone().count() as f64;
+    let mean = values.clone().sum::<f64>() / count;
+    (values.map(|v| (v - mean).powi(2)).sum::<f64>() / count).sqrt()
+}
+
+/// Lists the events of a session with their time since the launch, and the detection thresholds
+pub fn timeline_panel(ui: &mut Ui, settings: &mut EventSettings, session: &[SensedData], events: &[FlightEvent]) {
+    ui.heading("Mission timeline");
+
+    ui.separator();
+
+    if events.is_empty() {
+        ui.label("No events detected");
+    } else {
+        let launch = events.iter()
+            .find(|event| event.kind == FlightEventKind::Launch)
+            .map(|event| session[event.record].timestamp());
+
+        egui::Grid::new("timeline_grid").num_columns(4).striped(true).show(ui, |ui| {
+            ui.strong("Event");
+            ui.strong("Index");
+            ui.strong("Uptime [s]");
+            ui.strong("T+ [s]");
+            ui.end_row();
+
+            for event in events {
+                let record = &session[event.record];
+                ui.label(event.kind.name());
+                ui.label(record.index.to_string());
+                ui.label(format!("{:.3}", record.timestamp() as f64 / 1_000_000.0));
+                match launch {
+                    Some(launch) => ui.label(format!("{:.3}", (record.timestamp() as f64 - launch as f64) / 1_000_000.0)),
+                    None => ui.weak("-"),
+                };
+                ui.end_row();
+            }
+        });
+    }
+
+    ui.separator();
+
+    CollapsingHeader::new("Detection").default_open(false).show(ui, |ui| {
+        egui::Grid::new("event_settings_grid").num_columns(2).show(ui, |ui| {
+            ui.label("Launch");
+            ui.add(DragValue::new(&mut settings.launch_acceleration).range(1.0..=100.0).speed(0.1).prefix("≥ ").suffix(" g"));
+            ui.end_row();
+
+            ui.label("Apogee");
+            ui.add(DragValue::new(&mut settings.apogee_margin).range(1.0..=10000.0).speed(1.0).prefix("+ ").suffix(" Pa"))
+                .on_hover_text("How far the pressure must rise again above its minimum");
+            ui.end_row();
+
+            ui.label("Deployment");
+            ui.add(DragValue::new(&mut settings.deployment_acceleration).range(1.0..=100.0).speed(0.1).prefix("≥ ").suffix(" g"))
+                .on_hover_text("Shock of the parachute opening after the apogee");
+            ui.end_row();
+
+            ui.label("Landing after");
+            ui.add(DragValue::new(&mut settings.landing_window_s).range(1.0..=600.0).speed(0.5).suffix(" s"))
+                .on_hover_text("How long the probe must lie still");
+            ui.end_row();
+
+            ui.label("Pressure deviation");
+            ui.add(DragValue::new(&mut settings.landing_pressure_deviation).range(0.0..=1000.0).speed(0.5).prefix("≤ ").suffix(" Pa"));
+            ui.end_row();
+
+            ui.label("Acceleration deviation");
+            ui.add(DragValue::new(&mut settings.landing_acceleration_deviation).range(0.0..=10.0).speed(0.01).prefix("≤ ").suffix(" g"));
+            ui.end_row();
+        });
+
+        if ui.button("Reset").clicked() {
+            *settings = EventSettings::default();
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{data::MissionData, simulator::{Simulator, SimulatorSettings}};
+
+    fn detect_events(session: &[SensedData]) -> Vec<FlightEvent> {
+        EventDetector::new(EventSettings::default()).update(session).to_vec()
+    }
+
+    #[test]
+    fn test_simulated_flight() {
+        let settings = SimulatorSettings { packet_loss: 0.0, reset_chance: 0.0, ..Default::default() };
+        let mut simulator = Simulator::new(settings, MissionData::default());
+        for _ in 0..3000 {
+            simulator.step();
+        }
+        let session = &simulator.data().sessions()[0];
+
+        let events = detect_events(session);
+        let kinds: Vec<_> = events.iter().map(|e| e.kind).collect();
+        // The simulated parachute opens without a shock, and the touchdown is not taken for one
+        assert_eq!(kinds, [FlightEventKind::Launch, FlightEventKind::Apogee, FlightEventKind::Landing]);
+
+        // Launched after 30 s on the pad, at 5 frames per second
+        assert!((149..=152).contains(&events[0].record), "launch at {}", events[0].record);
+        let lowest = session.iter().enumerate().min_by(|a, b| a.1.pressure.total_cmp(&b.1.pressure)).unwrap().0;
+        assert!(events[1].record.abs_diff(lowest) <= 10);
+        assert!(events[2].record > events[1].record);
+    }
+
+    #[test]
+    fn test_incremental() {
+        // On the pad, a launch, a climb, the parachute opening on the way down, then lying still
+        let session: Vec<SensedData> = (0..400)
+            .map(|i| {
+                let (acceleration, pressure) = match i {
+                    0..10 => (1.0, 100000.0),
+                    10..13 => (8.0, 100000.0 - (i - 9) as f32 * 100.0),
+                    13..50 => (0.2, 99700.0 - (i - 12) as f32 * 10.0),
+                    50 => (0.2, 99320.0),
+                    51 => (0.2, 99400.0),
+                    52 => (4.0, 99420.0),
+                    53..200 => (1.0, 99420.0 + (i - 52) as f32 * 20.0),
+                    _ => (1.0, 103000.0),
+                };
+                SensedData { uptime: i * 100, pressure, acceleration: [0.0, 0.0, acceleration], ..Default::default() }
+            })
+            .collect();
+
+        let mut detector = EventDetector::new(EventSettings::default());
+        for end in [5, 11, 60, 150] {
+            detector.update(&session[..end]);
+        }
+        let events = detector.update(&session).to_vec();
+
+        assert_eq!(events, detect_events(&session));
+
+        // A late record inserted during the climb moves everything after it
+        let mut detector = EventDetector::new(EventSettings::default());
+        let early: Vec<SensedData> = session[..30].iter().chain(&session[31..61]).cloned().collect();
+        detector.update(&early);
+        assert_eq!(detector.update(&session), events);
+
+        assert_eq!(events, [
+            FlightEvent { kind: FlightEventKind::Launch, record: 10 },
+            FlightEvent { kind: FlightEventKind::Apogee, record: 50 },
+            FlightEvent { kind: FlightEventKind::Deployment, record: 52 },
+            FlightEvent { kind: FlightEventKind::Landing, record: 200 },
+        ]);
+    }
+}
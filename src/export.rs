@@ -0,0 +1,280 @@
+use std::io::{self, Write};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{data::{MissionData, SensedData}, expression::ComputedChannel, schema::{FieldType, TelemetrySchema}};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ExportFormat {
+    Csv,
+    JsonLines,
+    /// Each column stored as one block of little-endian numbers, see [`write_columnar`]
+    Columnar,
+}
+
+impl ExportFormat {
+    pub const ALL: [ExportFormat; 3] = [ExportFormat::Csv, ExportFormat::JsonLines, ExportFormat::Columnar];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "CSV",
+            ExportFormat::JsonLines => "JSON Lines",
+            ExportFormat::Columnar => "Binary columnar",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::JsonLines => "jsonl",
+            ExportFormat::Columnar => "gscol",
+        }
+    }
+}
+
+/// What File > Export writes out
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExportSettings {
+    pub format: ExportFormat,
+    /// Export every session of the source rather than the one shown
+    pub all_sessions: bool,
+    /// Leave out the records hidden by the index and time filters of the Plot tab
+    pub apply_plot_filters: bool,
+}
+
+impl Default for ExportSettings {
+    fn default() -> Self {
+        Self { format: ExportFormat::Csv, all_sessions: false, apply_plot_filters: false }
+    }
+}
+
+/// Value of a record, given with its position among the exported rows
+pub type ColumnValue<'a> = Box<dyn Fn(usize, &SensedData) -> f64 + 'a>;
+
+/// A column of exported data
+pub struct ExportColumn<'a> {
+    /// Name without spaces, as used in the schema
+    pub name: String,
+    pub unit: String,
+    /// Whether values are whole numbers, written without a fractional part
+    pub integer: bool,
+    pub value: ColumnValue<'a>,
+}
+
+/// One column for every field of the schema
+pub fn schema_columns(schema: &TelemetrySchema) -> Vec<ExportColumn<'_>> {
+    schema.fields().iter()
+        .map(|field| ExportColumn {
+            name: field.name.clone(),
+            unit: field.unit.clone(),
+            integer: field.field_type != FieldType::Float,
+            value: Box::new(|_, data| field.value(data)),
+        })
+        .collect()
+}
+
+/// One column for every computed channel that can be evaluated with the schema of the data.
+/// `rows` are the session and position in the session of each exported record.
+pub fn computed_columns(channels: &[ComputedChannel], mission: &MissionData, rows: &[(usize, usize)]) -> Vec<ExportColumn<'static>> {
+    channels.iter()
+        .filter_map(|channel| {
+            let sessions = mission.sessions().iter()
+                .map(|session| channel.evaluate(mission.schema(), session))
+                .collect::<Result<Vec<_>, _>>()
+                .ok()?;
+            let values: Vec<f64> = rows.iter().map(|&(session, i)| sessions[session][i]).collect();
+
+            Some(ExportColumn {
+                name: channel.name.replace(char::is_whitespace, "_"),
+                unit: channel.unit.clone(),
+                integer: false,
+                value: Box::new(move |row, _| values[row]),
+            })
+        })
+        .collect()
+}
+
+/// Writes records, given with the session they belong to, in the chosen format.
+/// A `session` column comes before the others.
+pub fn export(writer: &mut impl Write, format: ExportFormat, columns: &[ExportColumn<'_>], rows: &[(usize, &SensedData)]) -> io::Result<()> {
+    match format {
+        ExportFormat::Csv => write_csv(writer, columns, rows),
+        ExportFormat::JsonLines => write_json_lines(writer, columns, rows),
+        ExportFormat::Columnar => write_columnar(writer, columns, rows),
+    }
+}
+
+fn format_value(column: &ExportColumn<'_>, value: f64) -> String {
+    if column.integer {
+        format!("{}", value as i64)
+    } else {
+        format!("{value}")
+    }
+}
+
+/// A header row with units in brackets, then one row per record. Missing values are left empty.
+fn write_csv(writer: &mut impl Write, columns: &[ExportColumn<'_>], rows: &[(usize, &SensedData)]) -> io::Result<()> {
+    fn escape(text: &str) -> String {
+        if text.contains([',', '"', '\n']) {
+            format!("\"{}\"", text.replace('"', "\"\""))
+        } else {
+            text.to_owned()
+        }
+    }
+
+    let header: Vec<String> = columns.iter()
+        .map(|column| match column.unit.as_str() {
+            "" => escape(&column.name),
+            unit => escape(&format!("{} [{}]", column.name, unit)),
+        })
+        .collect();
+    writeln!(writer, "session,{}", header.join(","))?;
+
+    for (row, (session, data)) in rows.iter().enumerate() {
+        let values: Vec<String> = columns.iter()
+            .map(|column| {
+                let value = (column.value)(row, data);
+                if value.is_nan() { String::new() } else { format_value(column, value) }
+            })
+            .collect();
+        writeln!(writer, "{},{}", session, values.join(","))?;
+    }
+
+    Ok(())
+}
+
+/// One JSON object per line. Missing values are written as `null`.
+fn write_json_lines(writer: &mut impl Write, columns: &[ExportColumn<'_>], rows: &[(usize, &SensedData)]) -> io::Result<()> {
+    for (row, (session, data)) in rows.iter().enumerate() {
+        let mut object = serde_json::Map::new();
+        object.insert("session".to_owned(), (*session).into());
+
+        for column in columns {
+            let value = (column.value)(row, data);
+            let value = match value {
+                _ if value.is_nan() => serde_json::Value::Null,
+                _ if column.integer => (value as i64).into(),
+                _ => value.into(),
+            };
+            object.insert(column.name.clone(), value);
+        }
+
+        serde_json::to_writer(&mut *writer, &object)?;
+        writeln!(writer)?;
+    }
+
+    Ok(())
+}
+
+/// Magic bytes starting a binary columnar file
+pub const COLUMNAR_MAGIC: &[u8; 8] = b"GSCOL\0\0\x01";
+
+/// Writes a compact binary file, with all numbers little-endian:
+///
+/// - [`COLUMNAR_MAGIC`], then the column count and row count as `u32`
+/// - for every column, its name and unit as a `u16` byte length followed by UTF-8,
+///   and its type as a `u8`: 0 for `u32`, 1 for `f64`
+/// - for every column in the same order, all of its values back to back
+///
+/// The `session` column comes first and is a `u32`. Missing values of `f64` columns are NaN.
+fn write_columnar(writer: &mut impl Write, columns: &[ExportColumn<'_>], rows: &[(usize, &SensedData)]) -> io::Result<()> {
+    fn write_text(writer: &mut impl Write, text: &str) -> io::Result<()> {
+        let length = u16::try_from(text.len()).map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "column name too long"))?;
+        writer.write_all(&length.to_le_bytes())?;
+        writer.write_all(text.as_bytes())
+    }
+
+    writer.write_all(COLUMNAR_MAGIC)?;
+    writer.write_all(&(columns.len() as u32 + 1).to_le_bytes())?;
+    writer.write_all(&(rows.len() as u32).to_le_bytes())?;
+
+    write_text(writer, "session")?;
+    write_text(writer, "")?;
+    writer.write_all(&[0])?;
+    for column in columns {
+        write_text(writer, &column.name)?;
+        write_text(writer, &column.unit)?;
+        writer.write_all(&[if column.integer { 0 } else { 1 }])?;
+    }
+
+    for (session, _) in rows {
+        writer.write_all(&(*session as u32).to_le_bytes())?;
+    }
+    for column in columns {
+        for (row, (_, data)) in rows.iter().enumerate() {
+            let value = (column.value)(row, data);
+            if column.integer {
+                writer.write_all(&(value as u32).to_le_bytes())?;
+            } else {
+                writer.write_all(&value.to_le_bytes())?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows(schema: &TelemetrySchema) -> Vec<SensedData> {
+        ["1\t100\t0\t20.5\t98000\tnan\t0\t0\t3\t0\t0\t0\t0\t0\tnan\tnan\t0", "2\t200\t0\t21\t97990\t1\t0\t0\t3\t0\t0\t0\t0\t0\tnan\tnan\t0"]
+            .iter()
+            .map(|line| crate::data::parse_log_line(schema, line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_csv() {
+        let schema = TelemetrySchema::default();
+        let data = rows(&schema);
+        let rows: Vec<_> = data.iter().map(|d| (0, d)).collect();
+
+        let mut out = vec![];
+        export(&mut out, ExportFormat::Csv, &schema_columns(&schema), &rows).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("session,index,uptime [ms],"));
+        assert!(lines[1].starts_with("0,1,100,0,20.5,98000,,0,0,3,"));
+    }
+
+    #[test]
+    fn test_json_lines() {
+        let schema = TelemetrySchema::default();
+        let data = rows(&schema);
+
+        let mut out = vec![];
+        export(&mut out, ExportFormat::JsonLines, &schema_columns(&schema), &[(3, &data[0])]).unwrap();
+        let value: serde_json::Value = serde_json::from_slice(&out).unwrap();
+
+        assert_eq!(value["session"], 3);
+        assert_eq!(value["index"], 1);
+        assert_eq!(value["temperature"], 20.5);
+        assert!(value["accel_x"].is_null());
+    }
+
+    #[test]
+    fn test_columnar() {
+        let schema = TelemetrySchema::parse("index int\ntemperature float C").unwrap();
+        let data = rows(&TelemetrySchema::default());
+        let rows: Vec<_> = data.iter().map(|d| (1, d)).collect();
+
+        let mut out = vec![];
+        export(&mut out, ExportFormat::Columnar, &schema_columns(&schema), &rows).unwrap();
+
+        assert_eq!(&out[..8], COLUMNAR_MAGIC);
+        assert_eq!(u32::from_le_bytes(out[8..12].try_into().unwrap()), 3);
+        assert_eq!(u32::from_le_bytes(out[12..16].try_into().unwrap()), 2);
+
+        // session, index, temperature headers, then 2 sessions, 2 indices and 2 temperatures
+        let header = (2 + 7 + 2 + 1) + (2 + 5 + 2 + 1) + (2 + 11 + 2 + 1 + 1);
+        let values = &out[16 + header..];
+        assert_eq!(values.len(), 4 * 2 + 4 * 2 + 8 * 2);
+        assert_eq!(u32::from_le_bytes(values[8..12].try_into().unwrap()), 1);
+        assert_eq!(f64::from_le_bytes(values[16..24].try_into().unwrap()), 20.5);
+    }
+}
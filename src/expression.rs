@@ -0,0 +1,481 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{data::SensedData, schema::TelemetrySchema};
+
+/// A channel computed from the fields of the schema, defined by the user
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ComputedChannel {
+    pub name: String,
+    pub unit: String,
+    /// See [`Expression::parse`] for the syntax
+    pub expression: String,
+}
+
+impl ComputedChannel {
+    /// Value of the channel at every record of a session
+    pub fn evaluate(&self, schema: &TelemetrySchema, session: &[SensedData]) -> Result<Vec<f64>, ExpressionError> {
+        Expression::parse(&self.expression)?.evaluate(schema, session)
+    }
+
+    /// Name followed by the unit, if the channel has one
+    pub fn label_with_unit(&self) -> String {
+        if self.unit.is_empty() {
+            self.name.clone()
+        } else {
+            format!("{} [{}]", self.name, self.unit)
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExpressionError {
+    /// Byte offset in the expression text
+    pub position: usize,
+    pub msg: String,
+}
+
+impl fmt::Display for ExpressionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at character {}", self.msg, self.position + 1)
+    }
+}
+
+fn error<T>(position: usize, msg: impl Into<String>) -> Result<T, ExpressionError> {
+    Err(ExpressionError { position, msg: msg.into() })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Operator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Power,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Function {
+    Sqrt,
+    Abs,
+    Sin,
+    Cos,
+    Tan,
+    Atan2,
+    Ln,
+    Log10,
+    Exp,
+    Min,
+    Max,
+}
+
+impl Function {
+    fn from_name(name: &str) -> Option<Function> {
+        Some(match name {
+            "sqrt" => Function::Sqrt,
+            "abs" => Function::Abs,
+            "sin" => Function::Sin,
+            "cos" => Function::Cos,
+            "tan" => Function::Tan,
+            "atan2" => Function::Atan2,
+            "ln" => Function::Ln,
+            "log10" => Function::Log10,
+            "exp" => Function::Exp,
+            "min" => Function::Min,
+            "max" => Function::Max,
+            _ => return None,
+        })
+    }
+
+    fn arity(self) -> usize {
+        match self {
+            Function::Atan2 | Function::Min | Function::Max => 2,
+            _ => 1,
+        }
+    }
+
+    fn apply(self, args: &[f64]) -> f64 {
+        match self {
+            Function::Sqrt => args[0].sqrt(),
+            Function::Abs => args[0].abs(),
+            Function::Sin => args[0].sin(),
+            Function::Cos => args[0].cos(),
+            Function::Tan => args[0].tan(),
+            Function::Atan2 => args[0].atan2(args[1]),
+            Function::Ln => args[0].ln(),
+            Function::Log10 => args[0].log10(),
+            Function::Exp => args[0].exp(),
+            Function::Min => args[0].min(args[1]),
+            Function::Max => args[0].max(args[1]),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Number(f64),
+    /// A schema field, or `t` for the time in seconds
+    Variable { name: String, position: usize },
+    Negate(Box<Node>),
+    Binary(Operator, Box<Node>, Box<Node>),
+    Call(Function, Vec<Node>),
+    /// Change per second since the previous record
+    Derivative(Box<Node>),
+    /// Value the given number of records earlier
+    Previous(Box<Node>, usize),
+    /// Mean of the values of the given number of records, up to the current one
+    Average(Box<Node>, usize),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Name(String),
+    Operator(Operator),
+    Open,
+    Close,
+    Comma,
+}
+
+fn tokenize(text: &str) -> Result<Vec<(usize, Token)>, ExpressionError> {
+    let mut tokens = vec![];
+    let mut chars = text.char_indices().peekable();
+
+    while let Some(&(start, c)) = chars.peek() {
+        let token = match c {
+            _ if c.is_whitespace() => {
+                chars.next();
+                continue;
+            },
+            '0'..='9' | '.' => {
+                let mut end = start;
+                let mut previous = ' ';
+                while let Some(&(i, c)) = chars.peek() {
+                    let exponent_sign = (c == '-' || c == '+') && (previous == 'e' || previous == 'E');
+                    if !(c.is_ascii_digit() || c == '.' || c == 'e' || c == 'E' || exponent_sign) {
+                        break;
+                    }
+                    end = i + c.len_utf8();
+                    previous = c;
+                    chars.next();
+                }
+                match text[start..end].parse() {
+                    Ok(value) => Token::Number(value),
+                    Err(_) => return error(start, format!("Invalid number \"{}\"", &text[start..end])),
+                }
+            },
+            _ if c.is_alphabetic() || c == '_' => {
+                let mut end = start;
+                while let Some(&(i, c)) = chars.peek().filter(|(_, c)| c.is_alphanumeric() || *c == '_') {
+                    end = i + c.len_utf8();
+                    chars.next();
+                }
+                Token::Name(text[start..end].to_owned())
+            },
+            _ => {
+                chars.next();
+                match c {
+                    '+' => Token::Operator(Operator::Add),
+                    '-' => Token::Operator(Operator::Subtract),
+                    '*' => Token::Operator(Operator::Multiply),
+                    '/' => Token::Operator(Operator::Divide),
+                    '^' => Token::Operator(Operator::Power),
+                    '(' => Token::Open,
+                    ')' => Token::Close,
+                    ',' => Token::Comma,
+                    _ => return error(start, format!("Unexpected \"{c}\"")),
+                }
+            },
+        };
+        tokens.push((start, token));
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    next: usize,
+    /// Position reported when the expression ends too early
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.next).map(|(_, token)| token)
+    }
+
+    fn position(&self) -> usize {
+        self.tokens.get(self.next).map_or(self.end, |(position, _)| *position)
+    }
+
+    fn advance(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.next).map(|(_, token)| token.clone());
+        self.next += 1;
+        token
+    }
+
+    fn expect(&mut self, expected: Token, what: &str) -> Result<(), ExpressionError> {
+        let position = self.position();
+        match self.advance() {
+            Some(token) if token == expected => Ok(()),
+            _ => error(position, format!("Expected {what}")),
+        }
+    }
+
+    /// Sums and differences
+    fn expression(&mut self) -> Result<Node, ExpressionError> {
+        let mut node = self.term()?;
+        while let Some(&Token::Operator(operator @ (Operator::Add | Operator::Subtract))) = self.peek() {
+            self.next += 1;
+            node = Node::Binary(operator, Box::new(node), Box::new(self.term()?));
+        }
+        Ok(node)
+    }
+
+    /// Products and quotients
+    fn term(&mut self) -> Result<Node, ExpressionError> {
+        let mut node = self.unary()?;
+        while let Some(&Token::Operator(operator @ (Operator::Multiply | Operator::Divide))) = self.peek() {
+            self.next += 1;
+            node = Node::Binary(operator, Box::new(node), Box::new(self.unary()?));
+        }
+        Ok(node)
+    }
+
+    fn unary(&mut self) -> Result<Node, ExpressionError> {
+        if self.peek() == Some(&Token::Operator(Operator::Subtract)) {
+            self.next += 1;
+            return Ok(Node::Negate(Box::new(self.unary()?)));
+        }
+
+        let base = self.primary()?;
+        if self.peek() == Some(&Token::Operator(Operator::Power)) {
+            self.next += 1;
+            return Ok(Node::Binary(Operator::Power, Box::new(base), Box::new(self.unary()?)));
+        }
+        Ok(base)
+    }
+
+    fn primary(&mut self) -> Result<Node, ExpressionError> {
+        let position = self.position();
+
+        match self.advance() {
+            Some(Token::Number(value)) => Ok(Node::Number(value)),
+            Some(Token::Open) => {
+                let node = self.expression()?;
+                self.expect(Token::Close, "\")\"")?;
+                Ok(node)
+            },
+            Some(Token::Name(name)) if self.peek() == Some(&Token::Open) => {
+                self.next += 1;
+                let mut args = vec![self.expression()?];
+                while self.peek() == Some(&Token::Comma) {
+                    self.next += 1;
+                    args.push(self.expression()?);
+                }
+                self.expect(Token::Close, "\")\"")?;
+
+                self.call(&name, args, position)
+            },
+            Some(Token::Name(name)) if name == "pi" => Ok(Node::Number(std::f64::consts::PI)),
+            Some(Token::Name(name)) => Ok(Node::Variable { name, position }),
+            _ => error(position, "Expected a number, name or \"(\""),
+        }
+    }
+
+    fn call(&mut self, name: &str, mut args: Vec<Node>, position: usize) -> Result<Node, ExpressionError> {
+        fn count(args: &[Node], position: usize) -> Result<usize, ExpressionError> {
+            match args.get(1) {
+                None => Ok(1),
+                Some(&Node::Number(count)) if count >= 1.0 && count.fract() == 0.0 => Ok(count as usize),
+                Some(_) => error(position, "The record count must be a whole number of at least 1"),
+            }
+        }
+
+        let history = matches!(name, "d" | "prev" | "avg");
+        let arity = match (history, Function::from_name(name)) {
+            (true, _) if name == "d" => 1..=1,
+            (true, _) => 1..=2,
+            (false, Some(function)) => function.arity()..=function.arity(),
+            (false, None) => return error(position, format!("Unknown function \"{name}\"")),
+        };
+        if !arity.contains(&args.len()) {
+            return error(position, format!("Wrong number of arguments for \"{name}\""));
+        }
+
+        Ok(match name {
+            "d" => {
+                // Allow the derivative to be written as d(x)/dt
+                if self.peek() == Some(&Token::Operator(Operator::Divide)) && self.tokens.get(self.next + 1).map(|(_, t)| t) == Some(&Token::Name("dt".to_owned())) {
+                    self.next += 2;
+                }
+                Node::Derivative(Box::new(args.remove(0)))
+            },
+            "prev" => Node::Previous(Box::new(args[0].clone()), count(&args, position)?),
+            "avg" => Node::Average(Box::new(args[0].clone()), count(&args, position)?),
+            _ => Node::Call(Function::from_name(name).expect("checked above"), args),
+        })
+    }
+}
+
+/// A parsed expression
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expression {
+    root: Node,
+}
+
+impl Expression {
+    /// Parses an expression made of:
+    ///
+    /// - numbers, `pi`, schema field names such as `pressure` or `accel_x`, and `t` for the uptime in seconds
+    /// - `+`, `-`, `*`, `/`, `^` and parentheses
+    /// - `sqrt`, `abs`, `sin`, `cos`, `tan`, `atan2`, `ln`, `log10`, `exp`, `min` and `max`
+    /// - `d(x)` or `d(x)/dt`, the change of `x` per second since the previous record
+    /// - `prev(x, n)`, the value of `x` n records earlier, 1 if left out
+    /// - `avg(x, n)`, the mean of `x` over the last n records, ignoring missing values
+    pub fn parse(text: &str) -> Result<Expression, ExpressionError> {
+        let mut parser = Parser { tokens: tokenize(text)?, next: 0, end: text.len() };
+        let root = parser.expression()?;
+
+        if parser.next < parser.tokens.len() {
+            return error(parser.position(), "Unexpected input");
+        }
+
+        Ok(Expression { root })
+    }
+
+    /// Value of the expression at every record of a session. Fails if a name isn't in the schema.
+    pub fn evaluate(&self, schema: &TelemetrySchema, session: &[SensedData]) -> Result<Vec<f64>, ExpressionError> {
+        let time: Vec<f64> = session.iter().map(|s| s.timestamp() as f64 / 1_000_000.0).collect();
+        evaluate(&self.root, schema, session, &time)
+    }
+}
+
+fn evaluate(node: &Node, schema: &TelemetrySchema, session: &[SensedData], time: &[f64]) -> Result<Vec<f64>, ExpressionError> {
+    let evaluate = |node: &Node| evaluate(node, schema, session, time);
+
+    Ok(match node {
+        Node::Number(value) => vec![*value; session.len()],
+        Node::Variable { name, .. } if name == "t" => time.to_vec(),
+        Node::Variable { name, position } => {
+            let Some(field) = schema.fields().iter().find(|field| &field.name == name) else {
+                return error(*position, format!("No field named \"{name}\""));
+            };
+            session.iter().map(|s| field.value(s)).collect()
+        },
+        Node::Negate(node) => evaluate(node)?.into_iter().map(|v| -v).collect(),
+        Node::Binary(operator, left, right) => {
+            let (left, right) = (evaluate(left)?, evaluate(right)?);
+            left.into_iter().zip(right)
+                .map(|(a, b)| match operator {
+                    Operator::Add => a + b,
+                    Operator::Subtract => a - b,
+                    Operator::Multiply => a * b,
+                    Operator::Divide => a / b,
+                    Operator::Power => a.powf(b),
+                })
+                .collect()
+        },
+        Node::Call(function, args) => {
+            let args = args.iter().map(evaluate).collect::<Result<Vec<_>, _>>()?;
+            (0..session.len())
+                .map(|i| function.apply(&args.iter().map(|arg| arg[i]).collect::<Vec<_>>()))
+                .collect()
+        },
+        Node::Derivative(node) => {
+            let values = evaluate(node)?;
+            (0..values.len())
+                .map(|i| match i.checked_sub(1) {
+                    Some(p) if time[i] > time[p] => (values[i] - values[p]) / (time[i] - time[p]),
+                    _ => f64::NAN,
+                })
+                .collect()
+        },
+        Node::Previous(node, count) => {
+            let values = evaluate(node)?;
+            (0..values.len())
+                .map(|i| i.checked_sub(*count).map_or(f64::NAN, |p| values[p]))
+                .collect()
+        },
+        Node::Average(node, count) => {
+            let values = evaluate(node)?;
+            let (mut sum, mut valid) = (0.0, 0);
+            let mut means = Vec::with_capacity(values.len());
+
+            for i in 0..values.len() {
+                if !values[i].is_nan() {
+                    sum += values[i];
+                    valid += 1;
+                }
+                if let Some(&old) = i.checked_sub(*count).map(|p| &values[p]) {
+                    if !old.is_nan() {
+                        sum -= old;
+                        valid -= 1;
+                    }
+                }
+                means.push(if valid == 0 { f64::NAN } else { sum / valid as f64 });
+            }
+            means
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session() -> Vec<SensedData> {
+        (0..5)
+            .map(|i| SensedData {
+                uptime: i * 500,
+                pressure: 1000.0 - 10.0 * i as f32,
+                acceleration: [3.0, 4.0, i as f64],
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    fn evaluate(text: &str) -> Vec<f64> {
+        Expression::parse(text).unwrap().evaluate(&TelemetrySchema::default(), &session()).unwrap()
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        assert_eq!(evaluate("sqrt(accel_x^2 + accel_y^2)"), vec![5.0; 5]);
+        assert_eq!(evaluate("-2^2 + 10 / (4 - 2) * 3")[0], 11.0);
+        assert_eq!(evaluate("pressure / 100")[1], 9.9);
+        assert_eq!(evaluate("max(accel_z, 2) + 1.5e1")[..3], [17.0, 17.0, 17.0]);
+        assert_eq!(evaluate("t")[4], 2.0);
+    }
+
+    #[test]
+    fn test_history() {
+        let derivative = evaluate("d(pressure)/dt");
+        assert!(derivative[0].is_nan());
+        assert_eq!(derivative[1..], [-20.0; 4]);
+        assert_eq!(evaluate("d(pressure) * 2")[1..], evaluate("2 * d(pressure)/dt")[1..]);
+
+        let previous = evaluate("prev(accel_z, 2)");
+        assert!(previous[1].is_nan());
+        assert_eq!(previous[4], 2.0);
+
+        assert_eq!(evaluate("avg(accel_z, 2)"), vec![0.0, 0.5, 1.5, 2.5, 3.5]);
+    }
+
+    #[test]
+    fn test_errors() {
+        let schema = TelemetrySchema::default();
+        let parse_error = |text: &str| Expression::parse(text).unwrap_err().position;
+
+        assert_eq!(parse_error("1 +"), 3);
+        assert_eq!(parse_error("(1 + 2"), 6);
+        assert_eq!(parse_error("foo(1)"), 0);
+        assert_eq!(parse_error("avg(pressure, 1.5)"), 0);
+        assert_eq!(parse_error("1 $ 2"), 2);
+
+        let unknown = Expression::parse("2 * humidity").unwrap().evaluate(&schema, &[]).unwrap_err();
+        assert_eq!(unknown, ExpressionError { position: 4, msg: "No field named \"humidity\"".to_owned() });
+    }
+}
@@ -0,0 +1,93 @@
+use crate::data::SensedData;
+
+/// A run of frames that were never recieved
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gap {
+    /// Position in the session of the last frame recieved before the gap
+    pub before: usize,
+    /// First missing index
+    pub start_index: u32,
+    /// Last missing index
+    pub end_index: u32,
+    /// Uptime between the frames around the gap, in milliseconds
+    pub duration_ms: u64,
+}
+
+impl Gap {
+    pub fn missing(&self) -> u32 {
+        self.end_index - self.start_index + 1
+    }
+}
+
+/// Finds every gap in the indices of a session, which is ordered by index
+pub fn find_gaps(session: &[SensedData]) -> Vec<Gap> {
+    session.windows(2)
+        .enumerate()
+        .filter(|(_, pair)| pair[1].index.saturating_sub(pair[0].index) > 1)
+        .filter_map(|(before, pair)| Some(Gap {
+            before,
+            start_index: pair[0].index.checked_add(1)?,
+            end_index: pair[1].index - 1,
+            duration_ms: pair[1].timestamp().saturating_sub(pair[0].timestamp()) / 1000,
+        }))
+        .collect()
+}
+
+/// Share of frames lost over the `window` seconds of uptime before each frame, in percent.
+/// Points are given as uptime in seconds and loss.
+pub fn loss_rate(session: &[SensedData], window: f64) -> Vec<[f64; 2]> {
+    let window_us = (window * 1_000_000.0) as u64;
+    let mut first = 0;
+
+    session.iter()
+        .enumerate()
+        .map(|(i, frame)| {
+            while session[first].timestamp() + window_us < frame.timestamp() {
+                first += 1;
+            }
+
+            let expected = frame.index.saturating_sub(session[first].index) as usize + 1;
+            let recieved = (i + 1).saturating_sub(first).min(expected);
+            let loss = 1.0 - recieved as f64 / expected as f64;
+
+            [frame.timestamp() as f64 / 1_000_000.0, loss * 100.0]
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frames(indices: &[u32]) -> Vec<SensedData> {
+        indices.iter()
+            .map(|&index| SensedData { index, uptime: index * 100, ..Default::default() })
+            .collect()
+    }
+
+    #[test]
+    fn test_find_gaps() {
+        let session = frames(&[1, 2, 5, 6, 8]);
+        let gaps = find_gaps(&session);
+
+        assert_eq!(gaps.len(), 2);
+        assert_eq!((gaps[0].before, gaps[0].start_index, gaps[0].end_index, gaps[0].missing()), (1, 3, 4, 2));
+        assert_eq!(gaps[0].duration_ms, 300);
+        assert_eq!((gaps[1].before, gaps[1].missing()), (3, 1));
+
+        // A corrupted index at the very top of the range is not a gap
+        let session = [u32::MAX, 3].map(|index| SensedData { index, ..Default::default() });
+        assert!(find_gaps(&session).is_empty());
+    }
+
+    #[test]
+    fn test_loss_rate() {
+        let session = frames(&[0, 1, 2, 3, 8, 9]);
+        let rate = loss_rate(&session, 0.5);
+
+        assert_eq!(rate[3], [0.3, 0.0]);
+        // Frames 3 to 8 fall in the window of frame 8, 4 of the 6 are missing
+        assert!((rate[4][1] - 400.0 / 6.0).abs() < 1e-9);
+        assert_eq!(rate[5][0], 0.9);
+    }
+}
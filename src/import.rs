@@ -0,0 +1,383 @@
+use std::{path::PathBuf, sync::Arc};
+
+use egui::{Context, Ui};
+use egui_extras::{Column, TableBuilder};
+use serde::{Deserialize, Serialize};
+
+use crate::{data::{MissionData, ReadConfidence, SensedData}, schema::{FieldType, FieldValue, TelemetrySchema}};
+
+/// A delimited text file with a header row
+#[derive(Debug, Clone, PartialEq)]
+pub struct CsvTable {
+    pub headers: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+impl CsvTable {
+    /// Reads comma, semicolon or tab separated text, whichever the header row uses most.
+    /// Fields may be quoted with `"`. Empty lines and lines starting with `#` are skipped.
+    pub fn parse(text: &str) -> Option<CsvTable> {
+        let mut lines = text.lines()
+            .map(|line| line.trim_end_matches('\r'))
+            .filter(|line| !line.trim().is_empty() && !line.starts_with('#'));
+
+        let header = lines.next()?;
+        let delimiter = ['\t', ',', ';'].into_iter()
+            .max_by_key(|d| header.matches(*d).count())
+            .expect("there are delimiters to choose from");
+
+        Some(CsvTable {
+            headers: split_row(header, delimiter).into_iter().map(|h| h.trim().to_owned()).collect(),
+            rows: lines.map(|line| split_row(line, delimiter)).collect(),
+        })
+    }
+}
+
+fn split_row(line: &str, delimiter: char) -> Vec<String> {
+    let mut fields = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            },
+            '"' => quoted = !quoted,
+            c if c == delimiter && !quoted => fields.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+    fields.push(field);
+
+    fields
+}
+
+/// Turns a value in the unit of the imported file into the unit of the schema field
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Conversion {
+    pub scale: f64,
+    pub offset: f64,
+}
+
+impl Default for Conversion {
+    fn default() -> Self {
+        Self { scale: 1.0, offset: 0.0 }
+    }
+}
+
+impl Conversion {
+    pub fn apply(self, value: f64) -> f64 {
+        value * self.scale + self.offset
+    }
+}
+
+/// Common conversions offered in the wizard
+pub const CONVERSIONS: [(&str, Conversion); 10] = [
+    ("None", Conversion { scale: 1.0, offset: 0.0 }),
+    ("hPa → Pa", Conversion { scale: 100.0, offset: 0.0 }),
+    ("kPa → Pa", Conversion { scale: 1000.0, offset: 0.0 }),
+    ("s → ms", Conversion { scale: 1000.0, offset: 0.0 }),
+    ("ms → s", Conversion { scale: 0.001, offset: 0.0 }),
+    ("µs → ms", Conversion { scale: 0.001, offset: 0.0 }),
+    ("K → °C", Conversion { scale: 1.0, offset: -273.15 }),
+    ("°F → °C", Conversion { scale: 5.0 / 9.0, offset: -32.0 * 5.0 / 9.0 }),
+    ("m/s² → g", Conversion { scale: 1.0 / 9.80665, offset: 0.0 }),
+    ("rad/s → dps", Conversion { scale: 180.0 / std::f64::consts::PI, offset: 0.0 }),
+];
+
+/// Where a column of the imported file goes
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ColumnMapping {
+    pub column: String,
+    /// Name of the schema field, or `None` to leave the column out
+    pub field: Option<String>,
+    pub conversion: Conversion,
+}
+
+/// A reusable way of reading files of one layout
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ImportMapping {
+    pub name: String,
+    pub columns: Vec<ColumnMapping>,
+}
+
+impl ImportMapping {
+    /// Maps every column whose header names a schema field, ignoring case and a unit in brackets
+    pub fn guess(headers: &[String], schema: &TelemetrySchema) -> ImportMapping {
+        let columns = headers.iter()
+            .map(|header| {
+                let name = header.split(['[', '(']).next().unwrap_or_default().trim().to_lowercase();
+                let field = schema.fields().iter()
+                    .find(|field| field.name.to_lowercase() == name || field.label().to_lowercase() == name)
+                    .map(|field| field.name.clone());
+
+                ColumnMapping { column: header.clone(), field, conversion: Conversion::default() }
+            })
+            .collect();
+
+        ImportMapping { name: String::new(), columns }
+    }
+
+    /// Whether the mapping was made for files with these headers
+    pub fn matches(&self, headers: &[String]) -> bool {
+        self.columns.len() == headers.len() && self.columns.iter().zip(headers).all(|(mapping, header)| &mapping.column == header)
+    }
+
+    /// Reads a row into a record. Rows without an index column are numbered from 1.
+    pub fn convert_row(&self, schema: &TelemetrySchema, row: &[String], row_number: usize) -> Result<SensedData, String> {
+        let mut data = SensedData {
+            index: row_number as u32 + 1,
+            extra: vec![f64::NAN; schema.extra_count()],
+            ..Default::default()
+        };
+
+        for (mapping, text) in self.columns.iter().zip(row) {
+            let Some(field) = mapping.field.as_ref().and_then(|name| schema.fields().iter().find(|f| &f.name == name)) else {
+                continue;
+            };
+
+            let text = text.trim();
+            if text.is_empty() {
+                continue;
+            }
+            let value = text.parse::<f64>()
+                .map(|value| mapping.conversion.apply(value))
+                .map_err(|_| format!("\"{text}\" in column {} is not a number", mapping.column))?;
+
+            let value = match field.field_type {
+                FieldType::Float => FieldValue::Float(value),
+                _ if !(0.0..=u32::MAX as f64).contains(&value.round()) => {
+                    return Err(format!("{value} in column {} is not a whole number", mapping.column));
+                },
+                FieldType::Integer => FieldValue::Integer(value.round() as u32),
+                FieldType::Confidence => FieldValue::Confidence(ReadConfidence::try_from(value.round() as u8).unwrap_or(ReadConfidence::Unreliable)),
+            };
+            field.store(&mut data, value);
+        }
+
+        Ok(data)
+    }
+
+    /// Reads the whole table. Returns the data and the number of rows that could not be read.
+    pub fn import(&self, table: &CsvTable, schema: Arc<TelemetrySchema>) -> (MissionData, usize) {
+        let mut data = MissionData::with_schema(schema.clone());
+        let mut skipped = 0;
+
+        for (i, row) in table.rows.iter().enumerate() {
+            match self.convert_row(&schema, row, i) {
+                Ok(frame) => data.recieve_frame(frame),
+                Err(_) => skipped += 1,
+            }
+        }
+        data.flush_reorder_buffer();
+
+        (data, skipped)
+    }
+}
+
+/// How many converted rows the wizard previews
+const PREVIEW_ROWS: usize = 20;
+
+/// State of the window in which a file is mapped before it is imported
+pub struct ImportWizard {
+    pub path: PathBuf,
+    table: CsvTable,
+    mapping: ImportMapping,
+}
+
+impl ImportWizard {
+    /// Starts mapping a file, reusing a saved mapping if one fits its headers
+    pub fn new(path: PathBuf, table: CsvTable, saved: &[ImportMapping], schema: &TelemetrySchema) -> Self {
+        let mapping = saved.iter()
+            .find(|mapping| mapping.matches(&table.headers))
+            .cloned()
+            .unwrap_or_else(|| ImportMapping::guess(&table.headers, schema));
+
+        Self { path, table, mapping }
+    }
+}
+
+/// What the user chose in the import window
+pub enum WizardAction {
+    Import(MissionData, usize),
+    Cancel,
+}
+
+pub fn import_window(ctx: &Context, wizard: &mut ImportWizard, saved: &mut Vec<ImportMapping>, schema: &Arc<TelemetrySchema>) -> Option<WizardAction> {
+    let mut action = None;
+    let mut open = true;
+
+    let file_name = wizard.path.file_name().map_or_else(String::new, |name| name.to_string_lossy().into_owned());
+
+    egui::Window::new(format!("Import {file_name}"))
+        .open(&mut open)
+        .collapsible(false)
+        .default_width(720.0)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Saved mappings");
+                egui::ComboBox::from_id_salt("import_mapping_combo_box")
+                    .selected_text(if wizard.mapping.name.is_empty() { "Unsaved" } else { &wizard.mapping.name })
+                    .show_ui(ui, |ui| {
+                        for mapping in saved.iter() {
+                            let enabled = mapping.matches(&wizard.table.headers);
+                            if ui.add_enabled(enabled, egui::Button::selectable(mapping.name == wizard.mapping.name, &mapping.name)).clicked() {
+                                wizard.mapping = mapping.clone();
+                            }
+                        }
+                    });
+
+                ui.add(egui::TextEdit::singleline(&mut wizard.mapping.name).hint_text("Mapping name").desired_width(140.0));
+                if ui.add_enabled(!wizard.mapping.name.trim().is_empty(), egui::Button::new("Save")).clicked() {
+                    saved.retain(|mapping| mapping.name != wizard.mapping.name);
+                    saved.push(wizard.mapping.clone());
+                }
+            });
+
+            ui.separator();
+
+            mapping_grid(ui, &mut wizard.mapping, schema);
+
+            ui.separator();
+
+            ui.strong("Preview");
+            preview_table(ui, wizard, schema);
+
+            ui.separator();
+
+            ui.horizontal(|ui| {
+                if ui.button("Import").clicked() {
+                    let (data, skipped) = wizard.mapping.import(&wizard.table, schema.clone());
+                    action = Some(WizardAction::Import(data, skipped));
+                }
+                if ui.button("Cancel").clicked() {
+                    action = Some(WizardAction::Cancel);
+                }
+                ui.label(format!("{} rows", wizard.table.rows.len()));
+            });
+        });
+
+    if !open {
+        action = Some(WizardAction::Cancel);
+    }
+
+    action
+}
+
+fn mapping_grid(ui: &mut Ui, mapping: &mut ImportMapping, schema: &TelemetrySchema) {
+    egui::ScrollArea::vertical().id_salt("import_mapping_scroll").max_height(240.0).show(ui, |ui| {
+        egui::Grid::new("import_mapping_grid").num_columns(4).striped(true).show(ui, |ui| {
+            ui.strong("Column");
+            ui.strong("Field");
+            ui.strong("Conversion");
+            ui.strong("Scale, offset");
+            ui.end_row();
+
+            for (i, column) in mapping.columns.iter_mut().enumerate() {
+                ui.label(&column.column);
+
+                let selected = column.field.as_ref()
+                    .and_then(|name| schema.fields().iter().find(|f| &f.name == name))
+                    .map_or_else(|| "Ignored".to_owned(), |field| field.label_with_unit());
+                egui::ComboBox::from_id_salt(("import_field", i))
+                    .selected_text(selected)
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut column.field, None, "Ignored");
+                        for field in schema.fields() {
+                            ui.selectable_value(&mut column.field, Some(field.name.clone()), field.label_with_unit());
+                        }
+                    });
+
+                let preset = CONVERSIONS.iter().find(|(_, c)| *c == column.conversion).map_or("Custom", |(name, _)| *name);
+                egui::ComboBox::from_id_salt(("import_conversion", i))
+                    .selected_text(preset)
+                    .show_ui(ui, |ui| {
+                        for (name, conversion) in CONVERSIONS {
+                            ui.selectable_value(&mut column.conversion, conversion, name);
+                        }
+                    });
+
+                ui.horizontal(|ui| {
+                    ui.add(egui::DragValue::new(&mut column.conversion.scale).speed(0.01).prefix("× "));
+                    ui.add(egui::DragValue::new(&mut column.conversion.offset).speed(0.1).prefix("+ "));
+                });
+                ui.end_row();
+            }
+        });
+    });
+}
+
+fn preview_table(ui: &mut Ui, wizard: &ImportWizard, schema: &TelemetrySchema) {
+    let fields = schema.fields();
+    let text_height = egui::TextStyle::Body.resolve(ui.style()).size.max(ui.spacing().interact_size.y);
+
+    let rows: Vec<Result<SensedData, String>> = wizard.table.rows.iter()
+        .take(PREVIEW_ROWS)
+        .enumerate()
+        .map(|(i, row)| wizard.mapping.convert_row(schema, row, i))
+        .collect();
+
+    egui::ScrollArea::horizontal().id_salt("import_preview_scroll").show(ui, |ui| {
+        TableBuilder::new(ui)
+            .id_salt("import_preview")
+            .max_scroll_height(240.0)
+            .columns(Column::auto(), fields.len())
+            .header(20.0, |mut header| {
+                for field in fields {
+                    header.col(|ui| {ui.label(field.label_with_unit());});
+                }
+            })
+            .body(|body| {
+                body.rows(text_height, rows.len(), |mut row| {
+                    match &rows[row.index()] {
+                        Ok(data) => for field in fields {
+                            row.col(|ui| {ui.label(field.format(data));});
+                        },
+                        Err(e) => {
+                            row.col(|ui| {ui.colored_label(ui.visuals().error_fg_color, e);});
+                        },
+                    }
+                });
+            });
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_csv() {
+        let table = CsvTable::parse("# bench test\ntime [s];\"pressure; hPa\"\n1.5;1013.25\n2;\n").unwrap();
+
+        assert_eq!(table.headers, ["time [s]", "pressure; hPa"]);
+        assert_eq!(table.rows, [vec!["1.5", "1013.25"], vec!["2", ""]]);
+    }
+
+    #[test]
+    fn test_mapping() {
+        let schema = TelemetrySchema::default();
+        let table = CsvTable::parse("Uptime,Pressure [hPa],humidity\n1.5,1013.25,40\nbad,1000,41\n2,,42").unwrap();
+
+        let mut mapping = ImportMapping::guess(&table.headers, &schema);
+        assert_eq!(mapping.columns[0].field.as_deref(), Some("uptime"));
+        assert_eq!(mapping.columns[1].field.as_deref(), Some("pressure"));
+        assert_eq!(mapping.columns[2].field, None);
+
+        mapping.columns[0].conversion = CONVERSIONS[3].1;
+        mapping.columns[1].conversion = CONVERSIONS[1].1;
+
+        let (data, skipped) = mapping.import(&table, Arc::new(schema));
+        assert_eq!(skipped, 1);
+
+        let session = &data.sessions()[0];
+        assert_eq!((session[0].index, session[0].uptime, session[0].pressure), (1, 1500, 101325.0));
+        assert_eq!(session[1].index, 3);
+        assert!(session[1].pressure.is_nan());
+
+        assert!(mapping.matches(&table.headers));
+    }
+}
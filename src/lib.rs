@@ -1,8 +1,28 @@
 #![warn(clippy::all, rust_2018_idioms)]
 
+mod alarms;
+mod altitude;
 mod app;
+mod capture;
+mod checksum;
+mod console;
 mod data;
+mod events;
+mod export;
+mod expression;
+mod gaps;
+mod import;
+mod merge;
+mod network;
+mod project;
+mod reader;
+mod replay;
+mod schema;
+mod serial;
+mod simulator;
+mod sources;
 mod tabs;
+mod track;
 mod util;
 
 pub use app::TemplateApp;
\ No newline at end of file
@@ -0,0 +1,328 @@
+use std::{collections::HashMap, sync::Arc};
+
+use crate::{data::{MissionData, SensedData}, schema::TelemetrySchema, sources::SourceId};
+
+/// Combines the frames recieved by several ground station radios into one stream.
+/// A frame recieved by more than one radio is kept once. Only frames that passed
+/// validation reach the sessions of the inputs, so a frame corrupted on one radio
+/// is taken from another that recieved it intact.
+#[derive(Debug, Clone)]
+pub struct Merger {
+    receivers: Vec<Receiver>,
+    data: MissionData,
+    /// For every merged session, the receivers each frame index was recieved by
+    deliveries: Vec<HashMap<u32, Delivery>>,
+}
+
+#[derive(Debug, Clone)]
+struct Receiver {
+    source: SourceId,
+    name: String,
+    /// Merged session of every frame already taken from the input, by index and timestamp.
+    /// Frames are recognised by these rather than by position, as the sessions of the input
+    /// can be reordered, edited or rebuilt between updates.
+    taken: HashMap<(u32, u64), usize>,
+    /// Number of frames in the sessions of the input at the last update
+    frame_count: usize,
+    /// Index and timestamp of the last frame of the input at the last update
+    last_frame: Option<(u32, u64)>,
+    corrupted: usize,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Delivery {
+    /// Receiver that recieved the frame first
+    first: usize,
+    /// Bit set of all receivers that recieved the frame
+    receivers: u64,
+}
+
+/// How a single receiver did in a merged session
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReceiverStats {
+    pub name: String,
+    /// Frames recieved intact, including ones also recieved by other receivers
+    pub recieved: usize,
+    /// Frames this receiver was the first to deliver
+    pub contributed: usize,
+    /// Frames of the session this receiver didn't deliver
+    pub lost: usize,
+    /// Corrupted frames across all sessions
+    pub corrupted: usize,
+}
+
+/// At most this many receivers can be merged, as they are tracked in a bit set
+pub const MAX_RECEIVERS: usize = 64;
+
+impl Merger {
+    pub fn new(inputs: Vec<(SourceId, String)>, schema: Arc<TelemetrySchema>) -> Self {
+        Self {
+            receivers: inputs.into_iter()
+                .take(MAX_RECEIVERS)
+                .map(|(source, name)| Receiver { source, name, taken: HashMap::new(), frame_count: 0, last_frame: None, corrupted: 0 })
+                .collect(),
+            data: MissionData::with_schema(schema),
+            deliveries: vec![],
+        }
+    }
+
+    pub fn data(&self) -> &MissionData {
+        &self.data
+    }
+
+    pub fn inputs(&self) -> impl Iterator<Item = SourceId> + '_ {
+        self.receivers.iter().map(|r| r.source)
+    }
+
+    /// Takes in the frames the inputs recieved since the last update. Inputs that
+    /// are no longer open are simply missing from `inputs`.
+    pub fn update<'a>(&mut self, inputs: impl Iterator<Item = (SourceId, &'a MissionData)>) {
+        for (source, input) in inputs {
+            let Some(r) = self.receivers.iter().position(|r| r.source == source) else {
+                continue;
+            };
+
+            self.receivers[r].corrupted = (0..input.sessions().len()).map(|s| input.corrupted_frames(s)).sum();
+
+            // Edits of the input only move frames around, so there is nothing new unless the count
+            // or the last frame changed
+            let frame_count = input.sessions().iter().map(|frames| frames.len()).sum();
+            let last_frame = input.sessions().last()
+                .and_then(|frames| frames.last())
+                .map(|frame| (frame.index, frame.timestamp()));
+            if (frame_count, last_frame) == (self.receivers[r].frame_count, self.receivers[r].last_frame) {
+                continue;
+            }
+            self.receivers[r].frame_count = frame_count;
+            self.receivers[r].last_frame = last_frame;
+
+            // The sessions of the inputs don't have to line up with each other or with the merged
+            // ones, so a frame goes to the merged session the frames around it in the input went to
+            for frames in input.sessions() {
+                for (i, frame) in frames.iter().enumerate() {
+                    let taken = &self.receivers[r].taken;
+                    if taken.contains_key(&(frame.index, frame.timestamp())) {
+                        continue;
+                    }
+
+                    let session = frames[..i].iter().rev()
+                        .chain(&frames[i + 1..])
+                        .find_map(|neighbour| taken.get(&(neighbour.index, neighbour.timestamp())).copied())
+                        .unwrap_or_else(|| self.continued_session(frame));
+                    self.receivers[r].taken.insert((frame.index, frame.timestamp()), session);
+                    self.take_frame(r, session, frame);
+                }
+            }
+        }
+    }
+
+    /// Merged session for a frame nothing around it was taken for yet: the one already holding
+    /// the same frame, else the last one if the frame follows on from it, else a new one
+    fn continued_session(&self, frame: &SensedData) -> usize {
+        let sessions = self.data.sessions();
+        let known = sessions.iter().rposition(|frames| {
+            frames.iter().any(|f| f.index == frame.index && f.timestamp() == frame.timestamp())
+        });
+        let follows = sessions.last()
+            .and_then(|frames| frames.last())
+            .is_some_and(|newest| !self.data.segmentation().starts_session(newest, frame));
+
+        match known {
+            Some(session) => session,
+            None if follows => sessions.len() - 1,
+            None => sessions.len(),
+        }
+    }
+
+    fn take_frame(&mut self, receiver: usize, session: usize, frame: &SensedData) {
+        while self.deliveries.len() <= session {
+            self.deliveries.push(HashMap::new());
+        }
+
+        let bit = 1 << receiver;
+        match self.deliveries[session].get_mut(&frame.index) {
+            Some(delivery) => delivery.receivers |= bit,
+            None => {
+                self.deliveries[session].insert(frame.index, Delivery { first: receiver, receivers: bit });
+                self.data.insert_frame(session, frame.clone());
+            },
+        }
+    }
+
+    pub fn receiver_stats(&self, session: usize) -> Vec<ReceiverStats> {
+        let frames = self.data.sessions().get(session).map_or(&[][..], |s| s.as_slice());
+        let deliveries = self.deliveries.get(session);
+
+        let total = match (frames.first(), frames.last()) {
+            (Some(first), Some(last)) => (last.index - first.index + 1) as usize,
+            _ => 0,
+        };
+
+        self.receivers.iter().enumerate()
+            .map(|(r, receiver)| {
+                let (recieved, contributed) = deliveries.map_or((0, 0), |deliveries| {
+                    deliveries.values().fold((0, 0), |(recieved, contributed), delivery| (
+                        recieved + ((delivery.receivers >> r) & 1) as usize,
+                        contributed + (delivery.first == r) as usize,
+                    ))
+                });
+
+                ReceiverStats {
+                    name: receiver.name.clone(),
+                    recieved,
+                    contributed,
+                    lost: total.saturating_sub(recieved),
+                    corrupted: receiver.corrupted,
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::test_frame;
+
+    /// Uptime of a frame in milliseconds. Every boot of the probe starts its uptime lower than the one before.
+    fn uptime(boot: u32, index: u32) -> u32 {
+        60_000 / (boot + 1) + index * 100
+    }
+
+    /// An input that recieved the given frames, where an index going back is a reboot of the probe
+    fn input(indices: &[u32]) -> MissionData {
+        let mut boot = 0;
+        let log: Vec<String> = indices.iter().enumerate()
+            .map(|(i, &index)| {
+                if i > 0 && index < indices[i - 1] {
+                    boot += 1;
+                }
+                test_frame(index, uptime(boot, index))
+            })
+            .collect();
+        MissionData::from_log(&log.join("\n"), Arc::default())
+    }
+
+    fn indices(data: &MissionData, session: usize) -> Vec<u32> {
+        data.sessions()[session].iter().map(|d| d.index).collect()
+    }
+
+    #[test]
+    fn test_deduplicates_by_index() {
+        let mut merger = Merger::new(vec![(0, "A".to_owned()), (1, "B".to_owned())], Arc::default());
+        let a = input(&[1, 2, 4, 5]);
+        let b = input(&[1, 3, 4, 6]);
+
+        merger.update([(0, &a), (1, &b)].into_iter());
+        assert_eq!(indices(merger.data(), 0), [1, 2, 3, 4, 5, 6]);
+
+        // Updating again doesn't take the same frames twice
+        merger.update([(0, &a), (1, &b)].into_iter());
+        assert_eq!(merger.data().sessions()[0].len(), 6);
+
+        let stats = merger.receiver_stats(0);
+        assert_eq!((stats[0].recieved, stats[0].contributed, stats[0].lost), (4, 4, 2));
+        assert_eq!((stats[1].recieved, stats[1].contributed, stats[1].lost), (4, 2, 2));
+    }
+
+    #[test]
+    fn test_prefers_valid_frames() {
+        let mut merger = Merger::new(vec![(0, "A".to_owned()), (1, "B".to_owned())], Arc::default());
+
+        let mut a = MissionData::default();
+        let _ = a.parse_line(&test_frame(1, uptime(0, 1)));
+        let _ = a.parse_line(&format!("{}\t*00", test_frame(2, uptime(0, 2))));
+        let b = input(&[1, 2]);
+
+        merger.update([(0, &a), (1, &b)].into_iter());
+        assert_eq!(indices(merger.data(), 0), [1, 2]);
+
+        let stats = merger.receiver_stats(0);
+        assert_eq!(stats[0].corrupted, 1);
+        assert_eq!(stats[0].lost, 1);
+        assert_eq!(stats[1].lost, 0);
+    }
+
+    #[test]
+    fn test_follows_reboots() {
+        let mut merger = Merger::new(vec![(0, "A".to_owned()), (1, "B".to_owned())], Arc::default());
+
+        // A sees the reboot before B has caught up with the first session
+        let a = input(&[1, 2, 3, 1, 2]);
+        let b = input(&[1, 3]);
+        merger.update([(0, &a), (1, &b)].into_iter());
+
+        let b = input(&[1, 3, 1, 3]);
+        merger.update([(1, &b)].into_iter());
+
+        assert_eq!(merger.data().sessions().len(), 2);
+        assert_eq!(indices(merger.data(), 0), [1, 2, 3]);
+        assert_eq!(indices(merger.data(), 1), [1, 2, 3]);
+    }
+
+    #[test]
+    fn test_edited_inputs() {
+        let mut merger = Merger::new(vec![(0, "A".to_owned())], Arc::default());
+        let mut a = input(&[1, 2, 3, 4, 5, 6]);
+        merger.update([(0, &a)].into_iter());
+
+        // Splitting the input only moves frames that were already taken
+        a.split_session(0, 3);
+        merger.update([(0, &a)].into_iter());
+        assert_eq!(merger.data().sessions().len(), 1);
+        assert_eq!(indices(merger.data(), 0), [1, 2, 3, 4, 5, 6]);
+
+        // A replay seeked backwards starts its data over
+        let a = input(&[1, 2]);
+        merger.update([(0, &a)].into_iter());
+        assert_eq!(indices(merger.data(), 0), [1, 2, 3, 4, 5, 6]);
+
+        let a = input(&[1, 2, 3, 4, 5, 6, 7]);
+        merger.update([(0, &a)].into_iter());
+        assert_eq!(indices(merger.data(), 0), [1, 2, 3, 4, 5, 6, 7]);
+    }
+
+    #[test]
+    fn test_misaligned_inputs() {
+        let mut merger = Merger::new(vec![(0, "A".to_owned()), (1, "B".to_owned()), (2, "C".to_owned())], Arc::default());
+
+        // B split its session where A didn't, and C was only opened after the reboot
+        let mut a = input(&[1, 2, 3, 4, 5, 6, 1, 2]);
+        let mut b = input(&[2, 3, 4, 5]);
+        b.split_session(0, 2);
+        let mut c = MissionData::default();
+        c.parse_line(&test_frame(2, uptime(1, 2))).unwrap();
+        merger.update([(0, &a), (1, &b), (2, &c)].into_iter());
+        assert_eq!(indices(merger.data(), 0), [1, 2, 3, 4, 5, 6]);
+        assert_eq!(indices(merger.data(), 1), [1, 2]);
+
+        // Frames recieved after an edit of the inputs go on where the frames before them went
+        a.split_session(0, 3);
+        a.delete_session(2);
+        b.parse_line(&test_frame(6, uptime(0, 6))).unwrap();
+        b.parse_line(&test_frame(7, uptime(0, 7))).unwrap();
+        c.parse_line(&test_frame(3, uptime(1, 3))).unwrap();
+        a.parse_line(&test_frame(7, uptime(0, 7))).unwrap();
+        merger.update([(0, &a), (1, &b), (2, &c)].into_iter());
+        assert_eq!(merger.data().sessions().len(), 2);
+        assert_eq!(indices(merger.data(), 0), [1, 2, 3, 4, 5, 6, 7]);
+        assert_eq!(indices(merger.data(), 1), [1, 2, 3]);
+
+        let stats = merger.receiver_stats(0);
+        assert_eq!((stats[0].recieved, stats[1].recieved, stats[2].recieved), (7, 6, 0));
+        let stats = merger.receiver_stats(1);
+        assert_eq!((stats[0].recieved, stats[1].recieved, stats[2].recieved), (2, 0, 2));
+    }
+
+    #[test]
+    fn test_reordered_input_frames() {
+        let mut merger = Merger::new(vec![(0, "A".to_owned())], Arc::default());
+        let mut a = input(&[20, 22, 23]);
+        merger.update([(0, &a)].into_iter());
+
+        // The late frame goes in the middle of the input session
+        a.parse_line(&test_frame(21, uptime(0, 21))).unwrap();
+        merger.update([(0, &a)].into_iter());
+        assert_eq!(indices(merger.data(), 0), [20, 21, 22, 23]);
+    }
+}
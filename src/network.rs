@@ -0,0 +1,189 @@
+use std::{io::{self, BufReader, Read, Write}, net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs, UdpSocket}, sync::{Arc, Mutex}, time::Duration};
+
+use serde::{Deserialize, Serialize};
+
+use crate::reader::{Connection, LineWriter, Reconnect};
+
+/// How long a read may block, so the reader thread can notice it was cancelled
+const READ_TIMEOUT: Duration = Duration::from_millis(500);
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Addresses last entered in the Connection menu
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NetworkSettings {
+    pub tcp_host: String,
+    pub tcp_port: u16,
+    pub server_port: u16,
+    pub udp_port: u16,
+}
+
+impl Default for NetworkSettings {
+    fn default() -> Self {
+        Self {
+            tcp_host: "raspberrypi.local".to_owned(),
+            tcp_port: 5000,
+            server_port: 5000,
+            udp_port: 5000,
+        }
+    }
+}
+
+fn resolve(address: &str) -> io::Result<SocketAddr> {
+    address.to_socket_addrs()?
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "address could not be resolved"))
+}
+
+fn tcp_connection(stream: TcpStream) -> io::Result<Connection> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+
+    Ok(Connection {
+        writer: Some(Box::new(stream.try_clone()?)),
+        reader: Box::new(BufReader::new(stream)),
+    })
+}
+
+/// Connects to a TCP server sending telemetry lines. The address is resolved anew on every
+/// attempt, which the reader thread makes, so a slow lookup doesn't hold up the caller.
+/// The connection is reestablished whenever it's closed.
+pub fn connect_tcp(address: String) -> Reconnect {
+    Box::new(move || tcp_connection(TcpStream::connect_timeout(&resolve(&address)?, CONNECT_TIMEOUT)?))
+}
+
+/// Waits for a client to connect and send telemetry lines. One client
+/// is served at a time; after it disconnects, the next one is accepted.
+pub fn listen_tcp(port: u16) -> io::Result<Reconnect> {
+    let listener = TcpListener::bind(("0.0.0.0", port))?;
+    listener.set_nonblocking(true)?;
+
+    Ok(Box::new(move || {
+        let (stream, _) = listener.accept()?;
+        tcp_connection(stream)
+    }))
+}
+
+/// Listens for UDP datagrams, each holding one or more telemetry lines.
+/// Commands are sent back to whoever sent the last datagram.
+pub fn listen_udp(port: u16) -> io::Result<Connection> {
+    udp_connection(UdpSocket::bind(("0.0.0.0", port))?)
+}
+
+fn udp_connection(socket: UdpSocket) -> io::Result<Connection> {
+    socket.set_read_timeout(Some(READ_TIMEOUT))?;
+    let peer = Arc::new(Mutex::new(None));
+
+    Ok(Connection {
+        writer: Some(Box::new(DatagramWriter { socket: socket.try_clone()?, peer: peer.clone() }) as LineWriter),
+        reader: Box::new(BufReader::new(DatagramReader { socket, peer, datagram: vec![], position: 0 })),
+    })
+}
+
+/// Presents recieved datagrams as a stream of lines
+struct DatagramReader {
+    socket: UdpSocket,
+    /// Where the last datagram came from
+    peer: Arc<Mutex<Option<SocketAddr>>>,
+    datagram: Vec<u8>,
+    position: usize,
+}
+
+/// Sends each write as a datagram to the last sender
+struct DatagramWriter {
+    socket: UdpSocket,
+    peer: Arc<Mutex<Option<SocketAddr>>>,
+}
+
+impl Write for DatagramWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let peer = self.peer.lock().unwrap()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotConnected, "nothing has been recieved yet"))?;
+
+        self.socket.send_to(buf, peer)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Read for DatagramReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.position >= self.datagram.len() {
+            let mut datagram = vec![0; 65536];
+            let (length, peer) = self.socket.recv_from(&mut datagram)?;
+            datagram.truncate(length);
+            *self.peer.lock().unwrap() = Some(peer);
+
+            // A datagram is a complete message even if the sender didn't end it with a newline
+            if !datagram.ends_with(b"\n") {
+                datagram.push(b'\n');
+            }
+
+            self.datagram = datagram;
+            self.position = 0;
+        }
+
+        let length = buf.len().min(self.datagram.len() - self.position);
+        buf[..length].copy_from_slice(&self.datagram[self.position..self.position + length]);
+        self.position += length;
+
+        Ok(length)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::BufRead, thread};
+
+    use super::*;
+
+    #[test]
+    fn test_tcp_client() {
+        let server = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = server.local_addr().unwrap();
+
+        let server = thread::spawn(move || {
+            let (mut stream, _) = server.accept().unwrap();
+            stream.write_all(b"first\nsecond\n").unwrap();
+
+            let mut command = String::new();
+            BufReader::new(stream).read_line(&mut command).unwrap();
+            command
+        });
+
+        let mut connection = connect_tcp(address.to_string())().unwrap();
+        let mut line = String::new();
+        connection.reader.read_line(&mut line).unwrap();
+        connection.reader.read_line(&mut line).unwrap();
+        assert_eq!(line, "first\nsecond\n");
+
+        connection.writer.unwrap().write_all(b"ARM\n").unwrap();
+        assert_eq!(server.join().unwrap(), "ARM\n");
+    }
+
+    #[test]
+    fn test_udp_datagrams_become_lines() {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let address = socket.local_addr().unwrap();
+        let Connection { mut reader, writer } = udp_connection(socket).unwrap();
+
+        let sender = UdpSocket::bind("127.0.0.1:0").unwrap();
+        sender.send_to(b"first", address).unwrap();
+        sender.send_to(b"second\nthird\n", address).unwrap();
+
+        let mut lines = vec![];
+        for _ in 0..3 {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            lines.push(line);
+        }
+        assert_eq!(lines, ["first\n", "second\n", "third\n"]);
+
+        // Replies go back to the sender
+        writer.unwrap().write_all(b"ARM\n").unwrap();
+        let mut reply = [0; 16];
+        let length = sender.recv(&mut reply).unwrap();
+        assert_eq!(&reply[..length], b"ARM\n");
+    }
+}
@@ -0,0 +1,101 @@
+use std::{fs, io, path::{Path, PathBuf}};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{altitude::AltitudeSettings, data::Segmentation, tabs::{data::DataTabState, gaps::GapsTabState, map::MapSettings, plot::PlotTabState}};
+
+/// Everything needed to pick the analysis of a flight back up: the logs that
+/// were open, which sessions were shown and how the tabs were set up
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct Project {
+    pub logs: Vec<ProjectLog>,
+    /// Telemetry schema file the logs were read with, if not the default one
+    pub schema: Option<PathBuf>,
+    pub segmentation: Segmentation,
+    pub altitude: AltitudeSettings,
+    pub plot: PlotTabState,
+    pub map: MapSettings,
+    pub data: DataTabState,
+    pub gaps: GapsTabState,
+}
+
+/// A log file that was open as a source
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProjectLog {
+    pub path: PathBuf,
+    pub name: String,
+    pub color: [u8; 3],
+    /// Whether the log was being replayed rather than shown all at once
+    pub replay: bool,
+    pub session: usize,
+}
+
+impl Default for Project {
+    fn default() -> Self {
+        Self {
+            logs: vec![],
+            schema: None,
+            segmentation: Segmentation::default(),
+            altitude: AltitudeSettings::default(),
+            plot: PlotTabState::default(),
+            map: MapSettings::default(),
+            data: DataTabState { stick_to_bottom: true },
+            gaps: GapsTabState::default(),
+        }
+    }
+}
+
+impl Project {
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let text = serde_json::to_string_pretty(self).map_err(io::Error::other)?;
+        fs::write(path, text)
+    }
+
+    pub fn load(path: &Path) -> io::Result<Project> {
+        let text = fs::read_to_string(path)?;
+        serde_json::from_str(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+/// Finds a file referenced by a project. Files that were moved together with the
+/// project are looked up next to it.
+pub fn resolve_path(project: &Path, path: &Path) -> PathBuf {
+    if path.exists() {
+        return path.to_path_buf();
+    }
+
+    match (project.parent(), path.file_name()) {
+        (Some(dir), Some(name)) if dir.join(name).exists() => dir.join(name),
+        _ => path.to_path_buf(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_save_and_load() {
+        let dir = std::env::temp_dir().join(format!("gs_viewer_project_test_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("flight.gsproj");
+
+        let project = Project {
+            logs: vec![ProjectLog { path: "/missing/flight.log".into(), name: "flight.log".to_owned(), color: [1, 2, 3], replay: false, session: 2 }],
+            segmentation: Segmentation { reorder_window: 4, ..Default::default() },
+            ..Default::default()
+        };
+        project.save(&path).unwrap();
+
+        let loaded = Project::load(&path).unwrap();
+        assert_eq!(loaded.logs, project.logs);
+        assert_eq!(loaded.segmentation, project.segmentation);
+
+        // A log moved next to the project is found there
+        fs::write(dir.join("flight.log"), "").unwrap();
+        assert_eq!(resolve_path(&path, &loaded.logs[0].path), dir.join("flight.log"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
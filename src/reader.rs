@@ -0,0 +1,306 @@
+use std::{fmt, io::{self, BufRead, Write}, sync::{atomic::{AtomicBool, Ordering}, Arc, Mutex}, thread, time::Duration};
+
+use chrono::{DateTime, Local};
+use log::{info, warn};
+
+use crate::{capture::CaptureWriter, data::MissionData};
+
+pub type LineReader = Box<dyn BufRead + Send>;
+pub type LineWriter = Box<dyn Write + Send>;
+
+/// An open connection to the ground station
+pub struct Connection {
+    pub reader: LineReader,
+    /// Sends commands up to the probe, if the connection allows it
+    pub writer: Option<LineWriter>,
+}
+
+/// Opens the connection again after it was lost
+pub type Reconnect = Box<dyn FnMut() -> io::Result<Connection> + Send>;
+
+/// How long to wait between attempts to reconnect
+const RECONNECT_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Controls a thread spawned by [`spawn_data_reader_thread`]
+#[derive(Clone)]
+pub struct ReaderHandle {
+    cancel: Arc<AtomicBool>,
+    connected: Arc<AtomicBool>,
+    uplink: Arc<Mutex<Option<LineWriter>>>,
+    replies: Arc<Mutex<ReplyChannel>>,
+}
+
+/// A line recieved in reply to a command
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reply {
+    pub text: String,
+    pub recieved: DateTime<Local>,
+}
+
+/// Replies to commands, kept apart from the telemetry so that they don't count as rejected lines
+#[derive(Debug, Default)]
+struct ReplyChannel {
+    /// Text a reply must contain, and until when it is waited for
+    awaited: Vec<(String, DateTime<Local>)>,
+    recieved: Vec<Reply>,
+}
+
+impl fmt::Debug for ReaderHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReaderHandle")
+            .field("cancel", &self.cancel)
+            .field("connected", &self.connected)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ReaderHandle {
+    pub fn cancel(&self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether the connection is up. While it's down, the thread keeps trying to reconnect.
+    pub fn is_connected(&self) -> bool {
+        self.connected.load(Ordering::Relaxed)
+    }
+
+    /// Whether commands can be sent over the connection right now
+    pub fn can_send(&self) -> bool {
+        self.uplink.lock().unwrap().is_some()
+    }
+
+    /// Writes a line to the connection, adding the line ending
+    pub fn send(&self, line: &str) -> io::Result<()> {
+        let mut uplink = self.uplink.lock().unwrap();
+        let writer = uplink.as_mut()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotConnected, "the connection can't send commands right now"))?;
+
+        writer.write_all(format!("{line}\n").as_bytes())?;
+        writer.flush()
+    }
+
+    /// Until `deadline`, the next line containing `text` is a reply, handed to
+    /// [`ReaderHandle::take_replies`] rather than parsed as a frame
+    pub fn await_reply(&self, text: &str, deadline: DateTime<Local>) {
+        self.replies.lock().unwrap().awaited.push((text.to_owned(), deadline));
+    }
+
+    /// Stops waiting for a reply awaited with [`ReaderHandle::await_reply`]
+    pub fn cancel_reply(&self, text: &str, deadline: DateTime<Local>) {
+        let mut replies = self.replies.lock().unwrap();
+        if let Some(i) = replies.awaited.iter().position(|(t, d)| t == text && *d == deadline) {
+            replies.awaited.remove(i);
+        }
+    }
+
+    /// Replies recieved since the last call
+    pub fn take_replies(&self) -> Vec<Reply> {
+        std::mem::take(&mut self.replies.lock().unwrap().recieved)
+    }
+
+    /// Keeps the line as a reply if one containing it is awaited
+    fn accept_reply(&self, line: &str) -> bool {
+        let mut replies = self.replies.lock().unwrap();
+        let now = Local::now();
+
+        replies.awaited.retain(|(_, deadline)| *deadline >= now);
+        let Some(i) = replies.awaited.iter().position(|(text, _)| line.contains(text.as_str())) else {
+            return false;
+        };
+
+        replies.awaited.remove(i);
+        replies.recieved.push(Reply { text: line.trim_end().to_owned(), recieved: now });
+        true
+    }
+}
+
+/// Reads lines from `connection` into `data` until cancelled. When the connection
+/// fails or reaches its end, or there is no `connection` to begin with, `reconnect`
+/// is retried until it succeeds, and reading continues into the same data.
+pub fn spawn_data_reader_thread(
+    connection: Option<Connection>,
+    mut reconnect: Option<Reconnect>,
+    data: Arc<Mutex<MissionData>>,
+    mut capture: Option<CaptureWriter>
+) -> ReaderHandle {
+    let (reader, writer) = connection.map(|c| (c.reader, c.writer)).unzip();
+    let handle = ReaderHandle {
+        cancel: Arc::new(AtomicBool::new(false)),
+        connected: Arc::new(AtomicBool::new(reader.is_some())),
+        uplink: Arc::new(Mutex::new(writer.flatten())),
+        replies: Arc::default(),
+    };
+    let thread_handle = handle.clone();
+
+    thread::spawn(move || {
+        info!("Data reader thread spawned");
+        let handle = thread_handle;
+        let mut reader = reader;
+        let mut buffer = vec![];
+
+        while !handle.cancel.load(Ordering::Relaxed) {
+            let Some(current) = &mut reader else {
+                let Some(reconnect) = &mut reconnect else {
+                    info!("Connection lost and can't be reopened; ending thread.");
+                    return;
+                };
+
+                thread::sleep(RECONNECT_INTERVAL);
+                match reconnect() {
+                    Ok(new) => {
+                        info!("Reconnected");
+                        reader = Some(new.reader);
+                        *handle.uplink.lock().unwrap() = new.writer;
+                        handle.connected.store(true, Ordering::Relaxed);
+                    },
+                    // A listener with no client yet, tried again quietly
+                    Err(e) if e.kind() == io::ErrorKind::WouldBlock => {},
+                    Err(e) => info!("Reconnecting failed: {e}"),
+                }
+                continue;
+            };
+
+            // Radio noise is not guaranteed to be valid UTF-8, so it is decoded lossily
+            // and left for the parser to reject
+            match current.read_until(b'\n', &mut buffer) {
+                Ok(0) => {
+                    warn!("Connection reached its end");
+                    reader = None;
+                },
+                // A timeout just means nothing was sent; a partial line stays in the buffer.
+                // No more reordered frames are expected, so the held back ones are released.
+                Err(e) if matches!(e.kind(), io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock) => {
+                    data.lock().unwrap().flush_reorder_buffer();
+                    continue;
+                },
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    warn!("Connection failed: {e}");
+                    reader = None;
+                },
+                Ok(_) if !buffer.ends_with(b"\n") => continue,
+                Ok(_) => {},
+            }
+
+            if !buffer.is_empty() {
+                if let Some(writer) = &mut capture {
+                    if let Err(e) = writer.write_line(&buffer) {
+                        warn!("Failed to write to the capture file: {e}");
+                    }
+                }
+
+                let line = String::from_utf8_lossy(&buffer);
+                if !handle.accept_reply(&line) {
+                    // Lines that fail to parse are recorded by MissionData itself
+                    let _ = data.lock().unwrap().parse_line(&line);
+                }
+                buffer.clear();
+            }
+
+            if reader.is_none() {
+                data.lock().unwrap().flush_reorder_buffer();
+                *handle.uplink.lock().unwrap() = None;
+                handle.connected.store(false, Ordering::Relaxed);
+            }
+        }
+
+        info!("Cancel order detected; ending thread.");
+    });
+
+    handle
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Cursor, time::Instant};
+
+    use super::*;
+
+    const FRAME: &str = "0\t1\t1\t2\t3\t4\t4\t4\t0\t6\t6\t6\t0\t8\t9\t9\t10";
+
+    /// A connection on which nothing is being sent
+    struct Idle;
+
+    impl io::Read for Idle {
+        fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+            thread::sleep(Duration::from_millis(10));
+            Err(io::ErrorKind::TimedOut.into())
+        }
+    }
+
+    fn wait_for(condition: impl Fn() -> bool) -> bool {
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_secs(5) {
+            if condition() {
+                return true;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        false
+    }
+
+    #[test]
+    fn test_reconnect_continues_session() {
+        let data = Arc::new(Mutex::new(MissionData::default()));
+        let first = Connection { reader: Box::new(Cursor::new(format!("{FRAME}\n").replacen('0', "1", 1))), writer: None };
+
+        let mut attempts = 0;
+        let reconnect: Reconnect = Box::new(move || {
+            attempts += 1;
+            if attempts < 2 {
+                return Err(io::Error::new(io::ErrorKind::NotFound, "unplugged"));
+            }
+            Ok(Connection {
+                reader: Box::new(io::BufReader::new(io::Read::chain(Cursor::new(format!("{FRAME}\n").replacen('0', "2", 1)), Idle))),
+                writer: Some(Box::new(io::sink())),
+            })
+        });
+
+        let handle = spawn_data_reader_thread(Some(first), Some(reconnect), data.clone(), None);
+
+        assert!(wait_for(|| data.lock().unwrap().sessions().first().is_some_and(|s| s.len() == 2)));
+        assert!(handle.is_connected());
+        assert!(handle.send("ARM").is_ok());
+        handle.cancel();
+
+        let data = data.lock().unwrap();
+        assert_eq!(data.sessions().len(), 1);
+        assert_eq!(data.sessions()[0][1].index, 2);
+    }
+
+    #[test]
+    fn test_disconnect_without_reconnect() {
+        let data = Arc::new(Mutex::new(MissionData::default()));
+        let handle = spawn_data_reader_thread(Some(Connection { reader: Box::new(Cursor::new(FRAME)), writer: None }), None, data.clone(), None);
+
+        assert!(wait_for(|| !handle.is_connected()));
+        assert!(handle.send("ARM").is_err());
+        // The last line is kept even though it wasn't terminated
+        assert_eq!(data.lock().unwrap().sessions()[0].len(), 1);
+    }
+
+    #[test]
+    fn test_replies_kept_apart() {
+        let data = Arc::new(Mutex::new(MissionData::default()));
+        let reconnect: Reconnect = Box::new(|| Ok(Connection {
+            reader: Box::new(io::BufReader::new(io::Read::chain(Cursor::new("ACK ARM ok\ngarbage\nACK ARM\n"), Idle))),
+            writer: Some(Box::new(io::sink())),
+        }));
+
+        // The first connection is made after the reply is awaited
+        let handle = spawn_data_reader_thread(None, Some(reconnect), data.clone(), None);
+        let deadline = Local::now() + chrono::TimeDelta::seconds(10);
+        handle.await_reply("garbage", deadline);
+        handle.cancel_reply("garbage", deadline);
+        handle.await_reply("ACK ARM", deadline);
+
+        // A reply is taken once, so the second one counts as rejected, as does the line no longer awaited
+        assert!(wait_for(|| data.lock().unwrap().rejected_count() == 2));
+        handle.cancel();
+
+        let replies = handle.take_replies();
+        assert_eq!(replies.iter().map(|r| r.text.as_str()).collect::<Vec<_>>(), ["ACK ARM ok"]);
+        assert!(handle.take_replies().is_empty());
+        assert_eq!(data.lock().unwrap().rejected_lines()[1].text, "ACK ARM");
+    }
+}
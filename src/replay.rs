@@ -0,0 +1,228 @@
+use std::{sync::Arc, time::Instant};
+
+use crate::{capture::strip_host_timestamp, data::{parse_log_line, MissionData, Segmentation}, schema::TelemetrySchema};
+
+/// Playback speeds offered in the status bar
+pub const REPLAY_SPEEDS: [f64; 9] = [0.25, 0.5, 1.0, 2.0, 5.0, 10.0, 20.0, 30.0, 50.0];
+pub const MIN_REPLAY_SPEED: f64 = 0.25;
+pub const MAX_REPLAY_SPEED: f64 = 50.0;
+
+/// Feeds the lines of a log into [`MissionData`] as if they were recieved
+/// live, paced by the recorded uptime of the frames
+#[derive(Debug, Clone)]
+pub struct Replay {
+    lines: Vec<String>,
+    /// When each line is fed, in seconds from the start of the replay
+    times: Vec<f64>,
+    /// Number of lines fed so far
+    position: usize,
+
+    time: f64,
+    playing: bool,
+    speed: f64,
+    last_tick: Option<Instant>,
+
+    schema: Arc<TelemetrySchema>,
+    data: MissionData,
+}
+
+impl Replay {
+    pub fn new(text: &str, schema: Arc<TelemetrySchema>) -> Self {
+        let lines: Vec<String> = text.lines().map(|line| line.to_owned()).collect();
+
+        let mut times = Vec::with_capacity(lines.len());
+        let mut time = 0.0;
+        let mut last_timestamp = None;
+
+        for line in &lines {
+            let (_, frame) = strip_host_timestamp(line);
+
+            if let Ok(frame) = parse_log_line(&schema, frame) {
+                let timestamp = frame.timestamp();
+
+                // A new session restarts the uptime, so it follows the previous one immediately
+                if let Some(last) = last_timestamp.filter(|last| timestamp >= *last) {
+                    time += (timestamp - last) as f64 / 1_000_000.0;
+                }
+                last_timestamp = Some(timestamp);
+            }
+
+            // Lines without a valid frame are fed along with the frame before them
+            times.push(time);
+        }
+
+        Self {
+            lines,
+            times,
+            position: 0,
+            time: 0.0,
+            playing: true,
+            speed: 1.0,
+            last_tick: None,
+            data: MissionData::with_schema(schema.clone()),
+            schema,
+        }
+    }
+
+    pub fn data(&self) -> &MissionData {
+        &self.data
+    }
+
+    /// Splits what was replayed so far, and the rest of the log, with different rules
+    pub fn set_segmentation(&mut self, segmentation: Segmentation) {
+        self.data.resegment(segmentation);
+    }
+
+    /// Length of the replay in seconds
+    pub fn duration(&self) -> f64 {
+        self.times.last().copied().unwrap_or(0.0)
+    }
+
+    /// Current position in seconds
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.position >= self.lines.len()
+    }
+
+    pub fn speed(&self) -> f64 {
+        self.speed
+    }
+
+    pub fn set_speed(&mut self, speed: f64) {
+        self.speed = speed.clamp(MIN_REPLAY_SPEED, MAX_REPLAY_SPEED);
+    }
+
+    pub fn play(&mut self) {
+        // Playing a finished replay starts it over
+        if self.is_finished() {
+            self.seek(0.0);
+        }
+        self.playing = true;
+    }
+
+    pub fn pause(&mut self) {
+        self.playing = false;
+        self.last_tick = None;
+    }
+
+    /// Advances playback by the time elapsed since the previous tick. Called every frame.
+    pub fn tick(&mut self, now: Instant) {
+        if !self.playing {
+            return;
+        }
+
+        if let Some(last_tick) = self.last_tick {
+            let elapsed = now.saturating_duration_since(last_tick).as_secs_f64();
+            self.advance_to(self.time + elapsed * self.speed);
+        } else {
+            // Lines at the very start are fed right away
+            self.advance_to(self.time);
+        }
+        self.last_tick = Some(now);
+
+        if self.is_finished() {
+            self.pause();
+        }
+    }
+
+    /// Moves to the given position. Seeking backwards replays the log from the start.
+    pub fn seek(&mut self, time: f64) {
+        let time = time.clamp(0.0, self.duration());
+
+        if time < self.time {
+            self.data = MissionData::with_schema(self.schema.clone()).with_segmentation(self.data.segmentation());
+            self.position = 0;
+        }
+
+        self.advance_to(time);
+    }
+
+    fn advance_to(&mut self, time: f64) {
+        let time = time.min(self.duration());
+
+        while self.position < self.lines.len() && self.times[self.position] <= time {
+            // Lines that fail to parse are recorded by MissionData itself
+            let _ = self.data.parse_line(&self.lines[self.position]);
+            self.position += 1;
+        }
+        if self.position == self.lines.len() {
+            self.data.flush_reorder_buffer();
+        }
+
+        self.time = time;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::data::test_frame;
+
+    fn log() -> String {
+        [test_frame(0, 1000), test_frame(1, 2000), "garbage".to_owned(), test_frame(2, 4000), test_frame(0, 500), test_frame(1, 1500)].join("\n")
+    }
+
+    #[test]
+    fn test_timeline() {
+        let replay = Replay::new(&log(), Arc::default());
+        assert_eq!(replay.times, [0.0, 1.0, 1.0, 3.0, 3.0, 4.0]);
+        assert_eq!(replay.duration(), 4.0);
+    }
+
+    #[test]
+    fn test_paced_playback() {
+        let mut replay = Replay::new(&log(), Arc::default());
+        let start = Instant::now();
+
+        replay.tick(start);
+        assert_eq!(replay.data().sessions()[0].len(), 1);
+
+        replay.set_speed(2.0);
+        replay.tick(start + Duration::from_millis(600));
+        assert_eq!(replay.data().sessions()[0].len(), 2);
+        assert_eq!(replay.data().rejected_count(), 1);
+
+        replay.pause();
+        replay.tick(start + Duration::from_secs(100));
+        assert_eq!(replay.data().sessions()[0].len(), 2);
+
+        replay.play();
+        replay.tick(start + Duration::from_secs(101));
+        replay.tick(start + Duration::from_secs(103));
+        assert_eq!(replay.data().sessions().len(), 2);
+        assert!(replay.is_finished());
+        assert!(!replay.is_playing());
+    }
+
+    #[test]
+    fn test_seek() {
+        let mut replay = Replay::new(&log(), Arc::default());
+
+        replay.seek(3.5);
+        assert_eq!(replay.data().sessions().len(), 2);
+        assert_eq!(replay.data().sessions()[1].len(), 1);
+
+        replay.seek(1.0);
+        assert_eq!(replay.data().sessions().len(), 1);
+        assert_eq!(replay.data().sessions()[0].len(), 2);
+        assert_eq!(replay.data().rejected_count(), 1);
+    }
+
+    #[test]
+    fn test_speed_is_clamped() {
+        let mut replay = Replay::new(&log(), Arc::default());
+        replay.set_speed(1000.0);
+        assert_eq!(replay.speed(), MAX_REPLAY_SPEED);
+        replay.set_speed(0.0);
+        assert_eq!(replay.speed(), MIN_REPLAY_SPEED);
+    }
+}
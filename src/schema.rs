@@ -0,0 +1,333 @@
+use crate::{checksum::ChecksumKind, data::{ReadConfidence, SensedData}};
+
+/// Layout the viewer expects when no schema file has been loaded
+pub const DEFAULT_SCHEMA: &str = include_str!("../assets/default_schema.txt");
+
+/// Describes the order and meaning of the columns of a telemetry frame
+#[derive(Debug, Clone, PartialEq)]
+pub struct TelemetrySchema {
+    fields: Vec<SchemaField>,
+    extra_count: usize,
+    /// Checksum every frame must carry. Without it, checksums are only verified when present.
+    checksum: Option<ChecksumKind>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SchemaField {
+    pub name: String,
+    pub field_type: FieldType,
+    pub unit: String,
+    pub optional: bool,
+    pub target: FieldTarget,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FieldType {
+    Integer,
+    Float,
+    Confidence,
+}
+
+/// Where the value of a column ends up in [`SensedData`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FieldTarget {
+    Index,
+    Uptime,
+    Micros,
+    Temperature,
+    Pressure,
+    Acceleration(usize),
+    AccelerationConfidence,
+    Gyroscope(usize),
+    GyroscopeConfidence,
+    GpsTime,
+    GpsLatitude,
+    GpsLongitude,
+    GpsAltitude,
+    /// A column the viewer has no dedicated field for, stored in [`SensedData::extra`]
+    Extra(usize),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct SchemaError {
+    pub line: usize,
+    pub msg: String,
+}
+
+impl FieldTarget {
+    fn from_name(name: &str) -> Option<FieldTarget> {
+        Some(match name {
+            "index" => FieldTarget::Index,
+            "uptime" => FieldTarget::Uptime,
+            "micros" => FieldTarget::Micros,
+            "temperature" => FieldTarget::Temperature,
+            "pressure" => FieldTarget::Pressure,
+            "accel_x" => FieldTarget::Acceleration(0),
+            "accel_y" => FieldTarget::Acceleration(1),
+            "accel_z" => FieldTarget::Acceleration(2),
+            "accel_confidence" => FieldTarget::AccelerationConfidence,
+            "gyro_x" => FieldTarget::Gyroscope(0),
+            "gyro_y" => FieldTarget::Gyroscope(1),
+            "gyro_z" => FieldTarget::Gyroscope(2),
+            "gyro_confidence" => FieldTarget::GyroscopeConfidence,
+            "gps_time" => FieldTarget::GpsTime,
+            "gps_lat" => FieldTarget::GpsLatitude,
+            "gps_lon" => FieldTarget::GpsLongitude,
+            "gps_alt" => FieldTarget::GpsAltitude,
+            _ => return None,
+        })
+    }
+
+    /// Type the column must have to be stored in this target
+    fn expected_type(&self) -> Option<FieldType> {
+        match self {
+            FieldTarget::Index | FieldTarget::Uptime | FieldTarget::Micros | FieldTarget::GpsTime => Some(FieldType::Integer),
+            FieldTarget::AccelerationConfidence | FieldTarget::GyroscopeConfidence => Some(FieldType::Confidence),
+            FieldTarget::Extra(_) => None,
+            _ => Some(FieldType::Float),
+        }
+    }
+}
+
+impl SchemaField {
+    /// Human readable name of the column
+    pub fn label(&self) -> String {
+        let label = match self.target {
+            FieldTarget::Index => "Index",
+            FieldTarget::Uptime => "Uptime",
+            FieldTarget::Micros => "Micros",
+            FieldTarget::Temperature => "Temperature",
+            FieldTarget::Pressure => "Pressure",
+            FieldTarget::Acceleration(0) => "Acceleration X",
+            FieldTarget::Acceleration(1) => "Acceleration Y",
+            FieldTarget::Acceleration(_) => "Acceleration Z",
+            FieldTarget::AccelerationConfidence => "Accel confidence",
+            FieldTarget::Gyroscope(0) => "Gyroscope X",
+            FieldTarget::Gyroscope(1) => "Gyroscope Y",
+            FieldTarget::Gyroscope(_) => "Gyroscope Z",
+            FieldTarget::GyroscopeConfidence => "Gyro confidence",
+            FieldTarget::GpsTime => "GPS Time",
+            FieldTarget::GpsLatitude => "GPS Latitude",
+            FieldTarget::GpsLongitude => "GPS Longitude",
+            FieldTarget::GpsAltitude => "GPS Altitude",
+            FieldTarget::Extra(_) => return self.name.clone(),
+        };
+        label.to_owned()
+    }
+
+    /// Label followed by the unit, if the column has one
+    pub fn label_with_unit(&self) -> String {
+        if self.unit.is_empty() {
+            self.label()
+        } else {
+            format!("{} [{}]", self.label(), self.unit)
+        }
+    }
+
+    /// Reads the value of this column back from a record
+    pub fn value(&self, data: &SensedData) -> f64 {
+        match self.target {
+            FieldTarget::Index => data.index as f64,
+            FieldTarget::Uptime => data.uptime as f64,
+            FieldTarget::Micros => data.micros as f64,
+            FieldTarget::Temperature => data.temperature as f64,
+            FieldTarget::Pressure => data.pressure as f64,
+            FieldTarget::Acceleration(i) => data.acceleration[i],
+            FieldTarget::AccelerationConfidence => data.acceleration_confidence as u8 as f64,
+            FieldTarget::Gyroscope(i) => data.gyroscope[i],
+            FieldTarget::GyroscopeConfidence => data.gyroscope_confidence as u8 as f64,
+            FieldTarget::GpsTime => data.gps_time as f64,
+            FieldTarget::GpsLatitude => data.gps_position[0],
+            FieldTarget::GpsLongitude => data.gps_position[1],
+            FieldTarget::GpsAltitude => data.gps_altitude,
+            FieldTarget::Extra(i) => data.extra.get(i).copied().unwrap_or(f64::NAN),
+        }
+    }
+
+    /// Formats the value of this column for display
+    pub fn format(&self, data: &SensedData) -> String {
+        match self.target {
+            FieldTarget::AccelerationConfidence => format!("{:?}", data.acceleration_confidence),
+            FieldTarget::GyroscopeConfidence => format!("{:?}", data.gyroscope_confidence),
+            _ => format!("{}", self.value(data)),
+        }
+    }
+
+    /// Stores a parsed value of this column in a record
+    pub(crate) fn store(&self, data: &mut SensedData, value: FieldValue) {
+        match (self.target, value) {
+            (FieldTarget::Index, FieldValue::Integer(v)) => data.index = v,
+            (FieldTarget::Uptime, FieldValue::Integer(v)) => data.uptime = v,
+            (FieldTarget::Micros, FieldValue::Integer(v)) => data.micros = v,
+            (FieldTarget::GpsTime, FieldValue::Integer(v)) => data.gps_time = v,
+            (FieldTarget::Temperature, FieldValue::Float(v)) => data.temperature = v as f32,
+            (FieldTarget::Pressure, FieldValue::Float(v)) => data.pressure = v as f32,
+            (FieldTarget::Acceleration(i), FieldValue::Float(v)) => data.acceleration[i] = v,
+            (FieldTarget::Gyroscope(i), FieldValue::Float(v)) => data.gyroscope[i] = v,
+            (FieldTarget::GpsLatitude, FieldValue::Float(v)) => data.gps_position[0] = v,
+            (FieldTarget::GpsLongitude, FieldValue::Float(v)) => data.gps_position[1] = v,
+            (FieldTarget::GpsAltitude, FieldValue::Float(v)) => data.gps_altitude = v,
+            (FieldTarget::AccelerationConfidence, FieldValue::Confidence(v)) => data.acceleration_confidence = v,
+            (FieldTarget::GyroscopeConfidence, FieldValue::Confidence(v)) => data.gyroscope_confidence = v,
+            (FieldTarget::Extra(i), value) => {
+                if data.extra.len() <= i {
+                    data.extra.resize(i + 1, f64::NAN);
+                }
+                data.extra[i] = match value {
+                    FieldValue::Integer(v) => v as f64,
+                    FieldValue::Float(v) => v,
+                    FieldValue::Confidence(v) => v as u8 as f64,
+                };
+            }
+            // The schema parser guarantees types match their targets
+            _ => unreachable!("value type does not match the schema field"),
+        }
+    }
+}
+
+/// A single parsed column value
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum FieldValue {
+    Integer(u32),
+    Float(f64),
+    Confidence(ReadConfidence),
+}
+
+impl TelemetrySchema {
+    /// Parses a schema file. Each non-empty line that isn't a `#` comment describes
+    /// one column as `<name> <type> [unit] [optional]`, except for a
+    /// `checksum <xor|crc16>` line requiring a trailing checksum on every frame.
+    pub fn parse(text: &str) -> Result<TelemetrySchema, SchemaError> {
+        let mut fields: Vec<SchemaField> = vec![];
+        let mut extra_count = 0;
+        let mut checksum = None;
+
+        for (i, line) in text.lines().enumerate() {
+            let line_number = i + 1;
+            let error = |msg: String| SchemaError { line: line_number, msg };
+
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let mut tokens = line.split_whitespace();
+            let name = tokens.next().expect("line should not be empty").to_owned();
+
+            if name == "checksum" {
+                let kind = tokens.next().unwrap_or_default();
+                checksum = Some(ChecksumKind::from_name(kind)
+                    .ok_or_else(|| error(format!("Unknown checksum \"{kind}\"")))?);
+                continue;
+            }
+
+            let field_type = match tokens.next() {
+                Some("int") => FieldType::Integer,
+                Some("float") => FieldType::Float,
+                Some("confidence") => FieldType::Confidence,
+                Some(other) => return Err(error(format!("Unknown type \"{other}\""))),
+                None => return Err(error(format!("Missing type of \"{name}\""))),
+            };
+
+            let mut unit = String::new();
+            let mut optional = false;
+            for token in tokens {
+                if token == "optional" {
+                    optional = true;
+                } else if unit.is_empty() {
+                    unit = token.to_owned();
+                } else {
+                    return Err(error(format!("Unexpected \"{token}\"")));
+                }
+            }
+
+            if fields.iter().any(|f| f.name == name) {
+                return Err(error(format!("Duplicate field \"{name}\"")));
+            }
+
+            let target = match FieldTarget::from_name(&name) {
+                Some(target) => target,
+                None => {
+                    extra_count += 1;
+                    FieldTarget::Extra(extra_count - 1)
+                }
+            };
+
+            if let Some(expected) = target.expected_type() {
+                if expected != field_type {
+                    return Err(error(format!("\"{name}\" must be of type {expected:?}")));
+                }
+            }
+
+            fields.push(SchemaField { name, field_type, unit, optional, target });
+        }
+
+        if !fields.iter().any(|f| f.target == FieldTarget::Index && !f.optional) {
+            return Err(SchemaError { line: 0, msg: "The schema needs a required \"index\" field".to_owned() });
+        }
+
+        Ok(TelemetrySchema { fields, extra_count, checksum })
+    }
+
+    pub fn fields(&self) -> &[SchemaField] {
+        &self.fields
+    }
+
+    pub fn field(&self, target: FieldTarget) -> Option<&SchemaField> {
+        self.fields.iter().find(|f| f.target == target)
+    }
+
+    /// Number of columns stored in [`SensedData::extra`]
+    pub fn extra_count(&self) -> usize {
+        self.extra_count
+    }
+
+    pub fn checksum(&self) -> Option<ChecksumKind> {
+        self.checksum
+    }
+}
+
+impl Default for TelemetrySchema {
+    fn default() -> Self {
+        TelemetrySchema::parse(DEFAULT_SCHEMA).expect("default schema should be valid")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_schema() {
+        let schema = TelemetrySchema::default();
+        assert_eq!(schema.fields().len(), 17);
+        assert_eq!(schema.extra_count(), 0);
+        assert_eq!(schema.fields()[0].target, FieldTarget::Index);
+        assert_eq!(schema.fields()[4].unit, "Pa");
+        assert_eq!(schema.checksum(), None);
+    }
+
+    #[test]
+    fn test_parse_schema() {
+        let schema = TelemetrySchema::parse("# comment\nuptime int ms\n\nindex int\nhumidity float % optional\nchecksum crc16\n").unwrap();
+        assert_eq!(schema.fields().len(), 3);
+        assert_eq!(schema.checksum(), Some(ChecksumKind::Crc16));
+        assert_eq!(schema.fields()[0].target, FieldTarget::Uptime);
+        assert_eq!(schema.fields()[2], SchemaField {
+            name: "humidity".to_owned(),
+            field_type: FieldType::Float,
+            unit: "%".to_owned(),
+            optional: true,
+            target: FieldTarget::Extra(0),
+        });
+    }
+
+    #[test]
+    fn test_parse_schema_errors() {
+        assert_eq!(TelemetrySchema::parse("index int\nuptime string").unwrap_err().line, 2);
+        assert_eq!(TelemetrySchema::parse("index float").unwrap_err().line, 1);
+        assert_eq!(TelemetrySchema::parse("index int\nindex int").unwrap_err().line, 2);
+        assert!(TelemetrySchema::parse("uptime int").is_err());
+        assert_eq!(TelemetrySchema::parse("index int\nchecksum md5").unwrap_err().line, 2);
+    }
+}
@@ -0,0 +1,181 @@
+use std::{io::{self, BufReader}, time::Duration};
+
+use egui::{ComboBox, DragValue, Ui};
+use serde::{Deserialize, Serialize};
+use serialport::{DataBits, FlowControl, Parity, SerialPort, SerialPortInfo, SerialPortType, StopBits};
+
+use crate::reader::{Connection, LineWriter, Reconnect};
+
+/// Baud rates offered in the settings dialog. Any other rate can still be typed in.
+const COMMON_BAUD_RATES: [u32; 8] = [9600, 19200, 38400, 57600, 115200, 230400, 460800, 921600];
+
+/// How to open a serial port
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SerialSettings {
+    pub baud_rate: u32,
+    pub data_bits: DataBits,
+    pub parity: Parity,
+    pub stop_bits: StopBits,
+    pub flow_control: FlowControl,
+    pub timeout_ms: u64,
+}
+
+impl Default for SerialSettings {
+    /// Settings of the primary ground station radio
+    fn default() -> Self {
+        Self {
+            baud_rate: 115200,
+            data_bits: DataBits::Eight,
+            parity: Parity::None,
+            stop_bits: StopBits::One,
+            flow_control: FlowControl::Hardware,
+            timeout_ms: 1000,
+        }
+    }
+}
+
+impl SerialSettings {
+    pub fn open(&self, port_name: &str) -> serialport::Result<Box<dyn SerialPort>> {
+        serialport::new(port_name, self.baud_rate)
+            .data_bits(self.data_bits)
+            .parity(self.parity)
+            .stop_bits(self.stop_bits)
+            .flow_control(self.flow_control)
+            .timeout(Duration::from_millis(self.timeout_ms))
+            .open()
+    }
+
+    /// Short description, e.g. "115200 8N1"
+    pub fn summary(&self) -> String {
+        let data_bits = match self.data_bits {
+            DataBits::Five => 5,
+            DataBits::Six => 6,
+            DataBits::Seven => 7,
+            DataBits::Eight => 8,
+        };
+        let parity = match self.parity {
+            Parity::None => 'N',
+            Parity::Odd => 'O',
+            Parity::Even => 'E',
+        };
+        let stop_bits = match self.stop_bits {
+            StopBits::One => 1,
+            StopBits::Two => 2,
+        };
+
+        format!("{} {data_bits}{parity}{stop_bits}", self.baud_rate)
+    }
+}
+
+/// Reads lines from an open port, and writes commands to a clone of its handle
+pub fn connection(port: Box<dyn SerialPort>) -> Connection {
+    let writer = port.try_clone().ok().map(|port| Box::new(port) as LineWriter);
+
+    Connection { reader: Box::new(BufReader::new(port)), writer }
+}
+
+/// Identifies a serial device across reconnections, as the system
+/// may give a USB device a different name when it's plugged back in
+#[derive(Debug, Clone, PartialEq)]
+pub struct PortIdentity {
+    pub port_name: String,
+    /// Vendor ID, product ID and serial number of a USB device
+    usb: Option<(u16, u16, Option<String>)>,
+}
+
+impl PortIdentity {
+    pub fn of(port: &SerialPortInfo) -> Self {
+        Self {
+            port_name: port.port_name.clone(),
+            usb: match &port.port_type {
+                SerialPortType::UsbPort(info) => Some((info.vid, info.pid, info.serial_number.clone())),
+                _ => None,
+            },
+        }
+    }
+
+    /// Finds the current name of the device among the available ports
+    pub fn find(&self) -> Option<String> {
+        let ports = serialport::available_ports().ok()?;
+
+        ports.iter()
+            .find(|port| match &self.usb {
+                Some(_) => PortIdentity::of(port).usb == self.usb,
+                None => port.port_name == self.port_name,
+            })
+            .map(|port| port.port_name.clone())
+    }
+
+    /// Creates a function reopening the device with the given settings
+    pub fn reconnector(self, settings: SerialSettings) -> Reconnect {
+        Box::new(move || {
+            let name = self.find()
+                .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "device is not connected"))?;
+            Ok(connection(settings.open(&name)?))
+        })
+    }
+}
+
+pub fn serial_settings_ui(ui: &mut Ui, settings: &mut SerialSettings) {
+    egui::Grid::new("serial_settings_grid").num_columns(2).show(ui, |ui| {
+        ui.label("Baud rate");
+        ui.horizontal(|ui| {
+            ComboBox::from_id_salt("baud_rate_combo_box")
+                .selected_text(settings.baud_rate.to_string())
+                .show_ui(ui, |ui| {
+                    for rate in COMMON_BAUD_RATES {
+                        ui.selectable_value(&mut settings.baud_rate, rate, rate.to_string());
+                    }
+                });
+            ui.add(DragValue::new(&mut settings.baud_rate).range(50..=4_000_000));
+        });
+        ui.end_row();
+
+        ui.label("Data bits");
+        ui.horizontal(|ui| {
+            ui.selectable_value(&mut settings.data_bits, DataBits::Five, "5");
+            ui.selectable_value(&mut settings.data_bits, DataBits::Six, "6");
+            ui.selectable_value(&mut settings.data_bits, DataBits::Seven, "7");
+            ui.selectable_value(&mut settings.data_bits, DataBits::Eight, "8");
+        });
+        ui.end_row();
+
+        ui.label("Parity");
+        ui.horizontal(|ui| {
+            ui.selectable_value(&mut settings.parity, Parity::None, "None");
+            ui.selectable_value(&mut settings.parity, Parity::Odd, "Odd");
+            ui.selectable_value(&mut settings.parity, Parity::Even, "Even");
+        });
+        ui.end_row();
+
+        ui.label("Stop bits");
+        ui.horizontal(|ui| {
+            ui.selectable_value(&mut settings.stop_bits, StopBits::One, "1");
+            ui.selectable_value(&mut settings.stop_bits, StopBits::Two, "2");
+        });
+        ui.end_row();
+
+        ui.label("Flow control");
+        ui.horizontal(|ui| {
+            ui.selectable_value(&mut settings.flow_control, FlowControl::None, "None");
+            ui.selectable_value(&mut settings.flow_control, FlowControl::Software, "XON/XOFF");
+            ui.selectable_value(&mut settings.flow_control, FlowControl::Hardware, "RTS/CTS");
+        });
+        ui.end_row();
+
+        ui.label("Timeout");
+        ui.add(DragValue::new(&mut settings.timeout_ms).range(1..=60_000).suffix(" ms"));
+        ui.end_row();
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summary() {
+        assert_eq!(SerialSettings::default().summary(), "115200 8N1");
+        assert_eq!(SerialSettings { baud_rate: 57600, parity: Parity::Even, ..Default::default() }.summary(), "57600 8E1");
+    }
+}
@@ -0,0 +1,351 @@
+use std::{f64::consts::PI, time::Instant};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::{checksum::ChecksumKind, data::{MissionData, ReadConfidence, SensedData}, schema::{FieldType, TelemetrySchema}};
+
+const GRAVITY: f64 = 9.81;
+const SEA_LEVEL_PRESSURE: f64 = 101325.0;
+/// Where the simulated probe is launched from, as latitude and longitude
+const LAUNCH_SITE: [f64; 2] = [52.2297, 21.0122];
+const METRES_PER_DEGREE: f64 = 111_320.0;
+
+/// Time spent on the launch pad, in seconds
+const PAD_TIME: f64 = 30.0;
+/// The GPS gets its first fix this many seconds after boot
+const GPS_FIX_TIME: f64 = 15.0;
+const BOOST_TIME: f64 = 3.0;
+const BOOST_ACCELERATION: f64 = 9.0 * GRAVITY;
+const DESCENT_RATE: f64 = 9.0;
+/// Launch time of day, used for the GPS time
+const LAUNCH_TIME_OF_DAY: u32 = 11 * 3600;
+
+/// Parameters of a simulated flight, chosen before it starts
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SimulatorSettings {
+    /// The same seed always produces the same flight
+    pub seed: u64,
+    /// Time between frames in milliseconds
+    pub interval_ms: u32,
+    /// Chance of each frame being lost, from 0 to 1
+    pub packet_loss: f64,
+    /// Chance of the probe rebooting at each frame, from 0 to 1
+    pub reset_chance: f64,
+}
+
+impl Default for SimulatorSettings {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            interval_ms: 200,
+            packet_loss: 0.05,
+            reset_chance: 0.0005,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FlightPhase {
+    Pad,
+    Boost,
+    Coast,
+    Descent,
+    Landed,
+}
+
+/// Synthesises telemetry frames of a CanSat flight: a rocket launch, free flight
+/// up to apogee, descent under a parachute and landing, drifting with the wind.
+/// The frames are formatted according to the schema and fed through
+/// [`MissionData::parse_line`], like those recieved from the radio.
+#[derive(Debug, Clone)]
+pub struct Simulator {
+    settings: SimulatorSettings,
+    rng: StdRng,
+
+    /// Flight time in seconds
+    time: f64,
+    phase: FlightPhase,
+    altitude: f64,
+    vertical_speed: f64,
+    /// Drift from the launch site in metres, east and north
+    drift: [f64; 2],
+    /// Measured acceleration of the previous step, in g
+    acceleration: f64,
+
+    /// Uptime and frame index of the probe, restarted when it reboots
+    boot_time: f64,
+    index: u32,
+
+    last_tick: Option<Instant>,
+    data: MissionData,
+}
+
+impl Simulator {
+    pub fn new(settings: SimulatorSettings, data: MissionData) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(settings.seed),
+            settings,
+            time: 0.0,
+            phase: FlightPhase::Pad,
+            altitude: 0.0,
+            vertical_speed: 0.0,
+            drift: [0.0, 0.0],
+            acceleration: 1.0,
+            boot_time: 0.0,
+            index: 0,
+            last_tick: None,
+            data,
+        }
+    }
+
+    pub fn data(&self) -> &MissionData {
+        &self.data
+    }
+
+    pub fn data_mut(&mut self) -> &mut MissionData {
+        &mut self.data
+    }
+
+    pub fn settings(&self) -> &SimulatorSettings {
+        &self.settings
+    }
+
+    pub fn phase(&self) -> FlightPhase {
+        self.phase
+    }
+
+    /// Generates the frames due since the previous tick. Called every frame.
+    pub fn tick(&mut self, now: Instant) {
+        let interval = self.interval();
+
+        if let Some(last_tick) = self.last_tick {
+            let mut elapsed = now.saturating_duration_since(last_tick).as_secs_f64();
+
+            while elapsed >= interval {
+                self.step();
+                elapsed -= interval;
+            }
+
+            // The remainder carries over to the next tick
+            self.last_tick = Some(now - std::time::Duration::from_secs_f64(elapsed));
+        } else {
+            self.last_tick = Some(now);
+        }
+    }
+
+    fn interval(&self) -> f64 {
+        self.settings.interval_ms.max(1) as f64 / 1000.0
+    }
+
+    /// Advances the flight by one frame and transmits it
+    pub fn step(&mut self) {
+        self.advance_flight(self.interval());
+
+        if self.rng.gen_bool(self.settings.reset_chance.clamp(0.0, 1.0)) {
+            self.boot_time = self.time;
+            self.index = 0;
+        }
+
+        let frame = self.frame();
+        self.index += 1;
+
+        if !self.rng.gen_bool(self.settings.packet_loss.clamp(0.0, 1.0)) {
+            let line = format_frame(self.data.schema(), &frame);
+            let _ = self.data.parse_line(&line);
+        }
+    }
+
+    fn advance_flight(&mut self, dt: f64) {
+        self.time += dt;
+        let previous_speed = self.vertical_speed;
+
+        match self.phase {
+            FlightPhase::Pad if self.time >= PAD_TIME => self.phase = FlightPhase::Boost,
+            FlightPhase::Boost if self.time >= PAD_TIME + BOOST_TIME => self.phase = FlightPhase::Coast,
+            FlightPhase::Coast if self.vertical_speed <= 0.0 => self.phase = FlightPhase::Descent,
+            FlightPhase::Descent if self.altitude <= 0.0 => {
+                self.phase = FlightPhase::Landed;
+                self.altitude = 0.0;
+                self.vertical_speed = 0.0;
+            },
+            _ => {},
+        }
+
+        let drag = 0.0015 * self.vertical_speed * self.vertical_speed.abs();
+        match self.phase {
+            FlightPhase::Pad | FlightPhase::Landed => {},
+            FlightPhase::Boost => self.vertical_speed += (BOOST_ACCELERATION - GRAVITY - drag) * dt,
+            FlightPhase::Coast => self.vertical_speed -= (GRAVITY + drag) * dt,
+            // The parachute quickly slows the probe down to its descent rate
+            FlightPhase::Descent => self.vertical_speed += (-DESCENT_RATE - self.vertical_speed) * (dt / 0.8).min(1.0),
+        }
+        self.altitude = (self.altitude + self.vertical_speed * dt).max(0.0);
+
+        // Felt acceleration: 1 g at rest, close to 0 g in free flight
+        self.acceleration = 1.0 + (self.vertical_speed - previous_speed) / dt / GRAVITY;
+
+        if !matches!(self.phase, FlightPhase::Pad | FlightPhase::Landed) {
+            // Gusty wind, mostly from the west
+            let gust = (self.time / 7.0).sin();
+            self.drift[0] += (4.0 + 1.5 * gust + self.noise(0.5)) * dt;
+            self.drift[1] += (0.8 * (self.time / 11.0).cos() + self.noise(0.5)) * dt;
+        }
+    }
+
+    fn noise(&mut self, amplitude: f64) -> f64 {
+        self.rng.gen_range(-amplitude..=amplitude)
+    }
+
+    /// What the probe would sense and transmit right now
+    fn frame(&mut self) -> SensedData {
+        let uptime = self.time - self.boot_time;
+        let altitude = self.altitude;
+
+        let pressure = SEA_LEVEL_PRESSURE * (1.0 - 2.25577e-5 * altitude).powf(5.25588) + self.noise(4.0);
+        let temperature = 21.0 - 0.0065 * altitude + 0.3 * (self.time / 60.0).sin() + self.noise(0.05);
+
+        let tilt = match self.phase {
+            FlightPhase::Descent => 0.15 * (self.time * 2.0 * PI / 3.0).sin(),
+            _ => 0.0,
+        };
+        let acceleration = [
+            self.acceleration * tilt.sin() + self.noise(0.02),
+            self.noise(0.02),
+            self.acceleration * tilt.cos() + self.noise(0.02),
+        ];
+
+        // The probe slowly spins under the parachute
+        let spin = if self.phase == FlightPhase::Descent { 45.0 } else { 0.0 };
+        let gyroscope = [self.noise(1.5), self.noise(1.5), spin + self.noise(1.5)];
+
+        let confidence = match self.phase {
+            // The accelerometer is near the end of its range during the boost
+            FlightPhase::Boost => ReadConfidence::Medium,
+            _ => ReadConfidence::High,
+        };
+
+        let (gps_time, gps_position, gps_altitude) = if self.time >= GPS_FIX_TIME {
+            let north = self.drift[1] + self.noise(2.0);
+            let east = self.drift[0] + self.noise(2.0);
+            let latitude = LAUNCH_SITE[0] + north / METRES_PER_DEGREE;
+            let longitude = LAUNCH_SITE[1] + east / (METRES_PER_DEGREE * LAUNCH_SITE[0].to_radians().cos());
+
+            let seconds = (LAUNCH_TIME_OF_DAY as f64 + self.time - PAD_TIME).round() as u32;
+            let gps_time = (seconds / 3600) * 10000 + (seconds / 60 % 60) * 100 + seconds % 60;
+
+            (gps_time, [latitude, longitude], altitude + self.noise(3.0))
+        } else {
+            (0, [f64::NAN, f64::NAN], f64::NAN)
+        };
+
+        SensedData {
+            index: self.index,
+            uptime: (uptime * 1000.0) as u32,
+            micros: self.rng.gen_range(0..1000),
+            temperature: temperature as f32,
+            pressure: pressure as f32,
+            acceleration,
+            acceleration_confidence: confidence,
+            gyroscope,
+            gyroscope_confidence: ReadConfidence::High,
+            gps_time,
+            gps_position,
+            gps_altitude,
+            extra: vec![],
+        }
+    }
+}
+
+/// Formats a record as a telemetry line of the given schema
+fn format_frame(schema: &TelemetrySchema, data: &SensedData) -> String {
+    let columns: Vec<String> = schema.fields().iter()
+        .map(|field| {
+            let value = field.value(data);
+            match field.field_type {
+                FieldType::Integer | FieldType::Confidence => format!("{}", value as u64),
+                FieldType::Float if value.is_nan() => "nan".to_owned(),
+                FieldType::Float => format!("{value:.6}"),
+            }
+        })
+        .collect();
+    let frame = columns.join("\t");
+
+    match schema.checksum() {
+        Some(kind) => {
+            let checksum = kind.compute(frame.as_bytes());
+            match kind {
+                ChecksumKind::Xor => format!("{frame}\t*{checksum:02X}"),
+                ChecksumKind::Crc16 => format!("{frame}\t*{checksum:04X}"),
+            }
+        },
+        None => frame,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn simulate(settings: SimulatorSettings, seconds: u32) -> Simulator {
+        let frames = seconds * 1000 / settings.interval_ms;
+        let mut simulator = Simulator::new(settings, MissionData::default());
+        for _ in 0..frames {
+            simulator.step();
+        }
+        simulator
+    }
+
+    #[test]
+    fn test_deterministic() {
+        let settings = SimulatorSettings { seed: 42, ..Default::default() };
+        // Compared through Debug, as NaN never equals itself
+        assert_eq!(format!("{:?}", simulate(settings.clone(), 60).data()), format!("{:?}", simulate(settings, 60).data()));
+    }
+
+    #[test]
+    fn test_flight_profile() {
+        let settings = SimulatorSettings { packet_loss: 0.0, reset_chance: 0.0, ..Default::default() };
+        let simulator = simulate(settings, 600);
+        assert_eq!(simulator.phase(), FlightPhase::Landed);
+
+        let data = simulator.data();
+        assert_eq!(data.sessions().len(), 1);
+        assert_eq!(data.rejected_count(), 0);
+
+        let session = &data.sessions()[0];
+        assert!(session[0].gps_position[0].is_nan());
+        assert!(!session.last().unwrap().gps_position[0].is_nan());
+
+        // The clock runs on the pad too, reaching the launch time of day at the launch
+        let first_fix = session.iter().find(|d| !d.gps_position[0].is_nan()).unwrap();
+        assert_eq!(first_fix.gps_time, 105945);
+
+        let apogee = session.iter().map(|d| d.gps_altitude).fold(0.0, f64::max);
+        assert!(apogee > 300.0 && apogee < 3000.0, "apogee {apogee}");
+
+        let lowest_pressure = session.iter().map(|d| d.pressure).fold(f32::MAX, f32::min);
+        assert!(lowest_pressure < session[0].pressure - 3000.0);
+    }
+
+    #[test]
+    fn test_packet_loss_and_resets() {
+        let settings = SimulatorSettings { packet_loss: 0.5, reset_chance: 0.01, ..Default::default() };
+        let simulator = simulate(settings, 200);
+        let data = simulator.data();
+
+        let recieved: usize = data.sessions().iter().map(|s| s.len()).sum();
+        assert!(recieved > 300 && recieved < 700, "recieved {recieved}");
+        assert!(data.sessions().len() > 1);
+    }
+
+    #[test]
+    fn test_checksummed_schema() {
+        let schema = TelemetrySchema::parse(&format!("checksum crc16\n{}", crate::schema::DEFAULT_SCHEMA)).unwrap();
+        let mut simulator = Simulator::new(SimulatorSettings::default(), MissionData::with_schema(schema.into()));
+        for _ in 0..100 {
+            simulator.step();
+        }
+        assert_eq!(simulator.data().rejected_count(), 0);
+    }
+}
@@ -0,0 +1,318 @@
+use std::{path::PathBuf, sync::{Arc, Mutex, MutexGuard}, time::Instant};
+
+use egui::Color32;
+
+use crate::{capture::CaptureInfo, data::{MissionData, Segmentation, SensedData}, merge::Merger, reader::ReaderHandle, replay::Replay, serial::SerialSettings, simulator::Simulator};
+
+/// Colours given to sources in the order they are opened
+const SOURCE_COLORS: [Color32; 6] = [
+    Color32::from_rgb(43, 134, 231),
+    Color32::from_rgb(239, 52, 80),
+    Color32::from_rgb(36, 178, 139),
+    Color32::from_rgb(245, 146, 44),
+    Color32::from_rgb(122, 96, 224),
+    Color32::from_rgb(130, 130, 130),
+];
+
+pub type SourceId = u32;
+
+#[derive(Debug, Clone)]
+pub enum DataSource {
+    File {
+        name: String,
+        /// Where the log was read from, so that projects can open it again
+        path: Option<PathBuf>,
+        data: MissionData
+    },
+    /// A log file streamed in as if it were recieved live
+    Replay {
+        name: String,
+        path: Option<PathBuf>,
+        replay: Box<Replay>
+    },
+    Simulator {
+        simulator: Box<Simulator>
+    },
+    Live {
+        kind: LiveSource,
+
+        data: Arc<Mutex<MissionData>>,
+        reader: ReaderHandle,
+        capture: Option<CaptureInfo>
+    },
+    /// Frames of other sources, combined and deduplicated
+    Merged {
+        merger: Box<Merger>
+    },
+}
+
+/// Where the lines of a live data source come from
+#[derive(Debug, Clone, PartialEq)]
+pub enum LiveSource {
+    SerialPort {
+        port_name: String,
+        settings: SerialSettings,
+    },
+    TcpClient {
+        address: String,
+    },
+    TcpServer {
+        port: u16,
+    },
+    UdpListener {
+        port: u16,
+    },
+}
+
+impl LiveSource {
+    /// Used to name the capture file and in the status bar
+    pub fn name(&self) -> String {
+        match self {
+            LiveSource::SerialPort { port_name, .. } => format!("serial {port_name}"),
+            LiveSource::TcpClient { address } => format!("TCP {address}"),
+            LiveSource::TcpServer { port } => format!("TCP server on port {port}"),
+            LiveSource::UdpListener { port } => format!("UDP port {port}"),
+        }
+    }
+
+    pub fn connected_text(&self) -> String {
+        match self {
+            LiveSource::SerialPort { port_name, settings } => format!("Connected to serial {} ({})", port_name, settings.summary()),
+            LiveSource::TcpServer { port } => format!("Client connected to TCP server on port {port}"),
+            LiveSource::UdpListener { port } => format!("Listening on UDP port {port}"),
+            _ => format!("Connected to {}", self.name()),
+        }
+    }
+
+    pub fn disconnected_text(&self) -> String {
+        match self {
+            LiveSource::TcpServer { port } => format!("Waiting for a client on TCP port {port}…"),
+            _ => format!("⚠ Disconnected from {}, reconnecting…", self.name()),
+        }
+    }
+}
+
+impl DataSource {
+    pub fn get_data_lock(&self) -> Option<MutexGuard<'_, MissionData>> {
+        match &self {
+            DataSource::Live { data, .. } => Some(data.lock().unwrap()),
+            _ => None,
+        }
+    }
+
+    pub fn get_data<'a>(&'a self, lock: &'a Option<MutexGuard<'_, MissionData>>) -> Option<&'a MissionData> {
+        match &self {
+            DataSource::File { data, .. } => Some(data),
+            DataSource::Replay { replay, .. } => Some(replay.data()),
+            DataSource::Simulator { simulator } => Some(simulator.data()),
+            DataSource::Live { .. } => lock.as_ref().map(|v| &**v),
+            DataSource::Merged { merger } => Some(merger.data()),
+        }
+    }
+
+    /// Advances sources that are driven by the UI. Returns whether they need repainting.
+    pub fn tick(&mut self, now: Instant) -> bool {
+        match self {
+            DataSource::Replay { replay, .. } => {
+                replay.tick(now);
+                replay.is_playing()
+            },
+            DataSource::Simulator { simulator } => {
+                simulator.tick(now);
+                true
+            },
+            _ => false,
+        }
+    }
+
+    /// Applies new segmentation rules to what the source recieved. Merged sources follow their inputs.
+    pub fn set_segmentation(&mut self, segmentation: Segmentation) {
+        match self {
+            DataSource::Replay { replay, .. } => replay.set_segmentation(segmentation),
+            DataSource::Merged { .. } => {},
+            _ => {
+                self.edit_data(|data| data.resegment(segmentation));
+            },
+        }
+    }
+
+    /// Lets the sessions of the source be changed by hand. Replays and merged sources rebuild
+    /// their sessions, so they can't be edited and `false` is returned.
+    pub fn edit_data(&mut self, edit: impl FnOnce(&mut MissionData)) -> bool {
+        match self {
+            DataSource::File { data, .. } => edit(data),
+            DataSource::Simulator { simulator } => edit(simulator.data_mut()),
+            DataSource::Live { data, .. } => edit(&mut data.lock().unwrap()),
+            DataSource::Replay { .. } | DataSource::Merged { .. } => return false,
+        }
+        true
+    }
+
+    /// Stops reading from the source
+    pub fn close(&self) {
+        if let DataSource::Live { reader, .. } = self {
+            reader.cancel();
+        }
+    }
+}
+
+/// An open data source
+#[derive(Debug)]
+pub struct Source {
+    pub id: SourceId,
+    pub name: String,
+    pub color: Color32,
+    pub data_source: DataSource,
+    pub current_session: usize,
+}
+
+/// A session of a source, as shown by the tabs that can display several sources at once
+pub struct SessionView<'a> {
+    pub name: &'a str,
+    pub color: Color32,
+    /// Position of the session within the source
+    pub session_index: usize,
+    pub mission: &'a MissionData,
+    pub session: &'a [SensedData],
+}
+
+/// The data sources open at the same time
+#[derive(Debug, Default)]
+pub struct Sources {
+    sources: Vec<Source>,
+    next_id: SourceId,
+    /// The source controlled from the status bar and the command console
+    active: Option<SourceId>,
+}
+
+impl Sources {
+    /// Opens a source and makes it the active one
+    pub fn add(&mut self, name: String, data_source: DataSource) -> SourceId {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        let data_lock = data_source.get_data_lock();
+        let current_session = data_source.get_data(&data_lock)
+            .map_or(0, |data| data.sessions().len().saturating_sub(1));
+        drop(data_lock);
+
+        self.sources.push(Source {
+            id,
+            name,
+            color: SOURCE_COLORS[id as usize % SOURCE_COLORS.len()],
+            data_source,
+            current_session,
+        });
+        self.active = Some(id);
+
+        id
+    }
+
+    pub fn close(&mut self, id: SourceId) {
+        if let Some(position) = self.sources.iter().position(|s| s.id == id) {
+            self.sources.remove(position).data_source.close();
+        }
+
+        if self.active == Some(id) {
+            self.active = self.sources.last().map(|s| s.id);
+        }
+    }
+
+    /// Feeds merged sources with what their inputs recieved since the last update
+    pub fn update_merged(&mut self) {
+        for i in 0..self.sources.len() {
+            let (before, rest) = self.sources.split_at_mut(i);
+            let (current, after) = rest.split_first_mut().expect("index should be in bounds");
+
+            let DataSource::Merged { merger } = &mut current.data_source else {
+                continue;
+            };
+
+            let inputs: Vec<&Source> = before.iter().chain(after.iter())
+                .filter(|source| merger.inputs().any(|id| id == source.id))
+                .collect();
+            let locks: Vec<_> = inputs.iter().map(|source| source.data_source.get_data_lock()).collect();
+
+            merger.update(inputs.iter().zip(&locks)
+                .filter_map(|(source, lock)| Some((source.id, source.data_source.get_data(lock)?))));
+        }
+    }
+
+    pub fn get(&self, id: SourceId) -> Option<&Source> {
+        self.sources.iter().find(|s| s.id == id)
+    }
+
+    pub fn get_mut(&mut self, id: SourceId) -> Option<&mut Source> {
+        self.sources.iter_mut().find(|s| s.id == id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Source> {
+        self.sources.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Source> {
+        self.sources.iter_mut()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sources.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.sources.len()
+    }
+
+    pub fn active_id(&self) -> Option<SourceId> {
+        self.active
+    }
+
+    pub fn set_active(&mut self, id: SourceId) {
+        if self.get(id).is_some() {
+            self.active = Some(id);
+        }
+    }
+
+    pub fn active(&self) -> Option<&Source> {
+        self.active.and_then(|id| self.get(id))
+    }
+
+    pub fn active_mut(&mut self) -> Option<&mut Source> {
+        let id = self.active?;
+        self.get_mut(id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(name: &str) -> DataSource {
+        DataSource::File { name: name.to_owned(), path: None, data: MissionData::from_log("1\t1\t1\t2\t3\t4\t4\t4\t0\t6\t6\t6\t0\t8\t9\t9\t10\n0\t1\t1\t2\t3\t4\t4\t4\t0\t6\t6\t6\t0\t8\t9\t9\t10", Arc::default()) }
+    }
+
+    #[test]
+    fn test_add_and_close() {
+        let mut sources = Sources::default();
+        let first = sources.add("first".to_owned(), file("first"));
+        let second = sources.add("second".to_owned(), file("second"));
+
+        assert_eq!(sources.active_id(), Some(second));
+        assert_ne!(sources.get(first).unwrap().color, sources.get(second).unwrap().color);
+        // The latest session is shown
+        assert_eq!(sources.get(first).unwrap().current_session, 1);
+
+        sources.set_active(first);
+        sources.close(first);
+        assert_eq!(sources.active_id(), Some(second));
+        assert_eq!(sources.len(), 1);
+
+        // Ids aren't reused, so selections of closed sources don't point at new ones
+        let third = sources.add("third".to_owned(), file("third"));
+        assert!(third != first && third != second);
+
+        sources.close(second);
+        sources.close(third);
+        assert!(sources.is_empty());
+        assert_eq!(sources.active_id(), None);
+    }
+}
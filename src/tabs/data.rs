@@ -1,14 +1,24 @@
 
 use egui::{Ui, Vec2};
 use egui_extras::{Column, TableBuilder};
+use serde::{Deserialize, Serialize};
 
-use crate::data::SensedData;
+use crate::{altitude::Altitude, data::MissionData, expression::ComputedChannel, merge::ReceiverStats, schema::FieldTarget};
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct DataTabState {
     pub stick_to_bottom: bool
 }
 
-pub fn data_tab(ui: &mut Ui, state: &mut DataTabState, data: &[SensedData]) {
+/// `receivers` break the stats down by ground station radio when the data is merged from several
+pub fn data_tab(ui: &mut Ui, state: &mut DataTabState, mission: &MissionData, session: usize, receivers: &[ReceiverStats], altitude: &Altitude, channels: &[ComputedChannel]) {
+    let schema = mission.schema();
+    let data = &mission.sessions()[session];
+    let altitude = schema.field(FieldTarget::Pressure).map(|_| altitude);
+    let computed: Vec<(&ComputedChannel, Vec<f64>)> = channels.iter()
+        .filter_map(|channel| Some((channel, channel.evaluate(schema, data).ok()?)))
+        .collect();
+
     let text_height = egui::TextStyle::Body
         .resolve(ui.style())
         .size
@@ -28,23 +38,34 @@ pub fn data_tab(ui: &mut Ui, state: &mut DataTabState, data: &[SensedData]) {
             ui.label("No messages recieved");
         } else {
             let recieved = data.len() as u32;
-            let total = data.last().unwrap().index - data.first().unwrap().index + 1;
+            // Sessions merged across a restart of the probe can hold more frames than their index range
+            let total = data.last().unwrap().index.saturating_sub(data.first().unwrap().index) + 1;
+            let corrupted = (mission.corrupted_frames(session) as u32).min(total.saturating_sub(recieved));
+            let lost = total.saturating_sub(recieved) - corrupted;
 
             ui.label(format!("Recieved: {}", recieved));
             ui.label(format!("Total: {}", total));
-            ui.label(format!("Lost: {}, {:.2}%", total - recieved, (total - recieved) as f32 / (total as f32) * 100.0));
+            ui.label(format!("Corrupted: {}, {:.2}%", corrupted, corrupted as f32 / (total as f32) * 100.0));
+            ui.label(format!("Lost: {}, {:.2}%", lost, lost as f32 / (total as f32) * 100.0));
+            ui.label(format!("Reordered: {}", mission.reordered_frames(session)))
+                .on_hover_text("Frames recieved after a frame with a higher index");
+            ui.label(format!("Duplicates: {}", mission.duplicate_frames(session)))
+                .on_hover_text("Frames recieved more than once. Only the first copy is kept.");
+            if mission.buffered_frames() > 0 {
+                ui.weak(format!("{} frames held in the reorder buffer", mission.buffered_frames()));
+            }
 
             ui.allocate_space(Vec2 { x: 0.0, y: 10.0 });
 
             let mut max_time = 0;
-            let mut min_time = 0;
+            let mut min_time = u64::MAX;
             let mut sum = 0;
             let mut count = 0;
 
-            let mut prev_uptime = 0;
+            let mut prev_timestamp: Option<u64> = None;
             for row in data {
-                if prev_uptime != 0 {
-                    if let Some(delta) = row.uptime.checked_sub(prev_uptime) {
+                if let Some(prev_timestamp) = prev_timestamp {
+                    if let Some(delta) = row.timestamp().checked_sub(prev_timestamp) {
                         if delta > max_time {
                             max_time = delta;
                         }
@@ -58,41 +79,78 @@ pub fn data_tab(ui: &mut Ui, state: &mut DataTabState, data: &[SensedData]) {
                     }
                 }
 
-                prev_uptime = row.uptime;
+                prev_timestamp = Some(row.timestamp());
             }
 
-            ui.label(format!("Avg time between messages: {}", sum as f32 / count as f32));
-            ui.label(format!("Max time between messages: {}", max_time));
-            ui.label(format!("Min time between messages: {}", min_time));
+            if count > 0 {
+                ui.label(format!("Avg time between messages: {:.3} ms", sum as f64 / count as f64 / 1000.0));
+                ui.label(format!("Max time between messages: {:.3} ms", max_time as f64 / 1000.0));
+                ui.label(format!("Min time between messages: {:.3} ms", min_time as f64 / 1000.0));
+            }
             ui.weak("Records with a negative time delta are ignored.");
+
+            if let Some(altitude) = &altitude {
+                ui.allocate_space(Vec2 { x: 0.0, y: 10.0 });
+
+                ui.label(format!("Reference pressure: {:.1} Pa", altitude.reference))
+                    .on_hover_text("Pressure taken as zero barometric altitude, set in View > Barometric altitude");
+                let max = altitude.altitude.iter().copied().filter(|a| !a.is_nan()).reduce(f64::max);
+                if let Some(max) = max {
+                    ui.label(format!("Max barometric altitude: {:.1} m", max));
+                }
+            }
+        }
+
+        if !receivers.is_empty() {
+            ui.separator();
+
+            ui.heading("Receivers");
+            let total = data.last().zip(data.first()).map_or(0, |(last, first)| last.index.saturating_sub(first.index) + 1);
+
+            egui::Grid::new("receivers_grid").num_columns(5).striped(true).show(ui, |ui| {
+                ui.strong("Receiver");
+                ui.strong("Recieved");
+                ui.strong("Contributed");
+                ui.strong("Lost");
+                ui.strong("Corrupted");
+                ui.end_row();
+
+                for receiver in receivers {
+                    ui.label(&receiver.name);
+                    ui.label(receiver.recieved.to_string());
+                    ui.label(receiver.contributed.to_string())
+                        .on_hover_text("Frames this receiver delivered first");
+                    ui.label(format!("{}, {:.2}%", receiver.lost, receiver.lost as f32 / (total.max(1) as f32) * 100.0));
+                    ui.label(receiver.corrupted.to_string());
+                    ui.end_row();
+                }
+            });
         }
         
     });
     
     egui::CentralPanel::default().show_inside(ui, |ui| {
+        let fields = schema.fields();
+        let derived_columns = if altitude.is_some() { 2 } else { 0 };
+        // The uptime is shown with the microseconds within the millisecond, as in the timestamp
+        let has_micros = fields.iter().any(|field| field.target == FieldTarget::Micros);
+
         TableBuilder::new(ui)
             .column(Column::auto().resizable(true))
-            .column(Column::auto().resizable(true))
-            .column(Column::auto().resizable(true))
-            .column(Column::auto().resizable(true))
-            .column(Column::auto().resizable(true))
-            .column(Column::auto().resizable(true))
-            .column(Column::auto().resizable(true))
-            .column(Column::auto().resizable(true))
-            .column(Column::auto().resizable(true))
-            .column(Column::auto().resizable(true))
+            .columns(Column::auto().resizable(true), fields.len() + derived_columns + computed.len())
             .stick_to_bottom(state.stick_to_bottom)
             .header(20.0, |mut header| {
                 header.col(|ui| {ui.label("#");});
-                header.col(|ui| {ui.label("Uptime");});
-                header.col(|ui| {ui.label("GPS Time");});
-                header.col(|ui| {ui.label("Temperature");});
-                header.col(|ui| {ui.label("Pressure");});
-                header.col(|ui| {ui.label("GPS Location");});
-                header.col(|ui| {ui.label("GPS Altitude");});
-                header.col(|ui| {ui.label("Accel X");});
-                header.col(|ui| {ui.label("Accel Y");});
-                header.col(|ui| {ui.label("Accel Z");});
+                for field in fields {
+                    header.col(|ui| {ui.label(field.label_with_unit());});
+                }
+                if altitude.is_some() {
+                    header.col(|ui| {ui.label("Baro altitude [m]").on_hover_text("Derived from the pressure");});
+                    header.col(|ui| {ui.label("Vertical speed [m/s]").on_hover_text("Derived from the pressure");});
+                }
+                for (channel, _) in &computed {
+                    header.col(|ui| {ui.label(channel.label_with_unit()).on_hover_text(&channel.expression);});
+                }
             })
             .body(|body| {
                 body.rows(text_height, data.len(), |mut row| {
@@ -104,37 +162,28 @@ pub fn data_tab(ui: &mut Ui, state: &mut DataTabState, data: &[SensedData]) {
                     row.col(|ui| {
                         ui.weak(row_index.to_string());
                     });
-                    row.col(|ui| {
-                        ui.label(format!("{}", data_row.uptime));
-                    });
-                    row.col(|ui| {
-                        ui.label(format!("{}", data_row.gps_time));
-                    });
-                    row.col(|ui| {
-                        ui.label(format!("{}", data_row.temperature));
-                    });
-                    row.col(|ui| {
-                        ui.label(format!("{}", data_row.pressure));
-                    });
-                    row.col(|ui| {
-                        if data_row.gps_position[0].is_nan() || data_row.gps_position[1].is_nan() {
-                            ui.weak("?");
-                        } else {
-                            ui.label(format!("{} {}", data_row.gps_position[0], data_row.gps_position[1]));
-                        }
-                    });
-                    row.col(|ui| {
-                        ui.label(format!("{}", data_row.gps_altitude));
-                    });
-                    row.col(|ui| {
-                        ui.label(format!("{}", data_row.acceleration[0]));
-                    });
-                    row.col(|ui| {
-                        ui.label(format!("{}", data_row.acceleration[1]));
-                    });
-                    row.col(|ui| {
-                        ui.label(format!("{}", data_row.acceleration[2]));
-                    });
+                    for field in fields {
+                        row.col(|ui| {
+                            if field.value(data_row).is_nan() {
+                                ui.weak("?");
+                            } else if field.target == FieldTarget::Uptime && has_micros {
+                                ui.label(format!("{}.{:03}", data_row.uptime, data_row.micros.min(999)));
+                            } else {
+                                ui.label(field.format(data_row));
+                            }
+                        });
+                    }
+                    let derived = altitude.iter().flat_map(|altitude| [altitude.altitude[row_index], altitude.vertical_speed[row_index]]);
+                    let computed = computed.iter().map(|(_, values)| values[row_index]);
+                    for value in derived.chain(computed) {
+                        row.col(|ui| {
+                            if value.is_nan() {
+                                ui.weak("?");
+                            } else {
+                                ui.label(format!("{:.2}", value));
+                            }
+                        });
+                    }
                 });
             });
     });
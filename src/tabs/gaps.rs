@@ -0,0 +1,99 @@
+use egui::Ui;
+use egui_extras::{Column, TableBuilder};
+use egui_plot::{Line, Plot, PlotPoints, VLine};
+use serde::{Deserialize, Serialize};
+
+use crate::{data::SensedData, gaps::{find_gaps, loss_rate}};
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GapsTabState {
+    /// Length of the sliding window of the loss rate, in seconds
+    pub window: f64,
+    /// Gaps shorter than this are left out of the list
+    pub min_missing: u32,
+}
+
+impl Default for GapsTabState {
+    fn default() -> Self {
+        Self { window: 10.0, min_missing: 1 }
+    }
+}
+
+/// Lists the gaps of a session and plots how the loss rate changed over time
+pub fn gaps_tab(ui: &mut Ui, state: &mut GapsTabState, session: &[SensedData]) {
+    let text_height = egui::TextStyle::Body
+        .resolve(ui.style())
+        .size
+        .max(ui.spacing().interact_size.y);
+
+    ui.style_mut().wrap_mode = Some(egui::TextWrapMode::Extend);
+
+    let gaps: Vec<_> = find_gaps(session).into_iter()
+        .filter(|gap| gap.missing() >= state.min_missing)
+        .collect();
+
+    egui::SidePanel::left("gaps_side_panel").min_width(360.0).show_inside(ui, |ui| {
+        ui.style_mut().wrap_mode = Some(egui::TextWrapMode::Extend);
+
+        ui.horizontal(|ui| {
+            ui.label("Loss rate window");
+            ui.add(egui::DragValue::new(&mut state.window).range(0.1..=3600.0).speed(0.5).suffix(" s"));
+        });
+        ui.horizontal(|ui| {
+            ui.label("Hide gaps shorter than");
+            ui.add(egui::DragValue::new(&mut state.min_missing).range(1..=u32::MAX));
+            ui.label("frames");
+        });
+
+        ui.separator();
+
+        let missing: u32 = gaps.iter().map(|gap| gap.missing()).sum();
+        ui.heading(format!("{} gaps, {} frames missing", gaps.len(), missing));
+        if let Some(longest) = gaps.iter().max_by_key(|gap| gap.duration_ms) {
+            ui.label(format!("Longest: {:.3} s after index {}", longest.duration_ms as f64 / 1000.0, longest.start_index - 1));
+        }
+
+        ui.separator();
+
+        TableBuilder::new(ui)
+            .striped(true)
+            .columns(Column::auto().resizable(true), 5)
+            .header(20.0, |mut header| {
+                header.col(|ui| {ui.label("Start index");});
+                header.col(|ui| {ui.label("End index");});
+                header.col(|ui| {ui.label("Missing");});
+                header.col(|ui| {ui.label("Duration [s]");});
+                header.col(|ui| {ui.label("Uptime [s]");});
+            })
+            .body(|body| {
+                body.rows(text_height, gaps.len(), |mut row| {
+                    let gap = &gaps[row.index()];
+
+                    row.col(|ui| {ui.label(gap.start_index.to_string());});
+                    row.col(|ui| {ui.label(gap.end_index.to_string());});
+                    row.col(|ui| {ui.label(gap.missing().to_string());});
+                    row.col(|ui| {ui.label(format!("{:.3}", gap.duration_ms as f64 / 1000.0));});
+                    row.col(|ui| {ui.label(format!("{:.3}", session[gap.before].timestamp() as f64 / 1_000_000.0));});
+                });
+            });
+    });
+
+    egui::CentralPanel::default().show_inside(ui, |ui| {
+        let gap_color = ui.visuals().warn_fg_color.gamma_multiply(0.5);
+
+        Plot::new("loss_rate_plot")
+            .x_axis_label("Uptime [s]")
+            .y_axis_label("Loss [%]")
+            .include_y(0.0)
+            .include_y(100.0)
+            .show(ui, |plot_ui| {
+                plot_ui.line(Line::new("Loss rate", PlotPoints::new(loss_rate(session, state.window))));
+
+                for gap in &gaps {
+                    plot_ui.vline(VLine::new("Gaps", session[gap.before].timestamp() as f64 / 1_000_000.0)
+                        .color(gap_color));
+                }
+            });
+    });
+}
@@ -1,10 +1,14 @@
 
 use directories::ProjectDirs;
-use egui::{color_picker::color_edit_button_rgba, CollapsingHeader, Color32, Context, DragValue, Frame, Layout, Popup, Rgba, RichText, Ui};
+use egui::{CollapsingHeader, Color32, Context, DragValue, Frame, Layout, Popup, RichText, Ui};
+use serde::{Deserialize, Serialize};
 use walkers::{extras::{LabeledSymbol, LabeledSymbolStyle, Places}, sources, HttpOptions, HttpTiles, Map, MapMemory, Position, Projector};
 
-use crate::data::SensedData;
-use crate::util::map_trail::TrailPlugin;
+use crate::gaps::find_gaps;
+use crate::events::FlightEvent;
+use crate::sources::SessionView;
+use crate::track::{distance, gps_time_seconds, Track};
+use crate::util::{map_gaps::GapMarkersPlugin, map_trail::TrailPlugin};
 
 pub struct MapTabState {
     map_memory: MapMemory,
@@ -15,11 +19,66 @@ pub struct MapTabState {
 
     ground_station: Position,
     
-    trail_color: Rgba,
     trail_length: usize,
+    /// Whether the places where frames were lost are marked
+    show_gaps: bool,
+
+    /// Track of another GPS receiver shown for comparison
+    reference: Option<Track>,
+    /// Added to `gps_time` of the telemetry to find the matching reference position
+    reference_offset: f64,
+    /// Whether the reference trail stops at the time of the latest record
+    reference_until_latest: bool,
+}
+
+/// Colour of the reference trail
+const REFERENCE_COLOR: Color32 = Color32::from_rgb(160, 160, 160);
+
+/// The parts of [`MapTabState`] that are kept between launches and in projects
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MapSettings {
+    pub geo_view: bool,
+    /// Latitude and longitude
+    pub ground_station: [f64; 2],
+    pub trail_length: usize,
+    pub show_gaps: bool,
+    /// Seconds added to `gps_time` when aligning the reference track
+    pub reference_offset: f64,
+    pub reference_until_latest: bool,
+}
+
+impl Default for MapSettings {
+    fn default() -> Self {
+        Self { geo_view: false, ground_station: [0.0, 0.0], trail_length: 0, show_gaps: false, reference_offset: 0.0, reference_until_latest: true }
+    }
 }
 
 impl MapTabState {
+    pub fn settings(&self) -> MapSettings {
+        MapSettings {
+            geo_view: self.geo_view,
+            ground_station: [self.ground_station.x(), self.ground_station.y()],
+            trail_length: self.trail_length,
+            show_gaps: self.show_gaps,
+            reference_offset: self.reference_offset,
+            reference_until_latest: self.reference_until_latest,
+        }
+    }
+
+    pub fn apply_settings(&mut self, settings: MapSettings) {
+        self.geo_view = settings.geo_view;
+        self.ground_station = Position::new(settings.ground_station[0], settings.ground_station[1]);
+        self.trail_length = settings.trail_length;
+        self.show_gaps = settings.show_gaps;
+        self.reference_offset = settings.reference_offset;
+        self.reference_until_latest = settings.reference_until_latest;
+    }
+
+    pub fn set_reference(&mut self, track: Option<Track>) {
+        self.reference = track;
+    }
+
     pub fn new(egui_ctx: &Context) -> Self {
         let cache = ProjectDirs::from("eu", "vlospace", env!("CARGO_CRATE_NAME"))
             .map(|dirs| dirs.cache_dir().to_path_buf());
@@ -44,17 +103,34 @@ impl MapTabState {
                 egui_ctx.to_owned()
             ),
             ground_station: Default::default(),
-            trail_color: Color32::BLACK.into(),
-            trail_length: 0
+            trail_length: 0,
+            show_gaps: false,
+            reference: None,
+            reference_offset: 0.0,
+            reference_until_latest: true,
         }
     }
 }
 
+/// Shows the sessions of one or more sources on the map. The camera follows the first one.
+/// `events` are the flight events of each view, pinned where the probe was at the time.
 pub fn map_tab(
     ui: &mut Ui, 
     state: &mut MapTabState,
-    data: &[SensedData]
+    views: &[SessionView<'_>],
+    events: &[Vec<FlightEvent>]
 ) {
+    let data = views.first().map_or(&[][..], |view| view.session);
+
+    // Time of day of the latest record with a GPS time, shifted onto the clock of the reference
+    let reference_time = data.iter().rev()
+        .find(|d| d.gps_time != 0)
+        .and_then(|d| gps_time_seconds(d.gps_time))
+        .map(|time| time + state.reference_offset);
+    let reference_position = state.reference.as_ref()
+        .zip(reference_time)
+        .and_then(|(track, time)| track.position_at(time));
+
     egui::SidePanel::left("map_side_panel").min_width(231.0).show_inside(ui, |ui| {
 
         CollapsingHeader::new("Map").default_open(true).show(ui, |ui| {
@@ -99,13 +175,22 @@ pub fn map_tab(
                 ui.add_space(18.0);
                 ui.heading("Azimuth");
 
-                ui.label(RichText::new( if let Some(last) = data.last() {
+                let azimuth = |view: &SessionView<'_>| if let Some(last) = view.session.last() {
                     let azimuth = calculate_azimuth(&[state.ground_station.x(), state.ground_station.y()], &last.gps_position);
                     format!("{:.2}", if azimuth < 0.0 {360.0 + azimuth} else {azimuth} )
                 }
                 else {
                     "-".to_string()
-                }).size(40.0));
+                };
+
+                ui.label(RichText::new(views.first().map_or("-".to_string(), azimuth)).size(40.0));
+
+                // The others are listed below the one the camera follows
+                if views.len() > 1 {
+                    for view in views {
+                        ui.colored_label(view.color, format!("{}: {}", view.name, azimuth(view)));
+                    }
+                }
                 ui.add_space(16.0);
             });
         
@@ -136,10 +221,47 @@ pub fn map_tab(
                 ui.add(DragValue::new(&mut state.trail_length).range(0..=usize::MAX).speed(50.0));
                 ui.label("positions");
             });
+            ui.weak("Trails take the colour of their source, set in the Sources menu");
+            ui.checkbox(&mut state.show_gaps, "Mark where frames were lost");
+        });
+
+        ui.separator();
+
+        CollapsingHeader::new("Reference track").default_open(true).show(ui, |ui| {
+            let Some(track) = &state.reference else {
+                ui.label("No track loaded");
+                ui.weak("Load a GPX file or NMEA log with File > Import GPS track");
+                return;
+            };
+
             ui.horizontal(|ui| {
-                ui.label("Trail color: ");
-                color_edit_button_rgba(ui, &mut state.trail_color, egui::color_picker::Alpha::Opaque);
+                ui.colored_label(REFERENCE_COLOR, &track.name);
+                ui.label(format!("({} positions)", track.points.len()));
             });
+
+            ui.horizontal(|ui| {
+                ui.label("Time offset");
+                ui.add(DragValue::new(&mut state.reference_offset).speed(0.1).suffix(" s"))
+                    .on_hover_text("Added to the GPS time of the telemetry, e.g. to correct a time zone or a receiver clock");
+            });
+            ui.checkbox(&mut state.reference_until_latest, "Only up to the latest record");
+
+            match (reference_position, data.iter().rev().find(|d| !d.gps_position[0].is_nan())) {
+                (Some(reference), Some(last)) => {
+                    let horizontal = distance([reference.latitude, reference.longitude], last.gps_position);
+                    ui.label(format!("Distance from the probe: {horizontal:.1} m"));
+                    if !reference.altitude.is_nan() {
+                        ui.label(format!("Reference altitude: {:.1} m", reference.altitude));
+                    }
+                },
+                (None, _) if reference_time.is_none() => {ui.weak("No GPS time in the telemetry");},
+                (None, _) => {ui.weak("The track doesn't cover the GPS time of the latest record");},
+                (Some(_), None) => {ui.weak("No probe position");},
+            }
+
+            if ui.button("Remove").clicked() {
+                state.reference = None;
+            }
         });
 
         ui.separator();
@@ -153,21 +275,44 @@ pub fn map_tab(
 
     egui::CentralPanel::default().frame(Frame::NONE).show_inside(ui, |ui| {
 
+        let mut trails: Vec<_> = views.iter()
+            .map(|view| (view.color, view.session.iter().rev().take(state.trail_length).map(|data| {
+                Position::new(data.gps_position[0], data.gps_position[1])
+            })))
+            .collect();
+
+        let mut reference_trail = state.reference.iter()
+            .flat_map(|track| &track.points)
+            .filter(|point| !state.reference_until_latest || reference_time.is_none_or(|time| point.time.is_none_or(|t| t <= time)))
+            .map(|point| Position::new(point.latitude, point.longitude))
+            .chain(reference_position.filter(|_| state.reference_until_latest).map(|point| Position::new(point.latitude, point.longitude)));
+
+        let mut map = Map::new(
+            Some(if state.geo_view {&mut state.geo_tiles} else {&mut state.osm_tiles}),
+            &mut state.map_memory,
+            current_position.unwrap_or_default()
+        );
+
+        map = map.with_plugin(TrailPlugin { positions: &mut reference_trail, color: REFERENCE_COLOR });
+
+        for (color, positions) in trails.iter_mut() {
+            map = map.with_plugin(TrailPlugin { positions, color: *color });
+        }
+
+        if state.show_gaps {
+            for view in views {
+                let gaps = find_gaps(view.session).into_iter()
+                    .map(|gap| (view.session[gap.before].gps_position, gap.missing()))
+                    .filter(|(position, _)| !position[0].is_nan() && !position[1].is_nan())
+                    .map(|(position, missing)| (Position::new(position[0], position[1]), missing))
+                    .collect();
+
+                map = map.with_plugin(GapMarkersPlugin { gaps, color: view.color });
+            }
+        }
+
         let map_response = ui.add(
-            Map::new(
-                Some(if state.geo_view {&mut state.geo_tiles} else {&mut state.osm_tiles}),
-                &mut state.map_memory,
-                current_position.unwrap_or_default()
-            )
-            .with_plugin(
-                TrailPlugin {
-                    positions: &mut data.iter().rev().take(state.trail_length).map(|data| {
-                        Position::new(data.gps_position[0], data.gps_position[1])
-                    }),
-                    color: state.trail_color.into()
-                }
-            )
-            .with_plugin({
+            map.with_plugin({
                 let mut points: Vec<LabeledSymbol> = vec![];
 
                 fn labeled_symbol(position: Position ,name: &str) -> LabeledSymbol {
@@ -179,8 +324,29 @@ pub fn map_tab(
                     }
                 }
 
-                if let Some(position) = current_position {
-                    points.push(labeled_symbol(position, "Latest position"));
+                for view in views {
+                    if let Some(last) = view.session.last() {
+                        let name = if views.len() > 1 { view.name } else { "Latest position" };
+                        points.push(labeled_symbol(Position::new(last.gps_position[0], last.gps_position[1]), name));
+                    }
+                }
+
+                for (view, events) in views.iter().zip(events) {
+                    for event in events {
+                        // The last known position, as the GPS may have no fix at the moment of the event
+                        let position = view.session[..=event.record].iter().rev()
+                            .map(|d| d.gps_position)
+                            .find(|position| !position[0].is_nan() && !position[1].is_nan());
+
+                        if let Some(position) = position {
+                            let name = if views.len() > 1 { format!("{}: {}", view.name, event.kind.name()) } else { event.kind.name().to_owned() };
+                            points.push(labeled_symbol(Position::new(position[0], position[1]), &name));
+                        }
+                    }
+                }
+
+                if let Some(reference) = reference_position {
+                    points.push(labeled_symbol(Position::new(reference.latitude, reference.longitude), "Reference"));
                 }
 
                 points.push(labeled_symbol(state.ground_station, "Ground station"));
@@ -1,3 +1,5 @@
 pub mod data;
 pub mod plot;
-pub mod map;
\ No newline at end of file
+pub mod map;
+pub mod rejected;
+pub mod gaps;
\ No newline at end of file
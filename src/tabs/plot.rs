@@ -1,8 +1,12 @@
+use std::collections::HashMap;
+
 use egui::{emath::Numeric, CollapsingHeader, Color32, Layout, Slider, Ui, Vec2b, WidgetText};
-use egui_plot::{Legend, Line, Plot, PlotPoints};
+use egui_plot::{Legend, Line, LineStyle, Plot, PlotPoint, PlotPoints, Text, VLine};
+use serde::{Deserialize, Serialize};
 
-use crate::data::SensedData;
+use crate::{altitude::Altitude, data::SensedData, events::FlightEvent, expression::ComputedChannel, gaps::find_gaps, schema::{FieldTarget, FieldType, TelemetrySchema}, sources::SessionView};
 
+#[derive(Clone, Serialize, Deserialize)]
 struct LineSettings {
     visible: bool,
     offset: f64,
@@ -17,13 +21,138 @@ impl Default for LineSettings {
     }
 }
 
+/// Value of a line at a record, given with its position in the session
+type LineValue<'a> = Box<dyn Fn(usize, &SensedData) -> f64 + 'a>;
+
+/// A line that can be drawn on the plot
+struct PlotLine<'a> {
+    name: String,
+    color: Color32,
+    value: LineValue<'a>,
+}
+
+/// Colours of the lines that have no colour of their own
+const PALETTE: [Color32; 6] = [
+    Color32::from_rgb(231, 111, 81),
+    Color32::from_rgb(42, 157, 143),
+    Color32::from_rgb(233, 196, 106),
+    Color32::from_rgb(144, 103, 198),
+    Color32::from_rgb(38, 70, 83),
+    Color32::from_rgb(244, 162, 97),
+];
+
+/// Collects the lines available for a schema: every float field, and the magnitude
+/// of the acceleration and gyroscope vectors if all of their axes are present
+fn plot_lines(schema: &TelemetrySchema) -> Vec<PlotLine<'_>> {
+    fn vector_sum<'a>(schema: &'a TelemetrySchema, name: &str, color: Color32, target: fn(usize) -> FieldTarget) -> Option<PlotLine<'a>> {
+        let axes = [
+            schema.field(target(0))?,
+            schema.field(target(1))?,
+            schema.field(target(2))?,
+        ];
+
+        Some(PlotLine {
+            name: name.to_owned(),
+            color,
+            value: Box::new(move |_, s| {
+                (axes[0].value(s).powi(2) + axes[1].value(s).powi(2) + axes[2].value(s).powi(2)).sqrt()
+            }),
+        })
+    }
+
+    let mut lines = vec![];
+
+    for field in schema.fields().iter().filter(|f| f.field_type == FieldType::Float) {
+        let color = match field.target {
+            FieldTarget::Acceleration(0) => Color32::from_rgb(239, 52, 80),
+            FieldTarget::Acceleration(1) => Color32::from_rgb(130, 202, 7),
+            FieldTarget::Acceleration(_) => Color32::from_rgb(43, 134, 231),
+            FieldTarget::Gyroscope(0) => Color32::from_rgb(245, 146, 44),
+            FieldTarget::Gyroscope(1) => Color32::from_rgb(36, 178, 139),
+            FieldTarget::Gyroscope(_) => Color32::from_rgb(122, 96, 224),
+            FieldTarget::Extra(i) => PALETTE[i % PALETTE.len()],
+            _ => Color32::from_rgb(43, 134, 231),
+        };
+
+        lines.push(PlotLine {
+            name: field.label(),
+            color,
+            value: Box::new(|_, s| field.value(s)),
+        });
+
+        match field.target {
+            FieldTarget::Acceleration(2) => lines.extend(
+                vector_sum(schema, "Acceleration sum", Color32::from_rgb(195, 107, 176), FieldTarget::Acceleration)
+            ),
+            FieldTarget::Gyroscope(2) => lines.extend(
+                vector_sum(schema, "Gyroscope sum", Color32::from_rgb(214, 176, 38), FieldTarget::Gyroscope)
+            ),
+            _ => {}
+        }
+    }
+
+    lines
+}
+
+/// Lines of the channels derived from the pressure, if the schema has one
+fn altitude_lines<'a>(schema: &TelemetrySchema, altitude: &'a Altitude) -> Vec<PlotLine<'a>> {
+    if schema.field(FieldTarget::Pressure).is_none() {
+        return vec![];
+    }
+
+    vec![
+        PlotLine {
+            name: "Barometric altitude".to_owned(),
+            color: Color32::from_rgb(86, 180, 233),
+            value: Box::new(move |i, _| altitude.altitude[i]),
+        },
+        PlotLine {
+            name: "Vertical speed".to_owned(),
+            color: Color32::from_rgb(213, 94, 0),
+            value: Box::new(move |i, _| altitude.vertical_speed[i]),
+        },
+    ]
+}
+
+/// Lines of the computed channels, given with their values. Palette colours are picked from
+/// the end so that they differ from the ones of the extra schema fields.
+fn computed_lines(computed: &[(String, Vec<f64>)]) -> Vec<PlotLine<'_>> {
+    computed.iter().enumerate()
+        .map(|(i, (name, values))| PlotLine {
+            name: name.clone(),
+            color: PALETTE[PALETTE.len() - 1 - i % PALETTE.len()],
+            value: Box::new(move |i, _| values[i]),
+        })
+        .collect()
+}
+
+/// A note the user pinned to a moment of a session
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Annotation {
+    /// Name of the source the note belongs to
+    pub source: String,
+    pub session: usize,
+    /// Uptime in milliseconds
+    pub time: f64,
+    pub text: String,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct PlotTabState {
-    acceleration: [LineSettings; 3],
-    acceleration_sum: LineSettings,
-    temperature: LineSettings,
-    pressure: LineSettings,
+    lines: HashMap<String, LineSettings>,
+    annotations: Vec<Annotation>,
+    /// Where the next note will be pinned, picked by clicking the plot
+    #[serde(skip)]
+    annotation_time: Option<f64>,
+    #[serde(skip)]
+    annotation_text: String,
 
     hide_nans: bool,
+    /// Whether lost frames are marked with vertical lines
+    show_gaps: bool,
+    /// Whether launch, apogee, deployment and landing are marked with vertical lines
+    show_events: bool,
 
     filter_index_enabled: bool,
     filter_index_start: u32,
@@ -37,11 +166,13 @@ pub struct PlotTabState {
 impl Default for PlotTabState {
     fn default() -> Self {
         Self { 
-            acceleration: Default::default(), 
-            acceleration_sum: Default::default(),
-            temperature: Default::default(), 
-            pressure: Default::default(), 
+            lines: HashMap::new(),
+            annotations: vec![],
+            annotation_time: None,
+            annotation_text: String::new(),
             hide_nans: true,
+            show_gaps: false,
+            show_events: true,
             filter_index_enabled: false,
             filter_index_start: 0,
             filter_index_count: 200,
@@ -52,6 +183,14 @@ impl Default for PlotTabState {
     }
 }
 
+impl PlotTabState {
+    /// Whether a record passes the index and time filters
+    pub fn includes(&self, s: &SensedData) -> bool {
+        (!self.filter_index_enabled || (self.filter_index_start..=self.filter_index_start + self.filter_index_count).contains(&s.index))
+            && (!self.filter_time_enabled || (self.filter_time_start..=self.filter_time_end).contains(&(s.timestamp() / 1_000_000)))
+    }
+}
+
 fn duration_input(ui: &mut Ui, total: &mut u64) {
     ui.horizontal(|ui| {
         ui.spacing_mut().item_spacing.x = 1.0;
@@ -90,7 +229,34 @@ fn duration_input(ui: &mut Ui, total: &mut u64) {
     });
 }
 
-pub fn plot_tab(ui: &mut Ui, state: &mut PlotTabState, data: &[SensedData]) {
+/// Plots the sessions of one or more sources. Lines of different sources are
+/// told apart by their style and the source name in the legend. `events` are
+/// the flight events and `altitudes` the barometric altitude of each view.
+pub fn plot_tab(ui: &mut Ui, state: &mut PlotTabState, views: &[SessionView<'_>], events: &[Vec<FlightEvent>], altitudes: &[&Altitude], channels: &[ComputedChannel]) {
+    // Channels that can't be evaluated with the schema of a source are left out of its lines
+    let computed: Vec<Vec<(String, Vec<f64>)>> = views.iter()
+        .map(|view| channels.iter()
+            .filter_map(|channel| Some((channel.name.clone(), channel.evaluate(view.mission.schema(), view.session).ok()?)))
+            .collect())
+        .collect();
+    let source_lines: Vec<Vec<PlotLine<'_>>> = views.iter().zip(altitudes).zip(&computed)
+        .map(|((view, altitude), computed)| {
+            let mut lines = plot_lines(view.mission.schema());
+            lines.extend(altitude_lines(view.mission.schema(), altitude));
+            lines.extend(computed_lines(computed));
+            lines
+        })
+        .collect();
+
+    // Lines of all sources, each listed once
+    let mut line_names: Vec<&str> = vec![];
+    for line in source_lines.iter().flatten() {
+        if !line_names.contains(&line.name.as_str()) {
+            line_names.push(&line.name);
+        }
+    }
+
+    let record_count = views.iter().map(|view| view.session.len()).max().unwrap_or(0);
 
     fn line_config(ui: &mut Ui, text: impl Into<WidgetText>, adjust: &mut LineSettings) {
         ui.checkbox(&mut adjust.visible, text);
@@ -112,26 +278,31 @@ pub fn plot_tab(ui: &mut Ui, state: &mut PlotTabState, data: &[SensedData]) {
                 ui.label("Max");
                 ui.end_row();
     
-                line_config(ui, "Acceleration X", &mut state.acceleration[0]);
-                ui.end_row();
-                
-                line_config(ui, "Acceleration Y", &mut state.acceleration[1]);
-                ui.end_row();
-    
-                line_config(ui, "Acceleration Z", &mut state.acceleration[2]);
-                ui.end_row();
-    
-                line_config(ui, "Acceleration Sum", &mut state.acceleration_sum);
-                ui.end_row();
-    
-                line_config(ui, "Temperature", &mut state.temperature);
-                ui.end_row();
-                
-                line_config(ui, "Pressure", &mut state.pressure);
-                ui.end_row();
+                for name in &line_names {
+                    line_config(ui, *name, state.lines.entry(name.to_string()).or_default());
+                    ui.end_row();
+                }
             })
         });
 
+        if views.len() > 1 {
+            ui.separator();
+
+            CollapsingHeader::new("Sources").default_open(true).show(ui, |ui| {
+                for (i, view) in views.iter().enumerate() {
+                    let style = match source_line_style(i) {
+                        LineStyle::Solid => "solid",
+                        LineStyle::Dashed { .. } => "dashed",
+                        LineStyle::Dotted { .. } => "dotted",
+                    };
+                    ui.horizontal(|ui| {
+                        ui.colored_label(view.color, "⏺");
+                        ui.label(format!("{}: {} lines", view.name, style));
+                    });
+                }
+            });
+        }
+
         ui.separator();
 
         CollapsingHeader::new("Filter by index").default_open(true).show(ui, |ui| {
@@ -147,7 +318,7 @@ pub fn plot_tab(ui: &mut Ui, state: &mut PlotTabState, data: &[SensedData]) {
                 });
     
                 ui.spacing_mut().slider_width = 230.0;
-                ui.add(Slider::new(&mut state.filter_index_start, 0..=data.len() as u32));
+                ui.add(Slider::new(&mut state.filter_index_start, 0..=record_count as u32));
             });
         });
 
@@ -183,7 +354,46 @@ pub fn plot_tab(ui: &mut Ui, state: &mut PlotTabState, data: &[SensedData]) {
 
         ui.separator();
 
+        CollapsingHeader::new("Notes").default_open(false).show(ui, |ui| {
+            let first = views.first();
+
+            match state.annotation_time {
+                Some(time) => ui.label(format!("Add a note at {:.3} s", time / 1000.0)),
+                None => ui.weak("Click the plot to pick where to add a note"),
+            };
+            ui.horizontal(|ui| {
+                ui.add(egui::TextEdit::singleline(&mut state.annotation_text).desired_width(160.0));
+
+                let can_add = first.is_some() && state.annotation_time.is_some() && !state.annotation_text.trim().is_empty();
+                if let (true, Some(view), Some(time)) = (ui.add_enabled(can_add, egui::Button::new("Add")).clicked(), first, state.annotation_time) {
+                    state.annotations.push(Annotation {
+                        source: view.name.to_owned(),
+                        session: view.session_index,
+                        time,
+                        text: std::mem::take(&mut state.annotation_text),
+                    });
+                }
+            });
+
+            let mut removed = None;
+            for (i, annotation) in state.annotations.iter().enumerate() {
+                ui.horizontal(|ui| {
+                    if ui.small_button("✖").clicked() {
+                        removed = Some(i);
+                    }
+                    ui.label(format!("{} #{} at {:.3} s: {}", annotation.source, annotation.session, annotation.time / 1000.0, annotation.text));
+                });
+            }
+            if let Some(i) = removed {
+                state.annotations.remove(i);
+            }
+        });
+
+        ui.separator();
+
         ui.checkbox(&mut state.hide_nans, "Do not show missing data as gaps");
+        ui.checkbox(&mut state.show_gaps, "Mark lost frames");
+        ui.checkbox(&mut state.show_events, "Mark flight events");
 
         ui.with_layout(Layout::bottom_up(egui::Align::Min), |ui| {
             ui.label("Double-click the plot to reset view");
@@ -191,41 +401,103 @@ pub fn plot_tab(ui: &mut Ui, state: &mut PlotTabState, data: &[SensedData]) {
         });
     });
 
-    let line: &dyn for<'a> Fn(&'a str, Color32, &'a LineSettings, &'a dyn Fn(&SensedData) -> f64) -> Line<'a> = &|name, color, settings, processor| {
+    let filtered_out = |s: &SensedData| !state.includes(s);
+
+    let line = |plot_line: &PlotLine<'_>, data: &[SensedData], name: String, style: LineStyle| {
+        let default_settings = LineSettings::default();
+        let settings = state.lines.get(&plot_line.name).unwrap_or(&default_settings);
+
         Line::new(name, PlotPoints::new(
-            data.iter()
-                .filter_map(|s| {
-                    let value: f64 = processor(s);
+            data.iter().enumerate()
+                .filter_map(|(i, s)| {
+                    let value: f64 = (plot_line.value)(i, s);
 
-                    if (state.hide_nans && value.is_nan())
+                    if !settings.visible
+                        || (state.hide_nans && value.is_nan())
                         || (value.abs() < settings.min_absolute_value)
                         || (settings.max_absolute_value > 0.0 && value.abs() > settings.max_absolute_value)
-                        || (state.filter_index_enabled && !(state.filter_index_start..=state.filter_index_start + state.filter_index_count).contains(&s.index) )
-                        || (state.filter_time_enabled && !(state.filter_time_start..=state.filter_time_end).contains(&(s.uptime as u64 / 1000))) {
+                        || filtered_out(s) {
                         return None;
                     }
-                    Some([s.uptime as f64, value * settings.scale + settings.offset])
+                    Some([s.timestamp() as f64 / 1000.0, value * settings.scale + settings.offset])
                 })
                 .collect()
         ))
-        .color(color)
+        .color(plot_line.color)
+        .style(style)
     };
 
-    egui::CentralPanel::default().show_inside(ui, |ui| {
-        Plot::new("plot")
+    let clicked_at = egui::CentralPanel::default().show_inside(ui, |ui| {
+        let response = Plot::new("plot")
             .legend(Legend::default())
             .auto_bounds(Vec2b::new(true, true))
             .show(ui, |plot_ui| {
-                plot_ui.line(line("Acceleration X", Color32::from_rgb(239, 52, 80),  &state.acceleration[0], &|s| { s.acceleration[0] }));
-                plot_ui.line(line("Acceleration Y", Color32::from_rgb(130, 202, 7),  &state.acceleration[1], &|s| { s.acceleration[1] }));
-                plot_ui.line(line("Acceleration Z", Color32::from_rgb(43, 134, 231), &state.acceleration[2], &|s| { s.acceleration[2] }));
+                for (i, (view, lines)) in views.iter().zip(&source_lines).enumerate() {
+                    for plot_line in lines {
+                        let name = if views.len() > 1 {
+                            format!("{} · {}", view.name, plot_line.name)
+                        } else {
+                            plot_line.name.clone()
+                        };
+
+                        plot_ui.line(line(plot_line, view.session, name, source_line_style(i)));
+                    }
 
-                plot_ui.line(line("Acceleration sum", Color32::from_rgb(195, 107, 176), &state.acceleration_sum, &|s| { 
-                    (s.acceleration[0].powi(2) + s.acceleration[1].powi(2) + s.acceleration[2].powi(2)).sqrt()
-                }));
+                    if state.show_gaps {
+                        let name = if views.len() > 1 { format!("{} · lost frames", view.name) } else { "Lost frames".to_owned() };
 
-                plot_ui.line(line("Temperature", Color32::from_rgb(43, 134, 231), &state.temperature, &|s| { s.temperature.to_f64() }));
-                plot_ui.line(line("Pressure",    Color32::from_rgb(43, 134, 231), &state.pressure, &|s| { s.pressure.to_f64() }));
+                        for gap in find_gaps(view.session) {
+                            let before = &view.session[gap.before];
+                            if filtered_out(before) {
+                                continue;
+                            }
+
+                            plot_ui.vline(VLine::new(name.clone(), before.timestamp() as f64 / 1000.0)
+                                .color(view.color.gamma_multiply(0.6))
+                                .style(LineStyle::Dashed { length: 4.0 }));
+                        }
+                    }
+
+                    let top = plot_ui.plot_bounds().max()[1];
+
+                    if state.show_events {
+                        let name = if views.len() > 1 { format!("{} · flight events", view.name) } else { "Flight events".to_owned() };
+
+                        for event in &events[i] {
+                            let time = view.session[event.record].timestamp() as f64 / 1000.0;
+                            plot_ui.vline(VLine::new(name.clone(), time)
+                                .color(view.color)
+                                .style(LineStyle::Dotted { spacing: 4.0 }));
+                            plot_ui.text(Text::new(name.clone(), PlotPoint::new(time, top), format!(" {}", event.kind.name()))
+                                .anchor(egui::Align2::LEFT_TOP)
+                                .color(view.color));
+                        }
+                    }
+
+                    for annotation in state.annotations.iter().filter(|a| a.source == view.name && a.session == view.session_index) {
+                        plot_ui.vline(VLine::new("Notes", annotation.time).color(view.color));
+                        plot_ui.text(Text::new("Notes", PlotPoint::new(annotation.time, top), format!(" {}", annotation.text))
+                            .anchor(egui::Align2::LEFT_TOP)
+                            .color(view.color));
+                    }
+                }
+
+                plot_ui.pointer_coordinate()
             });
-    });
-}
\ No newline at end of file
+
+        response.response.clicked().then(|| response.inner.map(|pointer| pointer.x))
+    }).inner;
+
+    if let Some(time) = clicked_at {
+        state.annotation_time = time;
+    }
+}
+
+/// Style of the lines of the `i`-th shown source
+fn source_line_style(i: usize) -> LineStyle {
+    match i % 3 {
+        0 => LineStyle::Solid,
+        1 => LineStyle::Dashed { length: 10.0 },
+        _ => LineStyle::Dotted { spacing: 6.0 },
+    }
+}
@@ -0,0 +1,75 @@
+use egui::Ui;
+use egui_extras::{Column, TableBuilder};
+use serde::{Deserialize, Serialize};
+
+use crate::data::MissionData;
+
+#[derive(Serialize, Deserialize)]
+pub struct RejectedTabState {
+    pub stick_to_bottom: bool
+}
+
+pub fn rejected_tab(ui: &mut Ui, state: &mut RejectedTabState, mission: &MissionData) {
+    let text_height = egui::TextStyle::Body
+        .resolve(ui.style())
+        .size
+        .max(ui.spacing().interact_size.y);
+
+    ui.style_mut().wrap_mode = Some(egui::TextWrapMode::Extend);
+
+    let rejected = mission.rejected_lines();
+
+    egui::SidePanel::left("rejected_side_panel").show_inside(ui, |ui| {
+        ui.style_mut().wrap_mode = Some(egui::TextWrapMode::Extend);
+
+        ui.checkbox(&mut state.stick_to_bottom, "Stick to bottom");
+
+        ui.separator();
+
+        ui.heading("Stats");
+        ui.label(format!("Rejected: {}", mission.rejected_count()));
+        ui.label(format!("Showing last: {}", rejected.len()));
+
+        if let Some(last) = rejected.back() {
+            ui.label(format!("Last rejected: {}", last.recieved.format("%H:%M:%S")));
+        }
+    });
+
+    egui::CentralPanel::default().show_inside(ui, |ui| {
+        if rejected.is_empty() {
+            ui.heading("No rejected lines.");
+            return;
+        }
+
+        TableBuilder::new(ui)
+            .column(Column::auto().resizable(true))
+            .column(Column::auto().resizable(true))
+            .column(Column::auto().resizable(true))
+            .column(Column::remainder())
+            .stick_to_bottom(state.stick_to_bottom)
+            .header(20.0, |mut header| {
+                header.col(|ui| {ui.label("Line");});
+                header.col(|ui| {ui.label("Recieved");});
+                header.col(|ui| {ui.label("Error");});
+                header.col(|ui| {ui.label("Text");});
+            })
+            .body(|body| {
+                body.rows(text_height, rejected.len(), |mut row| {
+                    let line = &rejected[row.index()];
+
+                    row.col(|ui| {
+                        ui.weak(line.line_number.to_string());
+                    });
+                    row.col(|ui| {
+                        ui.label(line.recieved.format("%H:%M:%S%.3f").to_string());
+                    });
+                    row.col(|ui| {
+                        ui.label(line.error.to_string());
+                    });
+                    row.col(|ui| {
+                        ui.monospace(line.text.escape_debug().to_string());
+                    });
+                });
+            });
+    });
+}
@@ -0,0 +1,222 @@
+use chrono::{DateTime, Timelike};
+
+use crate::checksum::xor;
+
+/// A fix of an external GPS receiver
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TrackPoint {
+    /// UTC time as seconds since midnight
+    pub time: Option<f64>,
+    pub latitude: f64,
+    pub longitude: f64,
+    /// Metres above sea level, NaN if unknown
+    pub altitude: f64,
+}
+
+/// A GPS track recorded independently of the telemetry, shown for reference
+#[derive(Debug, Clone, PartialEq)]
+pub struct Track {
+    pub name: String,
+    pub points: Vec<TrackPoint>,
+}
+
+impl Track {
+    /// Reads a GPX file, or NMEA sentences if the text isn't GPX
+    pub fn parse(name: String, text: &str) -> Track {
+        let points = if text.contains("<gpx") { parse_gpx(text) } else { parse_nmea(text) };
+        Track { name, points }
+    }
+
+    /// Position at a time of day, interpolated between the two closest fixes
+    pub fn position_at(&self, time: f64) -> Option<TrackPoint> {
+        let timed: Vec<(f64, &TrackPoint)> = self.points.iter().filter_map(|p| Some((p.time?, p))).collect();
+        let after = timed.partition_point(|(t, _)| *t < time);
+
+        let (&(t1, p1), &(t0, p0)) = (timed.get(after)?, timed.get(after.checked_sub(1)?)?);
+        let f = if t1 > t0 { (time - t0) / (t1 - t0) } else { 0.0 };
+
+        Some(TrackPoint {
+            time: Some(time),
+            latitude: p0.latitude + (p1.latitude - p0.latitude) * f,
+            longitude: p0.longitude + (p1.longitude - p0.longitude) * f,
+            altitude: p0.altitude + (p1.altitude - p0.altitude) * f,
+        })
+    }
+}
+
+/// Seconds since midnight of a `gps_time` value sent by the probe as `HHMMSS`
+pub fn gps_time_seconds(gps_time: u32) -> Option<f64> {
+    let (hours, minutes, seconds) = (gps_time / 10000, gps_time / 100 % 100, gps_time % 100);
+    (hours < 24 && minutes < 60 && seconds < 60).then(|| (hours * 3600 + minutes * 60 + seconds) as f64)
+}
+
+/// Distance in metres between two positions given as latitude and longitude in degrees
+pub fn distance(a: [f64; 2], b: [f64; 2]) -> f64 {
+    const EARTH_RADIUS: f64 = 6_371_000.0;
+
+    let (lat_a, lat_b) = (a[0].to_radians(), b[0].to_radians());
+    let d_lat = lat_b - lat_a;
+    let d_lon = (b[1] - a[1]).to_radians();
+
+    let h = (d_lat / 2.0).sin().powi(2) + lat_a.cos() * lat_b.cos() * (d_lon / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS * h.sqrt().asin()
+}
+
+/// Reads the fixes of `GGA` and `RMC` sentences from any talker. Sentences with a wrong
+/// checksum or without a fix are skipped. A `GGA` and an `RMC` of the same second make one point.
+pub fn parse_nmea(text: &str) -> Vec<TrackPoint> {
+    let mut points: Vec<TrackPoint> = vec![];
+
+    for line in text.lines() {
+        let Some(sentence) = line.trim().strip_prefix('$') else {
+            continue;
+        };
+
+        let sentence = match sentence.split_once('*') {
+            Some((body, checksum)) => {
+                if u8::from_str_radix(checksum.trim(), 16) != Ok(xor(body.as_bytes())) {
+                    continue;
+                }
+                body
+            },
+            None => sentence,
+        };
+
+        let fields: Vec<&str> = sentence.split(',').collect();
+        let kind = fields[0].get(2..).unwrap_or_default();
+
+        let point = match kind {
+            // time, lat, N/S, lon, E/W, fix quality, satellites, HDOP, altitude, M
+            "GGA" if fields.len() > 9 && fields[6] != "0" => nmea_point(fields[1], fields[2], fields[3], fields[4], fields[5], fields[9].parse().ok()),
+            // time, status, lat, N/S, lon, E/W
+            "RMC" if fields.len() > 6 && fields[2] == "A" => nmea_point(fields[1], fields[3], fields[4], fields[5], fields[6], None),
+            _ => None,
+        };
+        let Some(point) = point else {
+            continue;
+        };
+
+        match points.last_mut() {
+            Some(last) if last.time.is_some() && last.time == point.time => {
+                if last.altitude.is_nan() {
+                    last.altitude = point.altitude;
+                }
+            },
+            _ => points.push(point),
+        }
+    }
+
+    points
+}
+
+fn nmea_point(time: &str, lat: &str, north: &str, lon: &str, east: &str, altitude: Option<f64>) -> Option<TrackPoint> {
+    /// Converts `ddmm.mmmm` to degrees
+    fn degrees(value: &str, degree_digits: usize) -> Option<f64> {
+        let degrees: f64 = value.get(..degree_digits)?.parse().ok()?;
+        let minutes: f64 = value.get(degree_digits..)?.parse().ok()?;
+        Some(degrees + minutes / 60.0)
+    }
+
+    let latitude = degrees(lat, 2)? * if north == "S" { -1.0 } else { 1.0 };
+    let longitude = degrees(lon, 3)? * if east == "W" { -1.0 } else { 1.0 };
+
+    let time = (|| {
+        let hours: f64 = time.get(0..2)?.parse().ok()?;
+        let minutes: f64 = time.get(2..4)?.parse().ok()?;
+        let seconds: f64 = time.get(4..)?.parse().ok()?;
+        Some(hours * 3600.0 + minutes * 60.0 + seconds)
+    })();
+
+    Some(TrackPoint { time, latitude, longitude, altitude: altitude.unwrap_or(f64::NAN) })
+}
+
+/// Reads the `trkpt` elements of a GPX file
+pub fn parse_gpx(text: &str) -> Vec<TrackPoint> {
+    fn attribute(tag: &str, name: &str) -> Option<f64> {
+        let start = tag.find(&format!("{name}="))? + name.len() + 1;
+        let quote = tag[start..].chars().next().filter(|c| matches!(c, '"' | '\''))?;
+        let value = &tag[start + 1..];
+        value[..value.find(quote)?].parse().ok()
+    }
+
+    fn element<'a>(body: &'a str, name: &str) -> Option<&'a str> {
+        let start = body.find(&format!("<{name}>"))? + name.len() + 2;
+        let end = body[start..].find(&format!("</{name}>"))?;
+        Some(body[start..start + end].trim())
+    }
+
+    text.split("<trkpt").skip(1)
+        .filter_map(|point| {
+            let tag = &point[..point.find('>')?];
+            let body = &point[..point.find("</trkpt>").unwrap_or(point.len())];
+
+            let time = element(body, "time")
+                .and_then(|time| DateTime::parse_from_rfc3339(time).ok())
+                .map(|time| time.to_utc())
+                .map(|time| time.num_seconds_from_midnight() as f64 + time.nanosecond() as f64 / 1e9);
+
+            Some(TrackPoint {
+                time,
+                latitude: attribute(tag, "lat")?,
+                longitude: attribute(tag, "lon")?,
+                altitude: element(body, "ele").and_then(|ele| ele.parse().ok()).unwrap_or(f64::NAN),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_nmea() {
+        let text = "\
+$GPGGA,110001.00,5213.7820,N,02100.7320,E,1,08,0.9,120.5,M,40.0,M,,*63
+$GPRMC,110001.00,A,5213.7820,N,02100.7320,E,0.5,90.0,170626,,,A*6A
+$GNRMC,110002.00,A,5213.7830,S,02100.7330,W,0.5,90.0,170626,,,A*78
+$GPGGA,110003.00,5213.7840,N,02100.7340,E,0,00,,,M,,M,,*7D
+$GPGGA,110004.00,5213.7850,N,02100.7350,E,1,08,0.9,121.0,M,40.0,M,,*00
+";
+        let points = parse_nmea(text);
+
+        assert_eq!(points.len(), 2);
+        assert_eq!(points[0].time, Some(39601.0));
+        assert!((points[0].latitude - 52.2297).abs() < 1e-9);
+        assert!((points[0].longitude - 21.0122).abs() < 1e-9);
+        assert_eq!(points[0].altitude, 120.5);
+
+        assert!(points[1].latitude < 0.0 && points[1].longitude < 0.0);
+        assert!(points[1].altitude.is_nan());
+    }
+
+    #[test]
+    fn test_parse_gpx() {
+        let text = r#"<?xml version="1.0"?>
+<gpx version="1.1"><trk><trkseg>
+  <trkpt lat="52.2297" lon="21.0122"><ele>120.5</ele><time>2026-06-17T11:00:01Z</time></trkpt>
+  <trkpt lon='21.0130' lat='52.2300'><time>2026-06-17T13:00:03+02:00</time></trkpt>
+</trkseg></trk></gpx>"#;
+        let track = Track::parse("handheld".to_owned(), text);
+
+        assert_eq!(track.points.len(), 2);
+        assert_eq!(track.points[0].altitude, 120.5);
+        assert_eq!(track.points[1].latitude, 52.2300);
+        assert_eq!(track.points[1].time, Some(39603.0));
+
+        let halfway = track.position_at(39602.0).unwrap();
+        assert!((halfway.latitude - 52.22985).abs() < 1e-9);
+        assert!(track.position_at(39600.0).is_none());
+
+        // Unquoted attributes are skipped rather than sliced apart
+        assert!(parse_gpx("<trkpt lat=é52.1é lon=«21.0»></trkpt>").is_empty());
+    }
+
+    #[test]
+    fn test_gps_time() {
+        assert_eq!(gps_time_seconds(110001), Some(39601.0));
+        assert_eq!(gps_time_seconds(0), Some(0.0));
+        assert_eq!(gps_time_seconds(996000), None);
+        assert!((distance([52.2297, 21.0122], [52.2397, 21.0122]) - 1112.0).abs() < 1.0);
+    }
+}
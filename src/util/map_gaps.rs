@@ -0,0 +1,25 @@
+use egui::{Align2, Color32, FontId, Stroke, Ui};
+use walkers::{Plugin, Position};
+
+/// Marks the positions where frames were lost, with the number of missing frames
+pub struct GapMarkersPlugin {
+    pub gaps: Vec<(Position, u32)>,
+    pub color: Color32
+}
+
+impl Plugin for GapMarkersPlugin {
+    fn run(self: Box<Self>, ui: &mut Ui, _response: &egui::Response, projector: &walkers::Projector, _memory: &walkers::MapMemory) {
+        for (position, missing) in self.gaps {
+            let projected = projector.project(position).to_pos2();
+
+            ui.painter().circle_stroke(projected, 7.0, Stroke::new(2.0, self.color));
+            ui.painter().text(
+                projected + egui::vec2(9.0, -9.0),
+                Align2::LEFT_BOTTOM,
+                format!("-{missing}"),
+                FontId::proportional(12.0),
+                self.color
+            );
+        }
+    }
+}
@@ -1 +1,2 @@
-pub(crate) mod map_trail;
\ No newline at end of file
+pub(crate) mod map_trail;
+pub(crate) mod map_gaps;